@@ -0,0 +1,144 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Compares two checksum logs, as written by `rename-me --checksum-log`, and
+//! reports the first step where they differ.
+
+use game::checksum::{self, Entry};
+
+const USAGE: &str = "Usage: checksum-diff <left log> <right log>";
+
+/// Exit codes, like `diff` uses.
+const SAME: i32 = 0;
+const DIFFERENT: i32 = 1;
+const TROUBLE: i32 = 2;
+
+fn load(path: &str) -> Result<Vec<Entry>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| format!("Could not read {}: {}", path, err))?;
+
+    checksum::parse_log(&text)
+        .map_err(|err| format!("Could not parse {}: {}", path, err))
+}
+
+fn describe(entry: Entry) -> String {
+    format!(
+        "board {} level {}, step {}: {:016x}",
+        game::seed::encode(entry.board.seed),
+        entry.board.level,
+        entry.step,
+        entry.checksum,
+    )
+}
+
+/// What to print, and the exit code. A log that stops partway through the
+/// other still counts as matching, since the two sides of a netplay session
+/// each stop logging at slightly different steps.
+fn report(
+    (left_path, left): (&str, &[Entry]),
+    (right_path, right): (&str, &[Entry]),
+) -> (String, i32) {
+    let Some(divergence) = checksum::first_divergence(left, right) else {
+        let (shorter_path, shorter, longer) = if left.len() <= right.len() {
+            (left_path, left, right)
+        } else {
+            (right_path, right, left)
+        };
+
+        let text = if shorter.len() == longer.len() {
+            format!("The logs match, for all {} steps.", shorter.len())
+        } else {
+            format!(
+                "The logs match for {} steps, then {} ends, {} steps before the other.",
+                shorter.len(),
+                shorter_path,
+                longer.len() - shorter.len(),
+            )
+        };
+
+        return (text, SAME)
+    };
+
+    let text = format!(
+        "The logs first differ at entry {}:\n  {}: {}\n  {}: {}",
+        divergence.index + 1,
+        left_path,
+        describe(divergence.left),
+        right_path,
+        describe(divergence.right),
+    );
+
+    (text, DIFFERENT)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [left_path, right_path] = args.as_slice() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(TROUBLE);
+    };
+
+    let (left, right) = match (load(left_path), load(right_path)) {
+        (Ok(left), Ok(right)) => (left, right),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(TROUBLE);
+        },
+    };
+
+    let (text, code) = report((left_path, &left), (right_path, &right));
+    println!("{}", text);
+    std::process::exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::checksum::BoardId;
+
+    fn entries(checksums: &[checksum::Checksum]) -> Vec<Entry> {
+        let board = BoardId { seed: [0x5C; 16], level: 1 };
+
+        (1..)
+            .zip(checksums)
+            .map(|(step, &checksum)| Entry { board, step, checksum })
+            .collect()
+    }
+
+    #[test]
+    fn matching_logs_are_reported_with_their_length() {
+        let log = entries(&[1, 2, 3]);
+
+        assert_eq!(
+            report(("a", &log), ("b", &log)),
+            ("The logs match, for all 3 steps.".to_string(), SAME)
+        );
+    }
+
+    #[test]
+    fn a_log_that_stops_early_still_matches() {
+        let long = entries(&[1, 2, 3, 4]);
+        let short = entries(&[1, 2]);
+
+        let expected = (
+            "The logs match for 2 steps, then b ends, 2 steps before the other.".to_string(),
+            SAME,
+        );
+        assert_eq!(report(("a", &long), ("b", &short)), expected);
+        assert_eq!(report(("b", &short), ("a", &long)), expected);
+    }
+
+    #[test]
+    fn differences_are_reported_with_both_entries() {
+        let (text, code) = report(
+            ("a", &entries(&[1, 2, 3])),
+            ("b", &entries(&[1, 0xFF, 3, 4])),
+        );
+
+        assert_eq!(code, DIFFERENT);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "The logs first differ at entry 2:");
+        assert!(lines[1].starts_with("  a: board ") && lines[1].ends_with("step 2: 0000000000000002"), "{}", lines[1]);
+        assert!(lines[2].starts_with("  b: board ") && lines[2].ends_with("step 2: 00000000000000ff"), "{}", lines[2]);
+    }
+}
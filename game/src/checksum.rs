@@ -0,0 +1,406 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Checksums of the parts of `State` that update steps depend on, so two runs
+//! that should match, like the two sides of a netplay session, can be checked,
+//! and the first step where they stopped matching found.
+//!
+//! The hash is FNV-1a over each field in little endian order, written out by
+//! hand rather than going through `core::hash::Hash`, since the output of that
+//! is allowed to differ between platforms and compiler versions.
+
+use crate::{
+    score::Ticks,
+    scene::LevelNumber,
+    seed,
+    tile,
+    Board,
+    Eye,
+    EyeState,
+    Seed,
+    State,
+    TileKind,
+};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+pub type Checksum = u64;
+
+struct Hasher(Checksum);
+
+impl Hasher {
+    const OFFSET_BASIS: Checksum = 0xcbf2_9ce4_8422_2325;
+    const PRIME: Checksum = 0x0000_0100_0000_01b3;
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= Checksum::from(byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn u8(&mut self, n: u8) {
+        self.bytes(&[n]);
+    }
+
+    fn u16(&mut self, n: u16) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.u8(u8::from(b));
+    }
+
+    /// Hashes whether there is a value, so `None` and `Some(0)` differ.
+    fn option<A>(&mut self, option: Option<A>, mut hash: impl FnMut(&mut Self, A)) {
+        match option {
+            Some(a) => {
+                self.u8(1);
+                hash(self, a);
+            },
+            None => self.u8(0),
+        }
+    }
+
+    fn tile_xy(&mut self, xy: tile::XY) {
+        self.u16(tile::xy_to_i(xy) as u16);
+    }
+
+    fn tile_kind(&mut self, kind: TileKind) {
+        use TileKind::*;
+        match kind {
+            Floor => self.u8(0),
+            Wall => self.u8(1),
+            Goal => self.u8(2),
+            Ice => self.u8(3),
+            Teleporter(id) => {
+                self.u8(4);
+                self.u8(id);
+            },
+            Arrow(dir, arrow_kind) => {
+                self.u8(5);
+                self.u8(dir as u8);
+                self.u8(arrow_kind as u8);
+            },
+        }
+    }
+
+    fn eye_state(&mut self, state: EyeState) {
+        use EyeState::*;
+        match state {
+            Idle => self.u8(0),
+            Moved(dir) => {
+                self.u8(1);
+                self.u8(dir as u8);
+            },
+            NarrowAnimLeft => self.u8(2),
+            NarrowAnimCenter => self.u8(3),
+            NarrowAnimRight => self.u8(4),
+            SmallPupil => self.u8(5),
+            Closed => self.u8(6),
+            HalfLid => self.u8(7),
+        }
+    }
+
+    fn eye(&mut self, eye: &Eye) {
+        self.tile_xy(eye.xy);
+        self.bytes(&eye.offset_xy.x.0.to_le_bytes());
+        self.bytes(&eye.offset_xy.y.0.to_le_bytes());
+        self.eye_state(eye.state);
+        self.option(eye.arriving, |h, dir| h.u8(dir as u8));
+        self.u16(eye.moves);
+        self.u16(eye.par_moves);
+        self.option(eye.finished_at, Self::u32);
+    }
+
+    fn board(&mut self, board: &Board) {
+        for tile_data in board.tiles.tiles.iter() {
+            self.tile_kind(tile_data.kind);
+        }
+
+        self.u8(board.players.count);
+        self.u8(board.players.mode as u8);
+        for eye in board.eyes() {
+            self.eye(eye);
+        }
+
+        self.bool(board.solved);
+        self.u32(board.ticks);
+        self.u32(board.steps);
+    }
+}
+
+/// There is no random number generator kept between update steps, since boards
+/// are generated up front, so the seed and level stand in for it.
+pub fn compute(state: &State) -> Checksum {
+    let mut hasher = Hasher(Hasher::OFFSET_BASIS);
+
+    hasher.bytes(&state.seed);
+    hasher.u8(state.level);
+    hasher.board(&state.board);
+    hasher.u16(state.animation_timer);
+
+    hasher.0
+}
+
+pub type Version = u32;
+
+pub const CURRENT_VERSION: Version = 1;
+
+/// Which board the entries after a `board` line in a log are for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BoardId {
+    pub seed: Seed,
+    pub level: LevelNumber,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub board: BoardId,
+    /// How many steps had been run on the board, including this one.
+    pub step: Ticks,
+    pub checksum: Checksum,
+}
+
+/// Logs start with this, followed by `write_board` and `write_entry` output.
+pub fn write_header(w: &mut dyn core::fmt::Write) -> core::fmt::Result {
+    writeln!(w, "version = {}", CURRENT_VERSION)?;
+    writeln!(w, "# board <seed> <level>, then <step> <checksum> lines")
+}
+
+pub fn write_board(w: &mut dyn core::fmt::Write, board: BoardId) -> core::fmt::Result {
+    writeln!(w, "board {} {}", seed::encode(board.seed), board.level)
+}
+
+pub fn write_entry(
+    w: &mut dyn core::fmt::Write,
+    step: Ticks,
+    checksum: Checksum,
+) -> core::fmt::Result {
+    writeln!(w, "{} {:016x}", step, checksum)
+}
+
+pub type LineNumber = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnknownVersion,
+    /// Boards have `board <seed> <level>`.
+    BadBoard,
+    BadSeed(seed::ParseError),
+    /// Entries have `<step> <checksum>`.
+    WrongFieldCount,
+    BadNumber,
+    /// Checksums are written in hexadecimal.
+    BadChecksum,
+    /// There was an entry before any `board` line.
+    NoBoard,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Starting from 1, like text editors do.
+    pub line: LineNumber,
+    pub kind: ParseErrorKind,
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use ParseErrorKind::*;
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            UnknownVersion => write!(f, "unknown version"),
+            BadBoard => write!(f, "expected `board <seed> <level>`"),
+            BadSeed(err) => write!(f, "bad seed: {}", err),
+            WrongFieldCount => write!(f, "expected `<step> <checksum>`"),
+            BadNumber => write!(f, "expected a whole number"),
+            BadChecksum => write!(f, "expected a hexadecimal checksum"),
+            NoBoard => write!(f, "expected a `board` line first"),
+        }
+    }
+}
+
+/// Blank lines and lines starting with `#` are ignored.
+#[cfg(feature = "alloc")]
+pub fn parse_log(text: &str) -> Result<Vec<Entry>, ParseError> {
+    let mut entries = Vec::new();
+    let mut board = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |kind| ParseError { line: line_number, kind };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+
+        if let Some((_, value)) = line.split_once('=') {
+            if value.trim().parse() != Ok(CURRENT_VERSION) {
+                return Err(error(ParseErrorKind::UnknownVersion))
+            }
+            continue
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if let ["board", rest @ ..] = fields.as_slice() {
+            let &[seed_code, level] = rest else {
+                return Err(error(ParseErrorKind::BadBoard))
+            };
+
+            board = Some(BoardId {
+                seed: seed::parse(seed_code)
+                    .map_err(|err| error(ParseErrorKind::BadSeed(err)))?,
+                level: level.parse()
+                    .map_err(|_| error(ParseErrorKind::BadNumber))?,
+            });
+            continue
+        }
+
+        let &[step, checksum] = fields.as_slice() else {
+            return Err(error(ParseErrorKind::WrongFieldCount))
+        };
+
+        entries.push(Entry {
+            board: board.ok_or(error(ParseErrorKind::NoBoard))?,
+            step: step.parse().map_err(|_| error(ParseErrorKind::BadNumber))?,
+            checksum: Checksum::from_str_radix(checksum, 16)
+                .map_err(|_| error(ParseErrorKind::BadChecksum))?,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Where two logs first differ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Starting from 0.
+    pub index: usize,
+    pub left: Entry,
+    pub right: Entry,
+}
+
+/// Logs are compared entry by entry, up to the end of the shorter one. Runs
+/// that are stopped at different times, like the two sides of a netplay
+/// session, which each log up to the last step they have the other's input
+/// for, are expected to have one log carry on past the other, so that doesn't
+/// count as a difference. Returns `None` if the logs match that far.
+pub fn first_divergence(left: &[Entry], right: &[Entry]) -> Option<Divergence> {
+    left.iter()
+        .zip(right)
+        .position(|(l, r)| l != r)
+        .map(|index| Divergence {
+            index,
+            left: left[index],
+            right: right[index],
+        })
+}
+
+/// The log grows for as long as the game runs, so it needs an allocator.
+#[cfg(feature = "alloc")]
+pub(crate) type Log = alloc::string::String;
+
+/// Whether there is a log in `state` for checksums to go in. Computing them
+/// takes a while, so they are only computed when they will be used.
+pub(crate) fn is_logging(state: &State) -> bool {
+    #[cfg(feature = "alloc")]
+    return state.checksum_log.is_some();
+    #[cfg(not(feature = "alloc"))]
+    {
+        let _ = state;
+        false
+    }
+}
+
+/// Appends to the log in `state`, if it is enabled.
+pub(crate) fn log_entry(state: &mut State, step: Ticks, checksum: Checksum) {
+    #[cfg(feature = "alloc")]
+    if let Some(log) = state.checksum_log.as_mut() {
+        // Writing to a `String` never fails.
+        let _ = write_entry(log, step, checksum);
+    }
+    #[cfg(not(feature = "alloc"))]
+    let _ = (state, step, checksum);
+}
+
+/// Appends a line saying which board the following entries are for, if the log
+/// in `state` is enabled.
+pub(crate) fn log_board(state: &mut State) {
+    #[cfg(feature = "alloc")]
+    if let Some(log) = state.checksum_log.as_mut() {
+        let board = BoardId { seed: state.seed, level: state.level };
+        // Writing to a `String` never fails.
+        let _ = write_board(log, board);
+    }
+    #[cfg(not(feature = "alloc"))]
+    let _ = state;
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_round_trip_and_report_the_first_divergence() {
+        let board = BoardId { seed: [0x5C; 16], level: 2 };
+
+        let mut text = String::new();
+        write_header(&mut text).unwrap();
+        write_board(&mut text, board).unwrap();
+        for step in 1..=3 {
+            write_entry(&mut text, step, Checksum::from(step) << 40).unwrap();
+        }
+
+        let left = parse_log(&text).unwrap();
+        assert_eq!(
+            left,
+            (1..=3)
+                .map(|step| Entry { board, step, checksum: Checksum::from(step) << 40 })
+                .collect::<Vec<_>>()
+        );
+
+        let mut right = left.clone();
+        right[1].checksum += 1;
+        assert_eq!(
+            first_divergence(&left, &right),
+            Some(Divergence { index: 1, left: left[1], right: right[1] })
+        );
+        assert_eq!(
+            first_divergence(&right[..1], &left),
+            None,
+            "the entries before the difference match"
+        );
+
+        assert_eq!(first_divergence(&left, &left[..2]), None);
+        assert_eq!(first_divergence(&[], &left), None);
+        assert_eq!(first_divergence(&left, &left), None);
+    }
+
+    #[test]
+    fn parse_errors_give_the_line_and_kind() {
+        use ParseErrorKind::*;
+
+        let board = "board 1HQY-C937-M2RE-9AD 1\n";
+        let cases = [
+            ("version = 99\n", 1, UnknownVersion),
+            ("version = 1\nboard 1HQY-C937-M2RE-9AD\n", 2, BadBoard),
+            ("board ! 1\n", 1, BadSeed(seed::ParseError::BadChar('!'))),
+            ("board 1HQY-C937-M2RE-9AD one\n", 1, BadNumber),
+            (&format!("{}1 0 0\n", board), 2, WrongFieldCount),
+            (&format!("{}\n# a comment\n-1 0\n", board), 4, BadNumber),
+            (&format!("{}1 0x12\n", board), 2, BadChecksum),
+            ("1 0853c3b79673f1e9\n", 1, NoBoard),
+        ];
+
+        for (text, line, kind) in cases {
+            assert_eq!(parse_log(text), Err(ParseError { line, kind }), "{:?}", text);
+        }
+    }
+}
@@ -1,8 +1,158 @@
 #![deny(unused)]
 #![deny(bindings_with_variant_name)]
 
-// In case we decide that we care about no_std/not allocating
-type StrBuf = String;
+/// Enough for any single line of text we draw, and the short strings, like
+/// menu labels, that we build them from.
+pub const STR_BUF_CAPACITY: usize = 256;
+
+/// Enough for the longest block of text we lay out, which is the inspector.
+pub const LONG_STR_BUF_CAPACITY: usize = 2048;
+
+/// A `String` with a fixed capacity, so text can be built without an
+/// allocator. Text that doesn't fit is cut off after the last whole `char`
+/// that does. Writing with `write!` returns an error when that happens, which
+/// is why most calls ignore the result: showing what fits is the best we can
+/// do anyway.
+#[derive(Clone)]
+pub struct StrBuf<const CAPACITY: usize = STR_BUF_CAPACITY> {
+    bytes: [u8; CAPACITY],
+    len: usize,
+}
+
+/// For blocks of text that get split into lines by the `text` module.
+pub type LongStrBuf = StrBuf<LONG_STR_BUF_CAPACITY>;
+
+impl<const CAPACITY: usize> Default for StrBuf<CAPACITY> {
+    fn default() -> Self {
+        Self {
+            bytes: [0; CAPACITY],
+            len: 0,
+        }
+    }
+}
+
+impl<const CAPACITY: usize> StrBuf<CAPACITY> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Used by the `str_buf!` macro, which is usually more convenient.
+    pub fn from_fmt(args: core::fmt::Arguments) -> Self {
+        let mut output = Self::new();
+        // Any text that doesn't fit is cut off.
+        let _ = core::fmt::Write::write_fmt(&mut output, args);
+        output
+    }
+
+    pub fn as_str(&self) -> &str {
+        // We only ever copy in whole `str`s, and only cut them at char
+        // boundaries, so this never fails.
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns `true` if all of `s` fit.
+    fn try_push_str(&mut self, s: &str) -> bool {
+        let mut end = core::cmp::min(s.len(), CAPACITY - self.len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        self.bytes[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+
+        end == s.len()
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.try_push_str(s);
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.try_push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.as_str().chars().next_back()?;
+        self.len -= c.len_utf8();
+        Some(c)
+    }
+}
+
+/// Like `format!`, but for `StrBuf`s of any capacity.
+macro_rules! str_buf {
+    ($($arg: tt)*) => {
+        $crate::draw::StrBuf::from_fmt(format_args!($($arg)*))
+    }
+}
+pub(crate) use str_buf;
+
+impl<const CAPACITY: usize> core::ops::Deref for StrBuf<CAPACITY> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const CAPACITY: usize> AsRef<str> for StrBuf<CAPACITY> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const CAPACITY: usize> From<&str> for StrBuf<CAPACITY> {
+    fn from(s: &str) -> Self {
+        let mut output = Self::new();
+        output.push_str(s);
+        output
+    }
+}
+
+impl<const CAPACITY: usize> core::fmt::Write for StrBuf<CAPACITY> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.try_push_str(s) {
+            Ok(())
+        } else {
+            Err(core::fmt::Error)
+        }
+    }
+}
+
+impl<const CAPACITY: usize> core::fmt::Display for StrBuf<CAPACITY> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<const CAPACITY: usize> core::fmt::Debug for StrBuf<CAPACITY> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const CAPACITY: usize> PartialEq for StrBuf<CAPACITY> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const CAPACITY: usize> Eq for StrBuf<CAPACITY> {}
+
+impl<const CAPACITY: usize> PartialEq<str> for StrBuf<CAPACITY> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const CAPACITY: usize> PartialEq<&str> for StrBuf<CAPACITY> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
 
 type PlayX = DrawLength;
 type PlayY = DrawLength;
@@ -30,6 +180,19 @@ pub struct BoardXYWH {
     pub h: BoardH,
 }
 
+type PanelX = DrawLength;
+type PanelY = DrawLength;
+type PanelW = DrawLength;
+type PanelH = DrawLength;
+
+#[derive(Clone, Debug, Default)]
+pub struct PanelXYWH {
+    pub x: PanelX,
+    pub y: PanelY,
+    pub w: PanelW,
+    pub h: PanelH,
+}
+
 pub type DrawX = DrawLength;
 pub type DrawY = DrawLength;
 
@@ -72,55 +235,136 @@ pub struct DrawWH {
 pub type TileCount = usize;
 pub type TileSideLength = DrawLength;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Orientation {
+    /// The UI panels are to the left and right of the board.
+    #[default]
+    Landscape,
+    /// The UI panels are above and below the board.
+    Portrait,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Sizes {
     pub draw_wh: DrawWH,
+    pub orientation: Orientation,
     pub play_xywh: PlayXYWH,
     pub board_xywh: BoardXYWH,
+    /// To the left of the board in landscape, and above it in portrait.
+    pub first_panel_xywh: PanelXYWH,
+    /// To the right of the board in landscape, and below it in portrait.
+    pub second_panel_xywh: PanelXYWH,
     pub tile_side_length: TileSideLength,
 }
 
-const LEFT_UI_WIDTH_TILES: TileCount = 9;
-const RIGHT_UI_WIDTH_TILES: TileCount = 9;
-const CENTER_UI_WIDTH_TILES: TileCount = if crate::tile::X::COUNT < crate::tile::Y::COUNT {
+const FIRST_UI_TILES: TileCount = 9;
+const SECOND_UI_TILES: TileCount = 9;
+const CENTER_UI_TILES: TileCount = if crate::tile::X::COUNT < crate::tile::Y::COUNT {
     crate::tile::X::COUNT as _
 } else {
     crate::tile::Y::COUNT as _
 };
-const DRAW_WIDTH_TILES: TileCount = LEFT_UI_WIDTH_TILES 
-    + CENTER_UI_WIDTH_TILES 
-    + RIGHT_UI_WIDTH_TILES;
+/// The length of the play area, in tiles, along the axis the panels are laid
+/// out on.
+const LONG_SIDE_TILES: TileCount = FIRST_UI_TILES
+    + CENTER_UI_TILES
+    + SECOND_UI_TILES;
 
-pub fn fresh_sizes(wh: DrawWH) -> Sizes {
-    let w_length_bound = wh.w / DRAW_WIDTH_TILES as DrawW;
-    let h_length_bound = wh.h / CENTER_UI_WIDTH_TILES as DrawH;
-
-    let (raw_bound, tile_side_length, board_x_offset, board_y_offset) = {
-        if (w_length_bound - h_length_bound).abs() < 0.5 {
-            (h_length_bound, h_length_bound.trunc(), h_length_bound.fract() / 2., h_length_bound.fract() / 2.)
-        } else if w_length_bound > h_length_bound {
-            (h_length_bound, h_length_bound.trunc(), 0., h_length_bound.fract() / 2.)
-        } else if w_length_bound < h_length_bound {
-            (w_length_bound, w_length_bound.trunc(), w_length_bound.fract() / 2., 0.)
-        } else {
-            // NaN ends up here
-            // TODO return a Result? Panic? Take only known non-NaN values?
-            (100., 100., 0., 0.)
+/// Larger than any screen we expect to see, but small enough that we don't need
+/// to worry much about floating point precision.
+pub const MAX_DRAW_LENGTH: DrawLength = 65536.;
+
+fn sanitize_length(length: DrawLength) -> DrawLength {
+    if length.is_nan() || length < 0. {
+        0.
+    } else if length > MAX_DRAW_LENGTH {
+        // Infinity ends up here.
+        MAX_DRAW_LENGTH
+    } else {
+        length
+    }
+}
+
+impl DrawWH {
+    /// Returns a version of this `DrawWH` with NaNs and negative lengths replaced
+    /// with zero, and lengths above `MAX_DRAW_LENGTH` clamped to it.
+    pub fn sanitized(self) -> Self {
+        Self {
+            w: sanitize_length(self.w),
+            h: sanitize_length(self.h),
         }
+    }
+}
+
+pub fn fresh_sizes(wh: DrawWH) -> Sizes {
+    let wh = wh.sanitized();
+
+    let landscape_bound = (wh.w / LONG_SIDE_TILES as DrawW)
+        .min(wh.h / CENTER_UI_TILES as DrawH);
+    let portrait_bound = (wh.w / CENTER_UI_TILES as DrawW)
+        .min(wh.h / LONG_SIDE_TILES as DrawH);
+
+    let (orientation, raw_bound) = if portrait_bound > landscape_bound {
+        (Orientation::Portrait, portrait_bound)
+    } else {
+        (Orientation::Landscape, landscape_bound)
     };
 
-    let play_area_w = raw_bound * DRAW_WIDTH_TILES as PlayW;
-    let play_area_h = raw_bound * CENTER_UI_WIDTH_TILES as PlayH;
+    // Whole pixel tiles keep the pixel art looking crisp.
+    let tile_side_length = crate::float::trunc(raw_bound);
+
+    let (play_area_w, play_area_h) = match orientation {
+        Orientation::Landscape => (
+            raw_bound * LONG_SIDE_TILES as PlayW,
+            raw_bound * CENTER_UI_TILES as PlayH,
+        ),
+        Orientation::Portrait => (
+            raw_bound * CENTER_UI_TILES as PlayW,
+            raw_bound * LONG_SIDE_TILES as PlayH,
+        ),
+    };
     let play_area_x = (wh.w - play_area_w) / 2.;
     let play_area_y = (wh.h - play_area_h) / 2.;
 
-    let board_area_w = tile_side_length * CENTER_UI_WIDTH_TILES as BoardW;
-    let board_area_h = tile_side_length * CENTER_UI_WIDTH_TILES as BoardH;
-    let board_area_x = play_area_x + board_x_offset + (play_area_w - board_area_w) / 2.;
-    let board_area_y = play_area_y + board_y_offset + (play_area_h - board_area_h) / 2.;
+    let board_area_w = tile_side_length * CENTER_UI_TILES as BoardW;
+    let board_area_h = tile_side_length * CENTER_UI_TILES as BoardH;
+    let board_area_x = play_area_x + (play_area_w - board_area_w) / 2.;
+    let board_area_y = play_area_y + (play_area_h - board_area_h) / 2.;
+
+    let (first_panel_xywh, second_panel_xywh) = match orientation {
+        Orientation::Landscape => (
+            PanelXYWH {
+                x: play_area_x,
+                y: play_area_y,
+                w: board_area_x - play_area_x,
+                h: play_area_h,
+            },
+            PanelXYWH {
+                x: board_area_x + board_area_w,
+                y: play_area_y,
+                w: (play_area_x + play_area_w) - (board_area_x + board_area_w),
+                h: play_area_h,
+            },
+        ),
+        Orientation::Portrait => (
+            PanelXYWH {
+                x: play_area_x,
+                y: play_area_y,
+                w: play_area_w,
+                h: board_area_y - play_area_y,
+            },
+            PanelXYWH {
+                x: play_area_x,
+                y: board_area_y + board_area_h,
+                w: play_area_w,
+                h: (play_area_y + play_area_h) - (board_area_y + board_area_h),
+            },
+        ),
+    };
 
     Sizes {
         draw_wh: wh,
+        orientation,
         play_xywh: PlayXYWH {
             x: play_area_x,
             y: play_area_y,
@@ -133,17 +377,25 @@ pub fn fresh_sizes(wh: DrawWH) -> Sizes {
             w: board_area_w,
             h: board_area_h,
         },
+        first_panel_xywh,
+        second_panel_xywh,
         tile_side_length,
     }
 }
 
-use crate::{ArrowKind, Dir};
+use crate::{ArrowKind, Dir, TeleporterId, text::{FontSize, HAlign, VAlign}};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum SpriteKind {
+    #[default]
     NeutralEye,
     DirEye(Dir),
     Arrow(Dir, ArrowKind),
+    Floor,
+    Wall,
+    Goal,
+    Ice,
+    Teleporter(TeleporterId),
     SmallPupilEye,
     NarrowLeftEye,
     NarrowCenterEye,
@@ -152,36 +404,401 @@ pub enum SpriteKind {
     HalfLidEye,
 }
 
-impl Default for SpriteKind {
-    fn default() -> Self {
-        Self::NeutralEye
-    }
-}
-
+// Boxing the text would need an allocator.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Command {
     Sprite(SpriteSpec),
     Text(TextSpec),
+    Rect(RectSpec),
+}
+
+/// Only needed so commands can be kept in a `storage::ArrayStorage`, which
+/// fills its unused slots with this.
+impl Default for Command {
+    fn default() -> Self {
+        Self::Sprite(<_>::default())
+    }
+}
+
+impl Command {
+    /// Platform layers should draw commands with lower layers first. Commands
+    /// with the same layer should be drawn in the order they were produced.
+    pub fn layer(&self) -> Layer {
+        match self {
+            Self::Sprite(s) => s.layer,
+            Self::Text(t) => t.layer,
+            Self::Rect(r) => r.layer,
+        }
+    }
+}
+
+pub type Layer = u8;
+
+pub const BOARD_LAYER: Layer = 0;
+pub const HIGHLIGHT_LAYER: Layer = 64;
+pub const EYE_LAYER: Layer = 128;
+pub const TEXT_LAYER: Layer = 192;
+/// For things drawn over the rest of the scene, like the pause menu.
+pub const OVERLAY_LAYER: Layer = 224;
+pub const OVERLAY_TEXT_LAYER: Layer = 240;
+/// For fading between scenes.
+pub const TRANSITION_LAYER: Layer = 255;
+
+/// In degrees, clockwise, around the center of the sprite.
+pub type Rotation = f32;
+
+/// A multiple of the tile side length.
+pub type Scale = f32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Flip {
+    #[default]
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl Flip {
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Self::Horizontal | Self::Both)
+    }
+
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Self::Vertical | Self::Both)
+    }
 }
 
 #[derive(Debug)]
 pub struct SpriteSpec {
     pub sprite: SpriteKind,
+    /// The top left corner of the tile the sprite is in. Scaling and rotation
+    /// happen around the center of that tile.
     pub xy: DrawXY,
+    /// Multiplied with the sprite's colours, so `Colour::WHITE` means no change.
+    pub tint: Colour,
+    pub rotation: Rotation,
+    pub scale: Scale,
+    /// Flipping happens before rotation.
+    pub flip: Flip,
+    pub layer: Layer,
+}
+
+impl Default for SpriteSpec {
+    fn default() -> Self {
+        Self {
+            sprite: <_>::default(),
+            xy: <_>::default(),
+            tint: Colour::WHITE,
+            rotation: 0.,
+            scale: 1.,
+            flip: <_>::default(),
+            layer: BOARD_LAYER,
+        }
+    }
+}
+
+pub type ColourComponent = u8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Colour {
+    pub r: ColourComponent,
+    pub g: ColourComponent,
+    pub b: ColourComponent,
+    pub a: ColourComponent,
+}
+
+macro_rules! colour {
+    ($r: literal, $g: literal, $b: literal) => {
+        Colour { r: $r, g: $g, b: $b, a: 255 }
+    }
+}
+
+impl Colour {
+    pub const WHITE: Self = colour!(0xee, 0xee, 0xee);
+    pub const GREY: Self = colour!(0x88, 0x88, 0x88);
+    pub const RED: Self = colour!(0xde, 0x3a, 0x3a);
+    pub const GREEN: Self = colour!(0x3a, 0xc2, 0x4a);
+    pub const BLUE: Self = colour!(0x4a, 0x7a, 0xde);
+    pub const YELLOW: Self = colour!(0xe8, 0xc8, 0x3a);
+    /// The same colour the platform layer clears the screen with.
+    pub const BACKGROUND: Self = colour!(0x22, 0x22, 0x22);
+
+    /// The names that can be used to refer to colours in text markup.
+    pub const NAMED: [(&'static str, Self); 6] = [
+        ("white", Self::WHITE),
+        ("grey", Self::GREY),
+        ("red", Self::RED),
+        ("green", Self::GREEN),
+        ("blue", Self::BLUE),
+        ("yellow", Self::YELLOW),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMED.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, colour)| *colour)
+    }
+}
+
+impl Default for Colour {
+    fn default() -> Self {
+        Self::WHITE
+    }
 }
 
 /// This is provided to make font selection etc. easier for platform layers.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TextKind {
     UI,
+    Title,
+    Body,
+    Debug,
+    Tooltip,
+    DamagePopup,
+    Menu,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    /// The size we'd like the text to be, as a multiple of the tile side length,
+    /// so that text scales along with the board.
+    pub size_per_tile: DrawLength,
+    pub colour: Colour,
+    pub h_align: HAlign,
+    pub v_align: VAlign,
+    /// Whether to interpret `{colour}...{/}` markup in the text. This is off for
+    /// text that may contain arbitrary braces, like `Debug` output.
+    pub markup: bool,
+}
+
+impl TextKind {
+    pub fn style(self) -> TextStyle {
+        use TextKind::*;
+
+        macro_rules! style {
+            ($size_per_tile: literal, $colour: ident, $h_align: ident, $v_align: ident, $markup: literal) => {
+                TextStyle {
+                    size_per_tile: $size_per_tile,
+                    colour: Colour::$colour,
+                    h_align: HAlign::$h_align,
+                    v_align: VAlign::$v_align,
+                    markup: $markup,
+                }
+            }
+        }
+
+        match self {
+            UI => style!(0.7, WHITE, Left, Top, true),
+            Title => style!(1.5, WHITE, Center, Middle, true),
+            Body => style!(0.6, WHITE, Left, Top, true),
+            Debug => style!(0.4, GREY, Left, Top, false),
+            Tooltip => style!(0.5, YELLOW, Center, Top, true),
+            DamagePopup => style!(0.9, RED, Center, Middle, true),
+            Menu => style!(0.8, WHITE, Center, Top, true),
+        }
+    }
+}
+
+/// A single line of text, in a single colour, already laid out by the `text`
+/// module. Platform layers should draw each glyph `advance` to the right of the
+/// previous one, rather than using the font's own spacing, so that every
+/// platform ends up with the same layout.
 #[derive(Debug)]
 pub struct TextSpec {
     pub text: StrBuf,
+    /// The top left corner of the first glyph.
     pub xy: DrawXY,
-    /// We'd rather define a rectangle for the text to (hopefully) lie inside than
-    /// a font size directly.
-    pub wh: DrawWH,
+    pub size: FontSize,
+    pub advance: DrawLength,
+    pub colour: Colour,
     pub kind: TextKind,
+    pub layer: Layer,
+}
+
+/// A filled rectangle, in a single colour.
+#[derive(Debug)]
+pub struct RectSpec {
+    pub xy: DrawXY,
+    pub wh: DrawWH,
+    pub colour: Colour,
+    pub layer: Layer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{xs_from_seed, xorshift, Xs};
+
+    const CASE_COUNT: usize = 4096;
+
+    /// Produces a mix of ordinary window sizes and arbitrary bit patterns, so we
+    /// cover NaNs, infinities, negative numbers and subnormals too.
+    fn arbitrary_length(rng: &mut Xs) -> DrawLength {
+        let bits = xorshift(rng);
+        match bits % 4 {
+            0 => f32::from_bits(xorshift(rng)),
+            1 => (xorshift(rng) % 64) as DrawLength,
+            _ => (xorshift(rng) % 8192) as DrawLength
+                + (xorshift(rng) % 1024) as DrawLength / 1024.,
+        }
+    }
+
+    fn arbitrary_wh(rng: &mut Xs) -> DrawWH {
+        DrawWH {
+            w: arbitrary_length(rng),
+            h: arbitrary_length(rng),
+        }
+    }
+
+    // Allow for rounding error.
+    const EPSILON: DrawLength = 1. / 64.;
+
+    fn assert_inside(
+        (inner_x, inner_y, inner_w, inner_h): (DrawX, DrawY, DrawW, DrawH),
+        (outer_x, outer_y, outer_w, outer_h): (DrawX, DrawY, DrawW, DrawH),
+        context: &dyn core::fmt::Debug,
+    ) {
+        assert!(
+            inner_x >= outer_x - EPSILON
+            && inner_y >= outer_y - EPSILON
+            && inner_x + inner_w <= outer_x + outer_w + EPSILON
+            && inner_y + inner_h <= outer_y + outer_h + EPSILON,
+            "{:?} is not inside {:?}\n{:?}",
+            (inner_x, inner_y, inner_w, inner_h),
+            (outer_x, outer_y, outer_w, outer_h),
+            context
+        );
+    }
+
+    fn for_each_arbitrary_sizes(mut check: impl FnMut(DrawWH, &Sizes)) {
+        let mut rng = xs_from_seed([0xF5; 16]);
+
+        for _ in 0..CASE_COUNT {
+            let wh = arbitrary_wh(&mut rng);
+            check(wh, &fresh_sizes(wh));
+        }
+    }
+
+    #[test]
+    fn fresh_sizes_produces_only_finite_non_negative_lengths() {
+        for_each_arbitrary_sizes(|wh, sizes| {
+            for length in [
+                sizes.draw_wh.w,
+                sizes.draw_wh.h,
+                sizes.play_xywh.w,
+                sizes.play_xywh.h,
+                sizes.board_xywh.w,
+                sizes.board_xywh.h,
+                sizes.first_panel_xywh.w,
+                sizes.first_panel_xywh.h,
+                sizes.second_panel_xywh.w,
+                sizes.second_panel_xywh.h,
+                sizes.tile_side_length,
+            ] {
+                assert!(length.is_finite(), "{:?}\n{:?}", wh, sizes);
+                assert!(length >= -EPSILON, "{:?}\n{:?}", wh, sizes);
+            }
+        });
+    }
+
+    #[test]
+    fn fresh_sizes_board_fits_inside_play_area_and_window() {
+        for_each_arbitrary_sizes(|wh, sizes| {
+            let board = (
+                sizes.board_xywh.x,
+                sizes.board_xywh.y,
+                sizes.board_xywh.w,
+                sizes.board_xywh.h,
+            );
+            let play = (
+                sizes.play_xywh.x,
+                sizes.play_xywh.y,
+                sizes.play_xywh.w,
+                sizes.play_xywh.h,
+            );
+            let window = (0., 0., sizes.draw_wh.w, sizes.draw_wh.h);
+
+            let context = (wh, sizes);
+            assert_inside(board, play, &context);
+            assert_inside(board, window, &context);
+            assert_inside(play, window, &context);
+        });
+    }
+
+    #[test]
+    fn fresh_sizes_panels_fit_inside_play_area_without_overlapping_the_board() {
+        for_each_arbitrary_sizes(|wh, sizes| {
+            let play = (
+                sizes.play_xywh.x,
+                sizes.play_xywh.y,
+                sizes.play_xywh.w,
+                sizes.play_xywh.h,
+            );
+
+            let context = (wh, sizes);
+            for panel in [&sizes.first_panel_xywh, &sizes.second_panel_xywh] {
+                assert_inside((panel.x, panel.y, panel.w, panel.h), play, &context);
+            }
+
+            let board = &sizes.board_xywh;
+            let (first, second) = (&sizes.first_panel_xywh, &sizes.second_panel_xywh);
+            match sizes.orientation {
+                Orientation::Landscape => {
+                    assert!(first.x + first.w <= board.x + EPSILON, "{:?}", context);
+                    assert!(second.x >= board.x + board.w - EPSILON, "{:?}", context);
+                },
+                Orientation::Portrait => {
+                    assert!(first.y + first.h <= board.y + EPSILON, "{:?}", context);
+                    assert!(second.y >= board.y + board.h - EPSILON, "{:?}", context);
+                },
+            }
+        });
+    }
+
+    #[test]
+    fn fresh_sizes_picks_the_orientation_that_matches_the_window() {
+        assert_eq!(
+            fresh_sizes(DrawWH { w: 1920., h: 1080. }).orientation,
+            Orientation::Landscape
+        );
+        assert_eq!(
+            fresh_sizes(DrawWH { w: 1080., h: 1920. }).orientation,
+            Orientation::Portrait
+        );
+    }
+
+    #[test]
+    fn fresh_sizes_sanitizes_invalid_lengths() {
+        let sizes = fresh_sizes(DrawWH { w: f32::NAN, h: -1. });
+        assert_eq!(sizes.draw_wh, DrawWH { w: 0., h: 0. });
+        assert_eq!(sizes.tile_side_length, 0.);
+
+        let sizes = fresh_sizes(DrawWH { w: f32::INFINITY, h: 1080. });
+        assert_eq!(sizes.draw_wh, DrawWH { w: MAX_DRAW_LENGTH, h: 1080. });
+    }
+
+    #[test]
+    fn str_bufs_cut_off_text_at_a_char_boundary() {
+        use core::fmt::Write;
+
+        let mut buf = StrBuf::<8>::new();
+        assert_eq!(write!(buf, "ab{}", 12), Ok(()));
+        assert_eq!(buf, "ab12");
+
+        // "é" takes two bytes, so only two fit in the four that are left.
+        assert!(write!(buf, "ééé").is_err());
+        assert_eq!(buf, "ab12éé");
+
+        buf.push('x');
+        assert_eq!(buf, "ab12éé");
+
+        assert_eq!(buf.pop(), Some('é'));
+        buf.push('x');
+        assert_eq!(buf, "ab12éx");
+
+        let formatted: StrBuf<4> = str_buf!("{}", 123456);
+        assert_eq!(formatted, "1234");
+    }
 }
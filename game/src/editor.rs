@@ -0,0 +1,107 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use crate::{
+    draw::StrBuf,
+    level,
+    tile,
+    InputFlags,
+    Tiles,
+    INPUT_UP_PRESSED,
+    INPUT_DOWN_PRESSED,
+    INPUT_LEFT_PRESSED,
+    INPUT_RIGHT_PRESSED,
+    INPUT_INTERACT_PRESSED,
+    INPUT_ROTATE_PRESSED,
+    INPUT_PLACE_START_PRESSED,
+};
+
+/// Which file a level gets saved to or loaded from. The platform layer decides
+/// what that means exactly.
+pub type LevelSlot = u8;
+
+pub const SLOT_COUNT: LevelSlot = 9;
+
+#[derive(Debug)]
+pub struct Editor {
+    pub cursor: tile::XY,
+    pub start: tile::XY,
+    pub tiles: Tiles,
+    /// From 1 to `SLOT_COUNT`.
+    pub slot: LevelSlot,
+    /// Shown to the player, to say what happened last, like a save failing.
+    pub status: StrBuf,
+    pub solution: level::Solution,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        let tiles = Tiles::default();
+        let start = tile::XY::default();
+        let solution = level::solve(&tiles.tiles, start);
+
+        Self {
+            cursor: <_>::default(),
+            start,
+            tiles,
+            slot: 1,
+            status: <_>::default(),
+            solution,
+        }
+    }
+}
+
+impl Editor {
+    pub fn next_slot(&mut self) {
+        self.slot = self.slot % SLOT_COUNT + 1;
+    }
+
+    /// Replaces the level being edited.
+    pub fn set_level(&mut self, tiles: Tiles, start: tile::XY) {
+        self.tiles = tiles;
+        self.start = start;
+        self.cursor = start;
+        self.resolve();
+    }
+
+    /// Should be called after any change to the level.
+    fn resolve(&mut self) {
+        self.solution = level::solve(&self.tiles.tiles, self.start);
+    }
+
+    /// Handles the editing input. Should be called once per frame.
+    pub fn update(&mut self, input_flags: InputFlags) {
+        if input_flags & INPUT_UP_PRESSED != 0 {
+            self.cursor.move_up();
+        }
+        if input_flags & INPUT_DOWN_PRESSED != 0 {
+            self.cursor.move_down();
+        }
+        if input_flags & INPUT_LEFT_PRESSED != 0 {
+            self.cursor.move_left();
+        }
+        if input_flags & INPUT_RIGHT_PRESSED != 0 {
+            self.cursor.move_right();
+        }
+
+        let tile_data = &mut self.tiles.tiles[tile::xy_to_i(self.cursor)];
+
+        let mut changed = false;
+        if input_flags & INPUT_INTERACT_PRESSED != 0 {
+            tile_data.kind = tile_data.kind.cycled(true);
+            changed = true;
+        }
+        if input_flags & INPUT_ROTATE_PRESSED != 0 {
+            tile_data.kind = tile_data.kind.rotated();
+            changed = true;
+        }
+        if input_flags & INPUT_PLACE_START_PRESSED != 0 {
+            self.start = self.cursor;
+            changed = true;
+        }
+
+        if changed {
+            self.resolve();
+        }
+    }
+}
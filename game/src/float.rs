@@ -0,0 +1,53 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Rounding functions for `f32`s. `f32::trunc` and friends come from `std`,
+//! since they usually call into the platform's maths library, so we have our
+//! own, which also means they give the same results on every platform.
+
+/// Every `f32` with at least this magnitude is already a whole number.
+const FIRST_WITHOUT_FRACTION: f32 = 8_388_608.; // 2 ^ 23
+
+/// Rounds towards zero. NaNs and infinities are returned as is.
+pub fn trunc(x: f32) -> f32 {
+    // Written this way so NaNs end up in the `else` branch.
+    if x.abs() < FIRST_WITHOUT_FRACTION {
+        // The magnitude is small enough that this cast is exact, apart from
+        // dropping the fraction, which is what we want.
+        x as i32 as f32
+    } else {
+        x
+    }
+}
+
+/// Rounds towards negative infinity. NaNs and infinities are returned as is.
+pub fn floor(x: f32) -> f32 {
+    let truncated = trunc(x);
+    if truncated > x {
+        truncated - 1.
+    } else {
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn these_match_std() {
+        let cases = [
+            0., -0., 0.5, -0.5, 1., -1., 1.5, -1.5, 2.999, -2.999,
+            8_388_607.5, -8_388_607.5, 1e10, -1e10, f32::MIN_POSITIVE,
+            f32::MAX, f32::MIN, f32::INFINITY, f32::NEG_INFINITY,
+        ];
+
+        for x in cases {
+            assert_eq!(trunc(x), x.trunc(), "trunc({})", x);
+            assert_eq!(floor(x), x.floor(), "floor({})", x);
+        }
+
+        assert!(trunc(f32::NAN).is_nan());
+        assert!(floor(f32::NAN).is_nan());
+    }
+}
@@ -1,6 +1,10 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![deny(unused)]
 #![deny(bindings_with_variant_name)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 #[allow(unused)]
 macro_rules! compile_time_assert {
     ($assertion: expr) => {{
@@ -10,7 +14,8 @@ macro_rules! compile_time_assert {
     }}
 }
 
-// In case we decide that we care about no_std/not directly allocating ourself
+/// Where `update` puts the draw commands, so the platform layer can choose how
+/// they are stored, and the game doesn't need to allocate.
 pub trait ClearableStorage<A> {
     fn clear(&mut self);
 
@@ -50,7 +55,6 @@ fn xs_shuffle<A>(rng: &mut Xs, slice: &mut [A]) {
     }
 }
 
-#[allow(unused)]
 fn new_seed(rng: &mut Xs) -> Seed {
     let s0 = xorshift(rng).to_le_bytes();
     let s1 = xorshift(rng).to_le_bytes();
@@ -104,6 +108,47 @@ pub struct XY {
 }
 
 pub mod draw;
+use draw::str_buf;
+
+pub mod text;
+
+pub mod storage;
+
+mod float;
+
+pub mod profile;
+
+pub mod level;
+
+pub mod seed;
+
+pub mod score;
+
+pub mod players;
+pub use players::{MAX_PLAYERS, PlayerIndex};
+
+#[cfg(feature = "alloc")]
+pub mod netplay;
+
+pub mod checksum;
+
+#[cfg(any(feature = "fuzzing", all(test, feature = "alloc")))]
+pub mod invariants;
+
+mod level_file;
+pub use level_file::ParseError as LevelParseError;
+
+mod editor;
+pub use editor::LevelSlot;
+
+mod scene;
+pub use scene::LevelNumber;
+
+pub mod settings;
+pub use settings::Settings;
+
+#[cfg(feature = "debug-hud")]
+mod inspector;
 
 pub use draw::{
     DrawLength,
@@ -275,7 +320,6 @@ mod tile {
         }
     }
 
-    #[allow(unused)]
     pub fn xy_to_i(xy: XY) -> usize {
         xy_to_i_usize((usize::from(xy.x.0), usize::from(xy.y.0)))
     }
@@ -301,15 +345,30 @@ mod tile {
     }
 }
 
-fn draw_xy_from_tile(sizes: &Sizes, txy: tile::XY) -> DrawXY {
+/// Returns the top left corner of where an unscaled, tile-sized sprite would go,
+/// such that after scaling it by `camera.zoom` around its center, it covers the
+/// given tile.
+fn draw_xy_from_tile(sizes: &Sizes, camera: &camera::Camera, txy: tile::XY) -> DrawXY {
+    let zoomed_side_length = sizes.tile_side_length * camera.zoom;
+    let half_side_length = sizes.tile_side_length / 2.;
+
+    let tile_center = camera::XY {
+        x: tile::Coord::from(txy.x) as camera::Length + 0.5,
+        y: tile::Coord::from(txy.y) as camera::Length + 0.5,
+    };
+
     DrawXY {
-        x: sizes.board_xywh.x + sizes.board_xywh.w * (tile::Coord::from(txy.x) as DrawLength / tile::X::COUNT as DrawLength),
-        y: sizes.board_xywh.y + sizes.board_xywh.h * (tile::Coord::from(txy.y) as DrawLength / tile::Y::COUNT as DrawLength),
+        x: sizes.board_xywh.x + sizes.board_xywh.w / 2.
+        + (tile_center.x - camera.center.x) * zoomed_side_length
+        - half_side_length,
+        y: sizes.board_xywh.y + sizes.board_xywh.h / 2.
+        + (tile_center.y - camera.center.y) * zoomed_side_length
+        - half_side_length,
     }
 }
 
-fn draw_xy_from_offset(sizes: &Sizes, o_xy: offset::XY) -> DrawXY {
-    let offset_size = sizes.tile_side_length / TILE_OFFSET as DrawLength;
+fn draw_xy_from_offset(sizes: &Sizes, camera: &camera::Camera, o_xy: offset::XY) -> DrawXY {
+    let offset_size = sizes.tile_side_length * camera.zoom / TILE_OFFSET as DrawLength;
 
     DrawXY {
         x: o_xy.x.0 as DrawLength * offset_size,
@@ -317,29 +376,111 @@ fn draw_xy_from_offset(sizes: &Sizes, o_xy: offset::XY) -> DrawXY {
     }
 }
 
+/// Returns whether a sprite positioned with `draw_xy_from_tile` would lie
+/// entirely inside the board area. We don't draw sprites that wouldn't, so they
+/// don't end up on top of the UI when zoomed in.
+fn is_on_board(sizes: &Sizes, camera: &camera::Camera, xy: DrawXY) -> bool {
+    // Allow for rounding error.
+    const EPSILON: DrawLength = 1. / 64.;
+
+    let half_side_length = sizes.tile_side_length / 2.;
+    let half_zoomed_side_length = half_side_length * camera.zoom;
+
+    let center_x = xy.x + half_side_length;
+    let center_y = xy.y + half_side_length;
+
+    center_x - half_zoomed_side_length >= sizes.board_xywh.x - EPSILON
+    && center_x + half_zoomed_side_length <= sizes.board_xywh.x + sizes.board_xywh.w + EPSILON
+    && center_y - half_zoomed_side_length >= sizes.board_xywh.y - EPSILON
+    && center_y + half_zoomed_side_length <= sizes.board_xywh.y + sizes.board_xywh.h + EPSILON
+}
+
 /// A Tile should always be at a particular position, but that position should be
 /// derivable from the tiles location in the tiles array, so it doesn't need to be
 /// stored. But, we often want to get the tile's data and it's location as a single
 /// thing. This is why we have both `Tile` and `TileData`
 #[derive(Copy, Clone, Debug, Default)]
 struct TileData {
-    dir: Dir,
-    arrow_kind: ArrowKind,
+    kind: TileKind,
 }
 
 impl TileData {
-    fn from_rng(rng: &mut Xs) -> Self {
-        Self {
-            dir: Dir::from_rng(rng),
-            arrow_kind: ArrowKind::from_rng(rng),
+    fn sprite(&self) -> SpriteKind {
+        use TileKind::*;
+        match self.kind {
+            Floor => SpriteKind::Floor,
+            Wall => SpriteKind::Wall,
+            Goal => SpriteKind::Goal,
+            Ice => SpriteKind::Ice,
+            Teleporter(id) => SpriteKind::Teleporter(id),
+            Arrow(dir, arrow_kind) => SpriteKind::Arrow(dir, arrow_kind),
         }
     }
+}
 
-    fn sprite(&self) -> SpriteKind {
-        SpriteKind::Arrow(self.dir, self.arrow_kind)
+pub type TeleporterId = u8;
+
+/// How many different pairs of teleporters a board can have.
+pub const TELEPORTER_PAIR_COUNT: TeleporterId = 4;
+
+/// See the `level` module for the details of how each kind affects movement.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum TileKind {
+    #[default]
+    Floor,
+    Wall,
+    Goal,
+    Ice,
+    Teleporter(TeleporterId),
+    Arrow(Dir, ArrowKind),
+}
+
+/// Arrows are represented by one for each `ArrowKind`, since the direction is
+/// changed separately.
+const TILE_KINDS: [TileKind; 10] = [
+    TileKind::Floor,
+    TileKind::Wall,
+    TileKind::Goal,
+    TileKind::Ice,
+    TileKind::Teleporter(0),
+    TileKind::Teleporter(1),
+    TileKind::Teleporter(2),
+    TileKind::Teleporter(3),
+    TileKind::Arrow(Dir::Up, ArrowKind::Red),
+    TileKind::Arrow(Dir::Up, ArrowKind::Green),
+];
+
+impl TileKind {
+    /// The next or previous kind in `TILE_KINDS`. Arrows keep their direction.
+    fn cycled(self, forward: bool) -> Self {
+        let (current, dir) = match self {
+            Self::Arrow(dir, kind) => (Self::Arrow(Dir::Up, kind), dir),
+            kind => (kind, Dir::Up),
+        };
+
+        match cycle(&TILE_KINDS, current, forward) {
+            Self::Arrow(_, kind) => Self::Arrow(dir, kind),
+            kind => kind,
+        }
+    }
+
+    /// Turns arrows clockwise by one step. Other kinds don't change.
+    fn rotated(self) -> Self {
+        match self {
+            Self::Arrow(dir, kind) => Self::Arrow(cycle(&Dir::ALL, dir, true), kind),
+            kind => kind,
+        }
     }
 }
 
+/// Cycles `current` forward or backward through `all`.
+fn cycle<A: Copy + PartialEq>(all: &[A], current: A, forward: bool) -> A {
+    let len = all.len();
+    let i = all.iter().position(|&a| a == current).unwrap_or(0);
+
+    all[if forward { (i + 1) % len } else { (i + len - 1) % len }]
+}
+
 pub const TILES_LENGTH: usize = tile::XY::COUNT as _;
 
 type TileDataArray = [TileData; TILES_LENGTH as _];
@@ -357,21 +498,7 @@ impl Default for Tiles {
     }
 }
 
-impl Tiles {
-    fn from_rng(rng: &mut Xs) -> Self {
-        let mut tiles = [TileData::default(); TILES_LENGTH as _];
-
-        for tile_data in tiles.iter_mut() {
-            *tile_data = TileData::from_rng(rng);
-        }
-
-        Self {
-            tiles
-        }
-    }
-}
-
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum EyeState {
     Idle,
     Moved(Dir),
@@ -534,31 +661,352 @@ mod offset {
 
 const TILE_OFFSET: offset::Offset = 16;
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 struct Eye {
     xy: tile::XY,
     offset_xy: offset::XY,
     state: EyeState,
+    /// The direction of the move the eye is partway through, if any. We wait
+    /// until the move has finished to slide or teleport, so those are visible.
+    arriving: Option<Dir>,
+    moves: level::MoveCount,
+    /// The fewest moves it takes to get from where this eye started to a goal.
+    par_moves: level::MoveCount,
+    /// When the eye reached a goal, after which it stops moving.
+    finished_at: Option<score::Ticks>,
 }
 
-#[derive(Debug, Default)]
+impl Eye {
+    /// Moves to `xy`, with the offset set so the eye will appear to move there
+    /// from where it was.
+    fn move_to(&mut self, xy: tile::XY) {
+        let delta = |from: tile::Coord, to: tile::Coord| {
+            (from as offset::Offset - to as offset::Offset) * TILE_OFFSET
+        };
+
+        self.offset_xy = o_xy!{
+            delta(self.xy.x.into(), xy.x.into()),
+            delta(self.xy.y.into(), xy.y.into()),
+        };
+        self.xy = xy;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 struct Board {
     tiles: Tiles,
-    eye: Eye,
+    /// Only the first `players.count` are used.
+    eyes: [Eye; MAX_PLAYERS],
+    players: players::Setup,
+    metrics: level::Metrics,
+    /// Set once enough eyes have reached a goal, depending on the mode, after
+    /// which they all stop moving.
+    solved: bool,
+    /// How long the board has been played for, not counting time paused.
+    ticks: score::Ticks,
+    /// How many update steps have been run on the board. Unlike `ticks`, this
+    /// keeps going after the board is solved.
+    steps: score::Ticks,
 }
 
 impl Board {
-    fn from_seed(seed: Seed) -> Self {
-        let mut rng = xs_from_seed(seed);
+    fn from_seed(
+        seed: Seed,
+        difficulty: level::Difficulty,
+        players: players::Setup,
+    ) -> Self {
+        let level = level::generate(seed, difficulty);
+
+        let mut board = Self {
+            tiles: level.tiles,
+            players,
+            metrics: level.metrics,
+            ..<_>::default()
+        };
+        board.eyes[0] = Eye {
+            xy: level.start,
+            par_moves: level.metrics.min_moves,
+            ..<_>::default()
+        };
 
-        let tiles = Tiles::from_rng(&mut rng);
+        let mut other_starts = [None; MAX_PLAYERS - 1];
+        let other_count = board.eyes().len() - 1;
+        level::other_starts(
+            &board.tiles.tiles,
+            level.start,
+            level.metrics.min_moves,
+            &mut other_starts[..other_count],
+        );
+        for (eye, start) in board.eyes[1..].iter_mut().zip(other_starts) {
+            // If there's nowhere else to go, sharing the start is better than
+            // not playing.
+            let (xy, par_moves) = start.unwrap_or((level.start, level.metrics.min_moves));
+            *eye = Eye {
+                xy,
+                par_moves,
+                ..<_>::default()
+            };
+        }
 
-        Self {
-            tiles,
-            eye: Eye {
-                xy: tile::XY::from_rng(&mut rng),
+        board
+    }
+
+    fn eyes(&self) -> &[Eye] {
+        let count = usize::from(self.players.count).clamp(1, MAX_PLAYERS);
+        &self.eyes[..count]
+    }
+
+    /// How the given player did, if they finished.
+    fn entry(
+        &self,
+        seed: Seed,
+        level: scene::LevelNumber,
+        player: PlayerIndex,
+    ) -> Option<score::Entry> {
+        let eye = self.eyes().get(player)?;
+
+        eye.finished_at.map(|ticks| score::Entry {
+            seed,
+            level,
+            moves: eye.moves,
+            par_moves: eye.par_moves,
+            ticks,
+        })
+    }
+}
+
+mod camera {
+    use crate::{
+        offset,
+        tile,
+        InputFlags,
+        INPUT_PAN_UP_DOWN,
+        INPUT_PAN_DOWN_DOWN,
+        INPUT_PAN_LEFT_DOWN,
+        INPUT_PAN_RIGHT_DOWN,
+        TILE_OFFSET,
+    };
+
+    /// Measured in tiles.
+    pub type Length = f32;
+
+    /// Measured in tiles, from the top left corner of the board.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct XY {
+        pub x: Length,
+        pub y: Length,
+    }
+
+    impl XY {
+        pub fn from_eye(xy: tile::XY, o_xy: offset::XY) -> Self {
+            Self {
+                x: tile::Coord::from(xy.x) as Length
+                    + 0.5
+                    + o_xy.x.0 as Length / TILE_OFFSET as Length,
+                y: tile::Coord::from(xy.y) as Length
+                    + 0.5
+                    + o_xy.y.0 as Length / TILE_OFFSET as Length,
+            }
+        }
+    }
+
+    /// How many times bigger than usual things look. At the minimum zoom, the
+    /// whole board is visible.
+    pub type Zoom = f32;
+
+    pub const MIN_ZOOM: Zoom = 1.;
+    pub const MAX_ZOOM: Zoom = 4.;
+    const ZOOM_STEP: Zoom = 1.;
+
+    const BOARD_W: Length = tile::X::COUNT as Length;
+    const BOARD_H: Length = tile::Y::COUNT as Length;
+
+    /// The fraction of the remaining distance to the target covered each update.
+    const FOLLOW_RATE: f32 = 1. / 32.;
+    /// In tiles per update.
+    const PAN_SPEED: Length = 1. / 32.;
+
+    #[derive(Debug)]
+    pub struct Camera {
+        pub center: XY,
+        pub zoom: Zoom,
+        pub target_zoom: Zoom,
+        /// Added to the eye's position to get the position the camera follows.
+        pub pan: XY,
+    }
+
+    impl Default for Camera {
+        fn default() -> Self {
+            Self {
+                center: XY {
+                    x: BOARD_W / 2.,
+                    y: BOARD_H / 2.,
+                },
+                zoom: MIN_ZOOM,
+                target_zoom: MIN_ZOOM,
+                pan: XY::default(),
+            }
+        }
+    }
+
+    impl Camera {
+        pub fn zoom_in(&mut self) {
+            self.target_zoom = (self.target_zoom + ZOOM_STEP).min(MAX_ZOOM);
+        }
+
+        pub fn zoom_out(&mut self) {
+            self.target_zoom = (self.target_zoom - ZOOM_STEP).max(MIN_ZOOM);
+        }
+
+        pub fn step(&mut self, eye: XY, input_flags: InputFlags) {
+            if input_flags & INPUT_PAN_UP_DOWN != 0 {
+                self.pan.y -= PAN_SPEED;
+            }
+            if input_flags & INPUT_PAN_DOWN_DOWN != 0 {
+                self.pan.y += PAN_SPEED;
+            }
+            if input_flags & INPUT_PAN_LEFT_DOWN != 0 {
+                self.pan.x -= PAN_SPEED;
+            }
+            if input_flags & INPUT_PAN_RIGHT_DOWN != 0 {
+                self.pan.x += PAN_SPEED;
+            }
+
+            // There's no point letting the pan get further than this, since
+            // the center gets clamped to the board anyway.
+            self.pan.x = self.pan.x.clamp(-BOARD_W, BOARD_W);
+            self.pan.y = self.pan.y.clamp(-BOARD_H, BOARD_H);
+
+            self.zoom += (self.target_zoom - self.zoom) * FOLLOW_RATE;
+            if (self.target_zoom - self.zoom).abs() < 1. / 256. {
+                self.zoom = self.target_zoom;
+            }
+
+            self.center.x += (eye.x + self.pan.x - self.center.x) * FOLLOW_RATE;
+            self.center.y += (eye.y + self.pan.y - self.center.y) * FOLLOW_RATE;
+
+            // Keep the visible area inside the board.
+            let half_visible_w = BOARD_W / (2. * self.zoom);
+            let half_visible_h = BOARD_H / (2. * self.zoom);
+            self.center.x = self.center.x
+                .max(half_visible_w)
+                .min(BOARD_W - half_visible_w);
+            self.center.y = self.center.y
+                .max(half_visible_h)
+                .min(BOARD_H - half_visible_h);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::INPUT_PAN_UP_DOWN;
+
+        /// Longer than any of the camera's movements take to settle.
+        const SETTLE_STEPS: usize = 2048;
+
+        fn zoomed_in() -> Camera {
+            Camera {
+                zoom: MAX_ZOOM,
+                target_zoom: MAX_ZOOM,
                 ..<_>::default()
-            },
+            }
+        }
+
+        fn assert_within_board(camera: &Camera) {
+            let half_visible_w = BOARD_W / (2. * camera.zoom);
+            let half_visible_h = BOARD_H / (2. * camera.zoom);
+
+            assert!(camera.center.x - half_visible_w >= 0., "{:?}", camera);
+            assert!(camera.center.x + half_visible_w <= BOARD_W, "{:?}", camera);
+            assert!(camera.center.y - half_visible_h >= 0., "{:?}", camera);
+            assert!(camera.center.y + half_visible_h <= BOARD_H, "{:?}", camera);
+        }
+
+        #[test]
+        fn zoom_stays_within_its_limits() {
+            let mut camera = Camera::default();
+            let eye = XY { x: 8., y: 8. };
+
+            for _ in 0..16 {
+                camera.zoom_in();
+            }
+            assert_eq!(camera.target_zoom, MAX_ZOOM);
+
+            for _ in 0..SETTLE_STEPS {
+                camera.step(eye, 0);
+                assert!(camera.zoom <= MAX_ZOOM, "{:?}", camera);
+            }
+            assert_eq!(camera.zoom, MAX_ZOOM);
+
+            for _ in 0..16 {
+                camera.zoom_out();
+            }
+            assert_eq!(camera.target_zoom, MIN_ZOOM);
+
+            for _ in 0..SETTLE_STEPS {
+                camera.step(eye, 0);
+                assert!(camera.zoom >= MIN_ZOOM, "{:?}", camera);
+            }
+            assert_eq!(camera.zoom, MIN_ZOOM);
+        }
+
+        #[test]
+        fn the_camera_never_shows_outside_the_board() {
+            let corners = [
+                XY { x: 0.5, y: 0.5 },
+                XY { x: BOARD_W - 0.5, y: 0.5 },
+                XY { x: 0.5, y: BOARD_H - 0.5 },
+                XY { x: BOARD_W - 0.5, y: BOARD_H - 0.5 },
+            ];
+
+            for eye in corners {
+                for pan in [
+                    0,
+                    INPUT_PAN_UP_DOWN | INPUT_PAN_LEFT_DOWN,
+                    INPUT_PAN_DOWN_DOWN | INPUT_PAN_RIGHT_DOWN,
+                ] {
+                    let mut camera = zoomed_in();
+
+                    for _ in 0..SETTLE_STEPS {
+                        camera.step(eye, pan);
+                        assert_within_board(&camera);
+                    }
+
+                    assert!(camera.pan.x.abs() <= BOARD_W, "{:?}", camera);
+                    assert!(camera.pan.y.abs() <= BOARD_H, "{:?}", camera);
+                }
+            }
+
+            // At the minimum zoom, the whole board is visible, so the camera
+            // stays in the middle.
+            let mut camera = Camera::default();
+            for _ in 0..SETTLE_STEPS {
+                camera.step(corners[0], INPUT_PAN_UP_DOWN);
+                assert_eq!(camera.center, Camera::default().center);
+            }
+        }
+
+        #[test]
+        fn following_the_eye_converges_without_overshooting() {
+            let mut camera = zoomed_in();
+            let start = camera.center;
+            let eye = XY { x: 3.5, y: 12.5 };
+
+            let mut last_distance = f32::INFINITY;
+            for _ in 0..SETTLE_STEPS {
+                camera.step(eye, 0);
+
+                assert!(camera.center.x <= start.x && camera.center.x >= eye.x, "{:?}", camera);
+                assert!(camera.center.y >= start.y && camera.center.y <= eye.y, "{:?}", camera);
+
+                let distance = (eye.x - camera.center.x).abs()
+                    + (eye.y - camera.center.y).abs();
+                assert!(distance <= last_distance, "{} > {}", distance, last_distance);
+                last_distance = distance;
+            }
+
+            assert!(last_distance < 1. / 256., "{:?}", camera);
         }
     }
 }
@@ -569,39 +1017,383 @@ type AnimationTimer = u16;
 /// We use this because it has a lot more varied factors than 65536.
 const ANIMATION_TIMER_LENGTH: AnimationTimer = 60 * 60 * 18;
 
+/// Shown on the title screen.
+pub const TITLE: &str = "rename-me";
+
+/// How many update steps it takes to fade in a new scene.
+const TRANSITION_LENGTH: AnimationTimer = 60;
+
 #[derive(Debug, Default)]
 pub struct State {
     sizes: draw::Sizes,
+    seed: Seed,
+    /// The level being played, if any.
+    level: scene::LevelNumber,
+    /// Today, for the daily challenge. Provided by the platform layer.
+    date: Option<seed::Date>,
+    seed_entry: draw::StrBuf,
+    seed_entry_error: Option<seed::ParseError>,
     board: Board,
-    animation_timer: AnimationTimer
+    camera: camera::Camera,
+    animation_timer: AnimationTimer,
+    scenes: scene::Stack,
+    /// Counts down to zero after the scenes change.
+    transition_timer: AnimationTimer,
+    settings: Settings,
+    should_quit: bool,
+    editor: editor::Editor,
+    request: Option<Request>,
+    high_scores: score::HighScores,
+    /// The players chosen in the multiplayer menu. Starts with two, since one
+    /// player is not multiplayer.
+    lobby: players::Setup,
+    /// The players for the boards being played.
+    players: players::Setup,
+    /// The last finished board, and where it placed in the high scores.
+    last_result: Option<(score::Entry, Option<score::Rank>)>,
+    #[cfg(feature = "alloc")]
+    netplay: Option<netplay::Netplay>,
+    /// Waiting to be sent to the other peer.
+    #[cfg(feature = "alloc")]
+    packets: alloc::collections::VecDeque<netplay::Packet>,
+    /// Checksums logged since the last `take_checksum_log`, if logging is on.
+    #[cfg(feature = "alloc")]
+    checksum_log: Option<checksum::Log>,
+    profiler: profile::Profiler,
+    #[cfg(feature = "debug-hud")]
+    inspector: inspector::Inspector,
 }
 
 impl State {
     pub fn from_seed(seed: Seed) -> Self {
         Self {
-            board: Board::from_seed(seed),
+            seed,
+            level: 1,
+            board: Board::from_seed(seed, 1, <_>::default()),
+            lobby: players::Setup {
+                count: 2,
+                ..<_>::default()
+            },
+            transition_timer: TRANSITION_LENGTH,
             ..<_>::default()
         }
     }
 }
 
+/// The code for the current seed, for sharing with other people.
+pub fn seed_code(state: &State) -> draw::StrBuf {
+    seed::encode(state.seed)
+}
+
+/// The number of players on this computer, so the platform layer knows how to
+/// split up the input devices.
+pub fn player_count(state: &State) -> players::PlayerCount {
+    if is_netplaying(state) {
+        1
+    } else {
+        state.players.count
+    }
+}
+
+/// Netplay needs an allocator, for its packets and rollback history.
+#[cfg(feature = "alloc")]
+fn is_netplaying(state: &State) -> bool {
+    matches!(state.netplay, Some(netplay::Netplay::Playing(_)))
+}
+
+#[cfg(not(feature = "alloc"))]
+fn is_netplaying(_: &State) -> bool {
+    false
+}
+
+/// Waits for someone to join with `join_netplay`, then starts a two player
+/// board with the current seed and the mode from the multiplayer menu.
+#[cfg(feature = "alloc")]
+pub fn host_netplay(state: &mut State, input_delay: netplay::Frame) {
+    state.netplay = Some(netplay::Netplay::Hosting { input_delay });
+}
+
+/// Asks whoever the packets from `take_packet` go to to start a game.
+#[cfg(feature = "alloc")]
+pub fn join_netplay(state: &mut State, input_delay: netplay::Frame) {
+    state.netplay = Some(netplay::Netplay::Joining { input_delay });
+}
+
+/// Tells the other peer we've gone, if we were playing with one. The `Leave`
+/// packet still needs to be taken with `take_packet` and sent.
+pub fn leave_netplay(state: &mut State) {
+    #[cfg(feature = "alloc")]
+    if let Some(netplay::Netplay::Playing(_)) = state.netplay.take() {
+        state.packets.push_back(netplay::Message::Leave.encode());
+        state.players = <_>::default();
+    }
+    #[cfg(not(feature = "alloc"))]
+    let _ = state;
+}
+
+/// For each packet that arrives from the other peer.
+#[cfg(feature = "alloc")]
+pub fn receive_packet(state: &mut State, packet: &[u8]) {
+    use netplay::{Message, Netplay};
+
+    let Some(message) = Message::decode(packet) else {
+        return
+    };
+
+    match (&mut state.netplay, message) {
+        (Some(Netplay::Hosting { input_delay }), Message::Join) => {
+            let session = netplay::Session::new(0, *input_delay);
+            start_netplay(state, session, state.seed, 1, state.lobby.mode);
+            send_start(state);
+        },
+        (Some(Netplay::Playing(session)), Message::Join) if session.local_player() == 0 => {
+            // Our `Start` must have been lost.
+            send_start(state);
+        },
+        (Some(Netplay::Joining { input_delay }), Message::Start { seed, level, mode }) => {
+            let session = netplay::Session::new(1, *input_delay);
+            start_netplay(state, session, seed, level, mode);
+        },
+        (Some(Netplay::Playing(session)), message @ Message::Inputs { .. }) => {
+            session.receive(&message);
+        },
+        (Some(Netplay::Playing(_)), Message::Leave) => {
+            state.netplay = None;
+            state.players = <_>::default();
+            state.scenes.reset(scene::Scene::Title(0));
+            state.transition_timer = TRANSITION_LENGTH;
+        },
+        _ => {},
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn start_netplay(
+    state: &mut State,
+    session: netplay::Session,
+    seed: Seed,
+    level: scene::LevelNumber,
+    mode: players::Mode,
+) {
+    state.seed = seed;
+    state.players = players::Setup { count: 2, mode };
+    start_level(state, level);
+    state.transition_timer = TRANSITION_LENGTH;
+    state.netplay = Some(netplay::Netplay::Playing(session));
+}
+
+#[cfg(feature = "alloc")]
+fn send_start(state: &mut State) {
+    let start = netplay::Message::Start {
+        seed: state.seed,
+        level: state.level,
+        mode: state.players.mode,
+    };
+    state.packets.push_back(start.encode());
+}
+
+/// Should be called after each `update`, until it returns `None`, and the
+/// packets sent to the other peer.
+#[cfg(feature = "alloc")]
+pub fn take_packet(state: &mut State) -> Option<netplay::Packet> {
+    state.packets.pop_front()
+}
+
+/// Starts logging a checksum after each update step on a board, for comparing
+/// with another run using `checksum::first_divergence`. In netplay, only steps
+/// that won't be rolled back are logged, so both peers' logs should match.
+#[cfg(feature = "alloc")]
+pub fn enable_checksum_log(state: &mut State) {
+    let mut log = checksum::Log::new();
+    // Writing to a `String` never fails.
+    let _ = checksum::write_header(&mut log);
+    state.checksum_log = Some(log);
+}
+
+/// What was logged since the last call, which should be appended to whatever
+/// was taken before. Empty if logging is off.
+#[cfg(feature = "alloc")]
+pub fn take_checksum_log(state: &mut State) -> checksum::Log {
+    state.checksum_log.as_mut().map(core::mem::take).unwrap_or_default()
+}
+
+/// Should be set to the player's local date, so the daily challenge changes
+/// at midnight for them. If the local time zone can't be found, the date in
+/// UTC is the closest thing.
+pub fn set_date(state: &mut State, date: seed::Date) {
+    state.date = Some(date);
+}
+
+/// Longer than any valid code, with room for separators.
+const MAX_SEED_ENTRY_LENGTH: usize = 40;
+
+/// True when the game is waiting for text. Keys that both type characters and
+/// have other uses, like WASD, should only be passed to `type_char` then.
+pub fn wants_text_input(state: &State) -> bool {
+    matches!(state.scenes.top(), scene::Scene::EnterSeed(_))
+}
+
+/// For typed or pasted text. Characters the game isn't expecting are ignored.
+pub fn type_char(state: &mut State, c: char) {
+    if !wants_text_input(state)
+    || state.seed_entry.len() >= MAX_SEED_ENTRY_LENGTH
+    || (seed::char_value(c).is_none() && c != '-') {
+        return
+    }
+
+    state.seed_entry.push(c.to_ascii_uppercase());
+    state.seed_entry_error = None;
+}
+
+/// Level 1 uses the seed itself, so a printed seed reproduces it directly.
+fn level_seed(seed: Seed, level: scene::LevelNumber) -> Seed {
+    let mut rng = xs_from_seed(seed);
+
+    let mut output = seed;
+    for _ in 1..level {
+        output = new_seed(&mut rng);
+    }
+
+    output
+}
+
 pub fn sizes(state: &State) -> draw::Sizes {
     state.sizes.clone()
 }
 
-pub type InputFlags = u16;
+/// Profiling is off until a clock is provided. Pass `None` to turn it off again.
+pub fn set_profile_clock(state: &mut State, clock: Option<profile::Clock>) {
+    state.profiler.set_clock(clock);
+}
 
-pub const INPUT_UP_PRESSED: InputFlags              = 0b0000_0000_0000_0001;
-pub const INPUT_DOWN_PRESSED: InputFlags            = 0b0000_0000_0000_0010;
-pub const INPUT_LEFT_PRESSED: InputFlags            = 0b0000_0000_0000_0100;
-pub const INPUT_RIGHT_PRESSED: InputFlags           = 0b0000_0000_0000_1000;
+pub fn settings(state: &State) -> Settings {
+    state.settings
+}
 
-pub const INPUT_UP_DOWN: InputFlags                 = 0b0000_0000_0001_0000;
-pub const INPUT_DOWN_DOWN: InputFlags               = 0b0000_0000_0010_0000;
-pub const INPUT_LEFT_DOWN: InputFlags               = 0b0000_0000_0100_0000;
-pub const INPUT_RIGHT_DOWN: InputFlags              = 0b0000_0000_1000_0000;
+/// For changes made outside the game, like from a hotkey.
+pub fn set_settings(state: &mut State, settings: Settings) {
+    state.settings = settings;
+}
 
-pub const INPUT_INTERACT_PRESSED: InputFlags        = 0b0000_0001_0000_0000;
+/// Should be called at startup, with the scores saved last time.
+pub fn set_high_scores(state: &mut State, high_scores: score::HighScores) {
+    state.high_scores = high_scores;
+}
+
+/// Things the game needs the platform layer to do for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Request {
+    /// The output of `write_saved_level` should be saved, so it can be loaded
+    /// from the same slot later.
+    SaveLevel { slot: LevelSlot },
+    /// The text previously saved to the slot should be passed to `load_level`.
+    LoadLevel { slot: LevelSlot },
+    /// The output of `write_high_scores` should be saved, and passed to
+    /// `score::parse` next time.
+    SaveHighScores,
+}
+
+/// Should be called after each `update`, to see if the game needs anything.
+pub fn take_request(state: &mut State) -> Option<Request> {
+    state.request.take()
+}
+
+/// Writes out the level for a `Request::SaveLevel`. The platform layer decides
+/// where the text goes, so the game doesn't need to allocate space for it.
+pub fn write_saved_level(
+    state: &State,
+    w: &mut dyn core::fmt::Write,
+) -> core::fmt::Result {
+    level_file::write(w, &state.editor.tiles.tiles, state.editor.start)
+}
+
+/// Writes out the high scores for a `Request::SaveHighScores`.
+pub fn write_high_scores(
+    state: &State,
+    w: &mut dyn core::fmt::Write,
+) -> core::fmt::Result {
+    score::write(w, &state.high_scores)
+}
+
+/// Replaces the level in the editor with the one in `text`. If that fails, the
+/// level is left as it was, and the error is shown in the editor too.
+pub fn load_level(state: &mut State, text: &str) -> Result<(), LevelParseError> {
+    let slot = state.editor.slot;
+    match level_file::parse(text) {
+        Ok((tiles, start)) => {
+            state.editor.set_level(Tiles { tiles }, start);
+            state.editor.status = str_buf!("Loaded slot {}", slot);
+            Ok(())
+        },
+        Err(err) => {
+            state.editor.status = str_buf!("Could not load slot {}: {}", slot, err);
+            Err(err)
+        },
+    }
+}
+
+/// Skips the menus and starts a one player board, for runs without anyone to
+/// pick one.
+pub fn start_solo(state: &mut State, level: LevelNumber) {
+    state.players = <_>::default();
+    start_level(state, level);
+}
+
+/// Shows a message in the editor, like whether saving worked.
+pub fn set_status(state: &mut State, status: &str) {
+    state.editor.status = status.into();
+}
+
+/// True once the player has confirmed they want to quit.
+pub fn should_quit(state: &State) -> bool {
+    state.should_quit
+}
+
+/// The profiling records from the last call to `update`.
+pub fn profile_records(state: &State) -> &[profile::Record] {
+    state.profiler.records()
+}
+
+/// How many scopes from the last call to `update` were left out of
+/// `profile_records`, because there were too many.
+pub fn profile_dropped(state: &State) -> usize {
+    state.profiler.dropped()
+}
+
+pub type InputFlags = u32;
+
+pub const INPUT_UP_PRESSED: InputFlags              = 0b0000_0000_0000_0000_0000_0000_0000_0001;
+pub const INPUT_DOWN_PRESSED: InputFlags            = 0b0000_0000_0000_0000_0000_0000_0000_0010;
+pub const INPUT_LEFT_PRESSED: InputFlags            = 0b0000_0000_0000_0000_0000_0000_0000_0100;
+pub const INPUT_RIGHT_PRESSED: InputFlags           = 0b0000_0000_0000_0000_0000_0000_0000_1000;
+
+pub const INPUT_UP_DOWN: InputFlags                 = 0b0000_0000_0000_0000_0000_0000_0001_0000;
+pub const INPUT_DOWN_DOWN: InputFlags               = 0b0000_0000_0000_0000_0000_0000_0010_0000;
+pub const INPUT_LEFT_DOWN: InputFlags               = 0b0000_0000_0000_0000_0000_0000_0100_0000;
+pub const INPUT_RIGHT_DOWN: InputFlags              = 0b0000_0000_0000_0000_0000_0000_1000_0000;
+
+pub const INPUT_INTERACT_PRESSED: InputFlags        = 0b0000_0000_0000_0000_0000_0001_0000_0000;
+
+pub const INPUT_ZOOM_IN_PRESSED: InputFlags         = 0b0000_0000_0000_0000_0000_0010_0000_0000;
+pub const INPUT_ZOOM_OUT_PRESSED: InputFlags        = 0b0000_0000_0000_0000_0000_0100_0000_0000;
+
+pub const INPUT_PAN_UP_DOWN: InputFlags             = 0b0000_0000_0000_0000_0000_1000_0000_0000;
+pub const INPUT_PAN_DOWN_DOWN: InputFlags           = 0b0000_0000_0000_0000_0001_0000_0000_0000;
+pub const INPUT_PAN_LEFT_DOWN: InputFlags           = 0b0000_0000_0000_0000_0010_0000_0000_0000;
+pub const INPUT_PAN_RIGHT_DOWN: InputFlags          = 0b0000_0000_0000_0000_0100_0000_0000_0000;
+
+/// Only does anything if the `debug-hud` feature is enabled.
+pub const INPUT_INSPECTOR_PRESSED: InputFlags       = 0b0000_0000_0000_0000_1000_0000_0000_0000;
+
+/// Opens the pause menu during gameplay, and goes back in other menus.
+pub const INPUT_MENU_PRESSED: InputFlags            = 0b0000_0000_0000_0001_0000_0000_0000_0000;
+
+/// Turns arrows clockwise in the editor.
+pub const INPUT_ROTATE_PRESSED: InputFlags          = 0b0000_0000_0000_0010_0000_0000_0000_0000;
+/// Moves the eye's starting position to the cursor in the editor.
+pub const INPUT_PLACE_START_PRESSED: InputFlags     = 0b0000_0000_0000_0100_0000_0000_0000_0000;
+/// Deletes the last typed character.
+pub const INPUT_BACKSPACE_PRESSED: InputFlags       = 0b0000_0000_0000_1000_0000_0000_0000_0000;
 
 #[derive(Clone, Copy, Debug)]
 enum Input {
@@ -638,9 +1430,67 @@ impl Input {
     }
 }
 
+/// An update step for a board that isn't being played over the network.
+fn update_local_step(state: &mut State, inputs: [Input; MAX_PLAYERS]) {
+    update_step(state, inputs);
+
+    // Boards played from the editor aren't worth checking.
+    if checksum::is_logging(state)
+    && state.scenes.base() == scene::Scene::Gameplay {
+        checksum::log_entry(state, state.board.steps, checksum::compute(state));
+    }
+}
+
 fn update_step(
     state: &mut State,
+    inputs: [Input; MAX_PLAYERS],
+) {
+    let board = &mut state.board;
+
+    if !board.solved {
+        board.ticks = board.ticks.saturating_add(1);
+    }
+    board.steps = board.steps.saturating_add(1);
+
+    for (player, &input) in inputs.iter().enumerate().take(board.eyes().len()) {
+        update_eye_step(board, player, input, state.animation_timer);
+    }
+
+    let mut finished = board.eyes().iter().map(|eye| eye.finished_at.is_some());
+    board.solved = match board.players.mode {
+        players::Mode::CoOp => finished.all(|f| f),
+        players::Mode::Versus => finished.any(|f| f),
+    };
+
+    state.animation_timer += 1;
+    if state.animation_timer >= ANIMATION_TIMER_LENGTH {
+        state.animation_timer = 0;
+    }
+}
+
+/// The middle of all the eyes, so the camera keeps them all in view if it can.
+fn camera_target(board: &Board) -> camera::XY {
+    let eyes = board.eyes();
+
+    let mut sum = camera::XY::default();
+    for eye in eyes {
+        let xy = camera::XY::from_eye(eye.xy, eye.offset_xy);
+        sum.x += xy.x;
+        sum.y += xy.y;
+    }
+
+    let count = eyes.len() as camera::Length;
+    camera::XY {
+        x: sum.x / count,
+        y: sum.y / count,
+    }
+}
+
+fn update_eye_step(
+    board: &mut Board,
+    player: PlayerIndex,
     input: Input,
+    animation_timer: AnimationTimer,
 ) {
     use EyeState::*;
     use Input::*;
@@ -648,66 +1498,119 @@ fn update_step(
 
     const HOLD_FRAMES: AnimationTimer = 30;
 
-    if state.board.eye.offset_xy == o_xy!{} {
+    // Eyes block each other, except for ones that have finished.
+    let mut occupied = [None; MAX_PLAYERS];
+    for (i, other) in board.eyes().iter().enumerate() {
+        if i != player && other.finished_at.is_none() {
+            occupied[i] = Some(other.xy);
+        }
+    }
+    let is_free = |xy: tile::XY| !occupied.contains(&Some(xy));
+
+    let solved = board.solved;
+    let ticks = board.ticks;
+    let tiles = &board.tiles.tiles;
+    let eye = &mut board.eyes[player];
+
+    if eye.offset_xy == o_xy!{} {
+        if let Some(dir) = eye.arriving.take() {
+            let eye_xy = eye.xy;
+
+            match tiles[tile::xy_to_i(eye_xy)].kind {
+                TileKind::Ice => {
+                    if let Some(next) = level::enterable_neighbour(tiles, eye_xy, dir)
+                        .filter(|&next| is_free(next)) {
+                        eye.move_to(next);
+                        eye.arriving = Some(dir);
+                    }
+                },
+                TileKind::Teleporter(_) => {
+                    if let Some(partner) = level::teleporter_partner(tiles, eye_xy)
+                        .filter(|&partner| is_free(partner)) {
+                        eye.xy = partner;
+                    }
+                },
+                TileKind::Floor
+                | TileKind::Wall
+                | TileKind::Goal
+                | TileKind::Arrow(..) => {},
+            }
+        }
+    }
+
+    if eye.offset_xy == o_xy!{} {
         macro_rules! offset_if_moved {
             ($($tile_method: ident)+ {$($offset_tokens: tt)*}) => {
-                let old_xy = state.board.eye.xy;
+                let old_xy = eye.xy;
                 $(
-                    state.board.eye.xy.$tile_method();
+                    eye.xy.$tile_method();
                 )+
                 let o_xy = o_xy!{$($offset_tokens)*};
-                if state.board.eye.xy.x != old_xy.x {
-                    state.board.eye.offset_xy.x = o_xy.x;
+                if eye.xy.x != old_xy.x {
+                    eye.offset_xy.x = o_xy.x;
                 }
-                if state.board.eye.xy.y != old_xy.y {
-                    state.board.eye.offset_xy.y = o_xy.y;
+                if eye.xy.y != old_xy.y {
+                    eye.offset_xy.y = o_xy.y;
                 }
             }
         }
 
+        if eye.finished_at.is_none()
+        && tiles[tile::xy_to_i(eye.xy)].kind == TileKind::Goal {
+            eye.finished_at = Some(ticks);
+        }
+
+        let xy_before = eye.xy;
+
         match input {
-            NoChange => match state.board.eye.state {
+            Dir(_) if solved || eye.finished_at.is_some() => {},
+            Dir(dir) if !level::can_move(tiles, xy_before, dir)
+            || level::neighbour(xy_before, dir).is_some_and(|xy| !is_free(xy)) => {
+                // Look that way, to show we got the input, but don't move.
+                eye.state = Moved(dir);
+            },
+            NoChange => match eye.state {
                 Idle => {
-                    if state.animation_timer % (HOLD_FRAMES * 3) == 0 {
-                        state.board.eye.state = NarrowAnimCenter;
+                    if animation_timer % (HOLD_FRAMES * 3) == 0 {
+                        eye.state = NarrowAnimCenter;
                     }
                 },
                 Moved(_) => {
-                    if state.animation_timer % HOLD_FRAMES == 0 {
-                        state.board.eye.state = Idle;
+                    if animation_timer % HOLD_FRAMES == 0 {
+                        eye.state = Idle;
                     }
                 },
                 SmallPupil => {
-                    if state.animation_timer % (HOLD_FRAMES * 3) == 0 {
-                        state.board.eye.state = Closed;
+                    if animation_timer % (HOLD_FRAMES * 3) == 0 {
+                        eye.state = Closed;
                     }
                 },
                 Closed => {
-                    if state.animation_timer % (HOLD_FRAMES) == 0 {
-                        state.board.eye.state = HalfLid;
+                    if animation_timer % (HOLD_FRAMES) == 0 {
+                        eye.state = HalfLid;
                     }
                 },
                 HalfLid => {
-                    if state.animation_timer % (HOLD_FRAMES * 5) == 0 {
-                        state.board.eye.state = Idle;
+                    if animation_timer % (HOLD_FRAMES * 5) == 0 {
+                        eye.state = Idle;
                     }
                 },
                 NarrowAnimCenter => {
-                    let modulus = state.animation_timer % (HOLD_FRAMES * 4);
+                    let modulus = animation_timer % (HOLD_FRAMES * 4);
                     if modulus == 0 {
-                        state.board.eye.state = NarrowAnimRight;
+                        eye.state = NarrowAnimRight;
                     } else if modulus == HOLD_FRAMES * 2 {
-                        state.board.eye.state = NarrowAnimLeft;
+                        eye.state = NarrowAnimLeft;
                     }
                 },
                 NarrowAnimLeft | NarrowAnimRight => {
-                    if state.animation_timer % HOLD_FRAMES == 0 {
-                        state.board.eye.state = NarrowAnimCenter;
+                    if animation_timer % HOLD_FRAMES == 0 {
+                        eye.state = NarrowAnimCenter;
                     }
                 },
             },
             Dir(Up) => {
-                state.board.eye.state = Moved(Up);
+                eye.state = Moved(Up);
                 offset_if_moved!{
                     move_up
                     {
@@ -717,7 +1620,7 @@ fn update_step(
                 }
             },
             Dir(UpRight) => {
-                state.board.eye.state = Moved(UpRight);
+                eye.state = Moved(UpRight);
                 offset_if_moved!{
                     move_up
                     move_right
@@ -728,7 +1631,7 @@ fn update_step(
                 }
             },
             Dir(Right) => {
-                state.board.eye.state = Moved(Right);
+                eye.state = Moved(Right);
                 offset_if_moved!{
                     move_right
                     {
@@ -738,7 +1641,7 @@ fn update_step(
                 }
             },
             Dir(DownRight) => {
-                state.board.eye.state = Moved(DownRight);
+                eye.state = Moved(DownRight);
                 offset_if_moved!{
                     move_down
                     move_right
@@ -749,7 +1652,7 @@ fn update_step(
                 }
             },
             Dir(Down) => {
-                state.board.eye.state = Moved(Down);
+                eye.state = Moved(Down);
                 offset_if_moved!{
                     move_down
                     {
@@ -759,7 +1662,7 @@ fn update_step(
                 }
             },
             Dir(DownLeft) => {
-                state.board.eye.state = Moved(DownLeft);
+                eye.state = Moved(DownLeft);
                 offset_if_moved!{
                     move_down
                     move_left
@@ -770,7 +1673,7 @@ fn update_step(
                 }
             },
             Dir(Left) => {
-                state.board.eye.state = Moved(Left);
+                eye.state = Moved(Left);
                 offset_if_moved!{
                     move_left
                     {
@@ -780,7 +1683,7 @@ fn update_step(
                 }
             },
             Dir(UpLeft) => {
-                state.board.eye.state = Moved(UpLeft);
+                eye.state = Moved(UpLeft);
                 offset_if_moved!{
                     move_up
                     move_left
@@ -791,11 +1694,19 @@ fn update_step(
                 }
             },
             Interact => {
-                state.board.eye.state = SmallPupil;
+                eye.state = SmallPupil;
             },
         }
+
+        if let Dir(dir) = input {
+            if eye.xy != xy_before {
+                eye.arriving = Some(dir);
+                eye.moves = eye.moves.saturating_add(1);
+            }
+        }
+
     } else {
-        let o_xy = &mut state.board.eye.offset_xy;
+        let o_xy = &mut eye.offset_xy;
 
         if o_xy.x > offset::X(0) {
            o_xy.x -= offset::X(1);
@@ -813,93 +1724,1109 @@ fn update_step(
             // Leave at zero
         }
     }
-
-    state.animation_timer += 1;
-    if state.animation_timer >= ANIMATION_TIMER_LENGTH {
-        state.animation_timer = 0;
-    }
 }
 
 pub type DeltaTimeInSeconds = f32;
 
-const S_PER_UPDATE: DeltaTimeInSeconds = 1./240.;
+pub const UPDATES_PER_SECOND: score::Ticks = 240;
+
+const S_PER_UPDATE: DeltaTimeInSeconds = 1. / UPDATES_PER_SECOND as DeltaTimeInSeconds;
+
+/// The most time we will catch up on in one frame. Without a limit, a long
+/// pause, like the window being dragged, would be followed by a burst of
+/// steps, and a large enough `dt` would never be counted down at all, since
+/// subtracting `S_PER_UPDATE` from it would leave it unchanged.
+const MAX_DT: DeltaTimeInSeconds = 0.25;
+
+/// One set of input flags for each player. Each player's directions and
+/// interact only move their own eye, while everything else, like the menus, can
+/// be controlled by any player. With one player, only the first set is used.
+pub type PlayerInputs = [InputFlags; MAX_PLAYERS];
 
 pub fn update(
     state: &mut State,
     commands: &mut dyn ClearableStorage<draw::Command>,
-    input_flags: InputFlags,
+    player_inputs: PlayerInputs,
     draw_wh: DrawWH,
-    mut dt: DeltaTimeInSeconds,
+    dt: DeltaTimeInSeconds,
 ) {
-    use draw::{TextSpec, TextKind, Command::*};
+    state.profiler.clear();
+
+    profile_scope!(state.profiler, Update, {
+        update_and_push_commands(state, commands, player_inputs, draw_wh, dt)
+    })
+}
 
-    if draw_wh != state.sizes.draw_wh {
+fn update_and_push_commands(
+    state: &mut State,
+    commands: &mut dyn ClearableStorage<draw::Command>,
+    player_inputs: PlayerInputs,
+    draw_wh: DrawWH,
+    mut dt: DeltaTimeInSeconds,
+) {
+    if draw_wh.sanitized() != state.sizes.draw_wh {
         state.sizes = draw::fresh_sizes(draw_wh);
     }
 
     commands.clear();
 
-    let input = Input::from_flags(input_flags);
+    let input_flags = player_inputs.iter().fold(0, |acc, &flags| acc | flags);
+
+    #[cfg_attr(not(feature = "debug-hud"), allow(unused_mut))]
+    let mut inputs = player_inputs.map(Input::from_flags);
+
+    #[cfg(feature = "debug-hud")]
+    {
+        if input_flags & INPUT_INSPECTOR_PRESSED != 0 {
+            state.inspector.is_open = !state.inspector.is_open;
+        }
+
+        if state.inspector.is_open {
+            inspector::update(state, input_flags);
+            // The inspector gets the input, but we keep the simulation running so
+            // edits can be seen live.
+            inputs = [Input::NoChange; MAX_PLAYERS];
+        }
+    }
+
+    // We handle these outside of `update_step`, so they only happen once per
+    // press, no matter how many steps happen per frame.
+    #[cfg(feature = "debug-hud")]
+    let menu_input_flags = if state.inspector.is_open { 0 } else { input_flags };
+    #[cfg(not(feature = "debug-hud"))]
+    let menu_input_flags = input_flags;
+
+    if let Some(action) = state.scenes.top_mut().update(menu_input_flags) {
+        apply_action(state, action);
+    }
+
+    let is_playing = matches!(
+        state.scenes.top(),
+        scene::Scene::Gameplay | scene::Scene::Playtest
+    );
+    let is_editing = state.scenes.top() == scene::Scene::Editor;
+    // Pausing would leave the other player waiting for us, so networked boards
+    // keep running under any menus, just without our input.
+    let is_netplaying = is_netplaying(state)
+        && state.scenes.base() == scene::Scene::Gameplay;
+    #[cfg(feature = "alloc")]
+    let netplay_input_flags = if is_playing { menu_input_flags } else { 0 };
+
+    if wants_text_input(state) && menu_input_flags & INPUT_BACKSPACE_PRESSED != 0 {
+        state.seed_entry.pop();
+        state.seed_entry_error = None;
+    }
+
+    if is_editing {
+        state.editor.update(menu_input_flags);
+    }
+
+    if is_playing || is_editing {
+        if input_flags & INPUT_ZOOM_IN_PRESSED != 0 {
+            state.camera.zoom_in();
+        }
+        if input_flags & INPUT_ZOOM_OUT_PRESSED != 0 {
+            state.camera.zoom_out();
+        }
+    }
+
+    let was_solved = state.board.solved;
 
+    dt = dt.min(MAX_DT);
     while dt >= S_PER_UPDATE {
-        update_step(state, input);
+        state.transition_timer = state.transition_timer.saturating_sub(1);
+
+        if is_playing || is_netplaying {
+            profile_scope!(state.profiler, UpdateStep, {
+                #[cfg(feature = "alloc")]
+                match state.netplay.take() {
+                    Some(netplay::Netplay::Playing(mut session)) => {
+                        session.step(state, netplay_input_flags);
+                        state.netplay = Some(netplay::Netplay::Playing(session));
+                    },
+                    netplay => {
+                        state.netplay = netplay;
+                        update_local_step(state, inputs);
+                    },
+                }
+                #[cfg(not(feature = "alloc"))]
+                update_local_step(state, inputs);
+            });
+
+            profile_scope!(state.profiler, CameraStep, {
+                state.camera.step(
+                    camera_target(&state.board),
+                    input_flags
+                );
+            });
+        }
+
+        if is_editing {
+            state.camera.step(
+                camera::XY::from_eye(state.editor.cursor, o_xy!{}),
+                input_flags
+            );
+        }
 
         dt -= S_PER_UPDATE;
     }
 
-    for i in 0..TILES_LENGTH {
-        let tile_data = state.board.tiles.tiles[i];
+    #[cfg(feature = "alloc")]
+    match &state.netplay {
+        Some(netplay::Netplay::Playing(session)) => {
+            state.packets.push_back(session.inputs_message().encode());
+        },
+        Some(netplay::Netplay::Joining { .. }) => {
+            state.packets.push_back(netplay::Message::Join.encode());
+        },
+        Some(netplay::Netplay::Hosting { .. }) | None => {},
+    }
+
+    // Boards played from the editor don't count. Networked boards can be
+    // finished under a menu, since they keep running.
+    if is_newly_finished(state, was_solved)
+    && state.scenes.base() == scene::Scene::Gameplay {
+        finish_board(state);
+    }
+
+    let base = state.scenes.base();
+    let top = state.scenes.top();
+
+    match base {
+        scene::Scene::Gameplay | scene::Scene::Playtest => {
+            profile_scope!(state.profiler, Commands, {
+                push_board_commands(state, commands);
+            });
+
+            profile_scope!(state.profiler, TextFormatting, {
+                push_ui_commands(state, commands, inputs);
+            });
+        },
+        scene::Scene::Editor => {
+            push_editor_commands(state, commands);
+        },
+        _ => {
+            push_menu_commands(state, base, commands);
+        },
+    }
+
+    if top != base {
+        push_menu_commands(state, top, commands);
+    }
+
+    if state.transition_timer > 0 {
+        commands.push(draw::Command::Rect(draw::RectSpec {
+            xy: DrawXY::default(),
+            wh: state.sizes.draw_wh,
+            colour: draw::Colour {
+                a: (
+                    u32::from(state.transition_timer) * 255
+                    / u32::from(TRANSITION_LENGTH)
+                ) as draw::ColourComponent,
+                ..draw::Colour::BACKGROUND
+            },
+            layer: draw::TRANSITION_LAYER,
+        }));
+    }
+}
+
+/// A networked board can be solved with a guess at the other player's input,
+/// and then un-solved by a rollback, so it isn't finished until the frame it
+/// was solved on is final.
+fn is_newly_finished(state: &mut State, was_solved: bool) -> bool {
+    #[cfg(feature = "alloc")]
+    if let Some(netplay::Netplay::Playing(session)) = &mut state.netplay {
+        return session.take_final_solve()
+    }
+
+    !was_solved && state.board.solved
+}
 
+fn finish_board(state: &mut State) {
+    state.last_result = None;
+
+    // Multiplayer boards have different starts and interference from the
+    // other players, so their scores aren't comparable with solo ones.
+    if !state.board.players.is_multiplayer() {
+        if let Some(entry) = state.board.entry(state.seed, state.level, 0) {
+            let rank = state.high_scores.insert(entry);
+            if rank.is_some() {
+                state.request = Some(Request::SaveHighScores);
+            }
+
+            state.last_result = Some((entry, rank));
+        }
+    }
+
+    state.scenes.push(scene::Scene::Results(0));
+    state.transition_timer = TRANSITION_LENGTH;
+}
+
+fn start_level(state: &mut State, level: scene::LevelNumber) {
+    state.level = level;
+    state.board = Board::from_seed(level_seed(state.seed, level), level, state.players);
+    state.camera = <_>::default();
+    state.animation_timer = 0;
+    state.scenes.reset(scene::Scene::Gameplay);
+    checksum::log_board(state);
+}
+
+fn apply_action(state: &mut State, action: scene::Action) {
+    use scene::{Action::*, Scene};
+
+    // Both peers would need to agree on what comes next, so a networked
+    // session only lasts for one board.
+    if matches!(action, NextLevel | Retry | Reset(_)) {
+        leave_netplay(state);
+    }
+
+    match action {
+        Push(scene) => state.scenes.push(scene),
+        Pop => state.scenes.pop(),
+        Reset(scene) => state.scenes.reset(scene),
+        PlaySolo => {
+            state.players = <_>::default();
+            state.scenes.push(Scene::LevelSelect(0));
+        },
+        PlayTogether => {
+            state.players = state.lobby;
+            state.scenes.push(Scene::LevelSelect(0));
+        },
+        NextPlayerCount => {
+            state.lobby.next_count();
+            return
+        },
+        NextMode => {
+            state.lobby.mode = state.lobby.mode.next();
+            return
+        },
+        StartLevel(level) => start_level(state, level),
+        NextLevel => start_level(state, state.level.saturating_add(1)),
+        Retry => start_level(state, state.level),
+        StartDaily => {
+            // Without a date, there's no way to agree on a seed, so we just
+            // use the current one.
+            if let Some(date) = state.date {
+                state.seed = seed::daily(date);
+            }
+            state.players = <_>::default();
+            start_level(state, 1);
+        },
+        UseEnteredSeed => match seed::parse(&state.seed_entry) {
+            Ok(entered) => {
+                state.seed = entered;
+                state.players = <_>::default();
+                state.seed_entry.clear();
+                state.scenes.pop();
+                state.scenes.push(Scene::LevelSelect(0));
+            },
+            Err(err) => {
+                state.seed_entry_error = Some(err);
+                return
+            },
+        },
+        ToggleFullscreen => {
+            state.settings.fullscreen = !state.settings.fullscreen;
+            // The scene is the same, so there's nothing to fade.
+            return
+        },
+        NextTargetFps => {
+            state.settings.next_target_fps();
+            return
+        },
+        ToggleShowStats => {
+            state.settings.show_stats = !state.settings.show_stats;
+            return
+        },
+        Quit => {
+            state.should_quit = true;
+            return
+        },
+        StartPlaytest => {
+            let editor = &state.editor;
+            let metrics = level::Metrics {
+                min_moves: editor.solution.min_moves.unwrap_or_default(),
+                reachable: editor.solution.reachable,
+                attempts: 0,
+            };
+            state.board = Board {
+                tiles: editor.tiles.clone(),
+                metrics,
+                ..<_>::default()
+            };
+            state.board.eyes[0] = Eye {
+                xy: editor.start,
+                par_moves: metrics.min_moves,
+                ..<_>::default()
+            };
+            state.animation_timer = 0;
+            // Replace the editor menu, so we go straight back to the editor.
+            state.scenes.pop();
+            state.scenes.push(Scene::Playtest);
+        },
+        SaveLevel => {
+            state.request = Some(Request::SaveLevel { slot: state.editor.slot });
+            state.scenes.pop();
+        },
+        LoadLevel => {
+            state.request = Some(Request::LoadLevel { slot: state.editor.slot });
+            state.scenes.pop();
+        },
+        NextSlot => {
+            state.editor.next_slot();
+            return
+        },
+        NewLevel => {
+            state.editor = editor::Editor {
+                slot: state.editor.slot,
+                ..<_>::default()
+            };
+            state.scenes.pop();
+        },
+    }
+
+    state.transition_timer = TRANSITION_LENGTH;
+}
+
+fn push_tile_commands(
+    sizes: &Sizes,
+    camera: &camera::Camera,
+    tiles: &TileDataArray,
+    commands: &mut dyn ClearableStorage<draw::Command>,
+) {
+    for (i, tile_data) in tiles.iter().enumerate() {
         let txy = tile::i_to_xy(i);
 
-        commands.push(Sprite(SpriteSpec{
+        let xy = draw_xy_from_tile(sizes, camera, txy);
+        if !is_on_board(sizes, camera, xy) {
+            continue
+        }
+
+        commands.push(draw::Command::Sprite(SpriteSpec{
             sprite: tile_data.sprite(),
-            xy: draw_xy_from_tile(&state.sizes, txy),
+            xy,
+            scale: camera.zoom,
+            ..<_>::default()
         }));
     }
+}
 
-    commands.push(Sprite(SpriteSpec{
-        sprite: state.board.eye.state.sprite(),
-        xy: draw_xy_from_tile(&state.sizes, state.board.eye.xy)
-        + draw_xy_from_offset(&state.sizes, state.board.eye.offset_xy),
-    }));
-
-    let left_text_x = state.sizes.play_xywh.x + MARGIN;
+fn push_editor_commands(
+    state: &State,
+    commands: &mut dyn ClearableStorage<draw::Command>,
+) {
+    use draw::{Command::*, TextKind};
 
     const MARGIN: f32 = 16.;
 
-    let small_section_h = state.sizes.draw_wh.h / 8. - MARGIN;
+    let camera = &state.camera;
+    let editor = &state.editor;
+
+    push_tile_commands(&state.sizes, camera, &editor.tiles.tiles, commands);
+
+    let start_xy = draw_xy_from_tile(&state.sizes, camera, editor.start);
+    if is_on_board(&state.sizes, camera, start_xy) {
+        commands.push(Sprite(SpriteSpec{
+            sprite: SpriteKind::NeutralEye,
+            xy: start_xy,
+            scale: camera.zoom,
+            layer: draw::EYE_LAYER,
+            ..<_>::default()
+        }));
+    }
+
+    let cursor_xy = draw_xy_from_tile(&state.sizes, camera, editor.cursor);
+    if is_on_board(&state.sizes, camera, cursor_xy) {
+        commands.push(Sprite(SpriteSpec{
+            sprite: editor.tiles.tiles[tile::xy_to_i(editor.cursor)].sprite(),
+            xy: cursor_xy,
+            tint: draw::Colour::BLUE,
+            scale: camera.zoom,
+            layer: draw::HIGHLIGHT_LAYER,
+            ..<_>::default()
+        }));
+    }
+
+    {
+        let panel = &state.sizes.first_panel_xywh;
+
+        push_text(
+            commands,
+            &state.sizes,
+            "Move the cursor over a tile, then interact to change it, or rotate to turn arrows. Place the start to move the eye. Open the menu to test, save or load.",
+            DrawXY { x: panel.x + MARGIN, y: panel.y + MARGIN },
+            DrawWH { w: panel.w - MARGIN * 2., h: panel.h - MARGIN * 2. },
+            TextKind::Body,
+            draw::TEXT_LAYER,
+        );
+    }
+
+    {
+        let panel = &state.sizes.second_panel_xywh;
+
+        let solution: draw::StrBuf = match editor.solution.min_moves {
+            Some(moves) => str_buf!("{{green}}Solvable in {} moves{{/}}", moves),
+            None => "{red}No goal can be reached{/}".into(),
+        };
+
+        let text: draw::LongStrBuf = str_buf!(
+            "{:?}\n{}\nSlot {}\n{}",
+            editor.tiles.tiles[tile::xy_to_i(editor.cursor)].kind,
+            solution,
+            editor.slot,
+            editor.status,
+        );
+
+        push_text(
+            commands,
+            &state.sizes,
+            &text,
+            DrawXY { x: panel.x + MARGIN, y: panel.y + MARGIN },
+            DrawWH { w: panel.w - MARGIN * 2., h: panel.h - MARGIN * 2. },
+            TextKind::Body,
+            draw::TEXT_LAYER,
+        );
+    }
+}
+
+fn push_board_commands(
+    state: &State,
+    commands: &mut dyn ClearableStorage<draw::Command>,
+) {
+    use draw::Command::*;
+
+    let camera = &state.camera;
+
+    push_tile_commands(&state.sizes, camera, &state.board.tiles.tiles, commands);
 
     {
-        let mut y = MARGIN;
+        // Highlight the tile the eye is on, with a gentle pulse.
+        const PULSE_FRAMES: AnimationTimer = 240;
+        let t = (state.animation_timer % PULSE_FRAMES) as draw::Scale
+            / PULSE_FRAMES as draw::Scale;
+        // Goes from 0 to 1 and back again.
+        let triangle = if t < 0.5 { t * 2. } else { 2. - t * 2. };
+
+        for eye in state.board.eyes() {
+            let eye_xy = eye.xy;
+
+            let xy = draw_xy_from_tile(&state.sizes, camera, eye_xy);
+            if is_on_board(&state.sizes, camera, xy) {
+                commands.push(Sprite(SpriteSpec{
+                    sprite: state.board.tiles.tiles[tile::xy_to_i(eye_xy)].sprite(),
+                    xy,
+                    tint: draw::Colour {
+                        a: 0x80,
+                        ..draw::Colour::YELLOW
+                    },
+                    scale: (1. + triangle * 0.125) * camera.zoom,
+                    layer: draw::HIGHLIGHT_LAYER,
+                    ..<_>::default()
+                }));
+            }
+        }
+    }
+
+    for (eye, &tint) in state.board.eyes().iter().zip(players::COLOURS.iter()) {
+        let xy = draw_xy_from_tile(&state.sizes, camera, eye.xy)
+        + draw_xy_from_offset(&state.sizes, camera, eye.offset_xy);
+
+        if is_on_board(&state.sizes, camera, xy) {
+            commands.push(Sprite(SpriteSpec{
+                sprite: eye.state.sprite(),
+                xy,
+                tint,
+                scale: camera.zoom,
+                layer: draw::EYE_LAYER,
+                ..<_>::default()
+            }));
+        }
+    }
+}
+
+/// Text shown between a menu's title and its options, if any.
+fn menu_body(state: &State, scene: scene::Scene) -> Option<draw::LongStrBuf> {
+    use core::fmt::Write;
+
+    let scene::Scene::Results(_) = scene else {
+        return None
+    };
+
+    if state.board.players.is_multiplayer() {
+        return Some(multiplayer_results(state))
+    }
+
+    let (entry, rank) = state.last_result?;
+
+    let mut body: draw::LongStrBuf = str_buf!(
+        "Moves: {} (par {})\nTime: {:.1}s (par {:.1}s)\nScore: {}\n",
+        entry.moves,
+        entry.par_moves,
+        score::seconds(entry.ticks),
+        score::seconds(entry.par_ticks()),
+        entry.points(),
+    );
+
+    if rank == Some(0) {
+        body.push_str("{green}New high score!{/}\n");
+    }
 
-        commands.push(Text(TextSpec{
-            text: format!(
-                "input: {:?}",
-                input
+    body.push_str("\nBest for this level:\n");
+    for (i, best) in state.high_scores.for_board(entry.seed, entry.level).enumerate() {
+        // Anything past the end of the buffer is cut off.
+        let _ = if Some(i) == rank {
+            writeln!(body, "{{yellow}}{}. {}{{/}}", i + 1, best.points())
+        } else {
+            writeln!(body, "{}. {}", i + 1, best.points())
+        };
+    }
+
+    Some(body)
+}
+
+fn multiplayer_results(state: &State) -> draw::LongStrBuf {
+    use core::fmt::Write;
+
+    let board = &state.board;
+    let mut body = draw::LongStrBuf::new();
+
+    if board.players.mode == players::Mode::Versus {
+        let first = board.eyes().iter().filter_map(|eye| eye.finished_at).min();
+        for (player, eye) in board.eyes().iter().enumerate() {
+            if eye.finished_at.is_some() && eye.finished_at == first {
+                // Anything past the end of the buffer is cut off.
+                let _ = writeln!(
+                    body,
+                    "{{{}}}Player {}{{/}} wins!",
+                    players::COLOUR_NAMES[player],
+                    player + 1
+                );
+            }
+        }
+        body.push('\n');
+    }
+
+    for player in 0..board.eyes().len() {
+        let name = players::COLOUR_NAMES[player];
+        // Anything past the end of the buffer is cut off.
+        let _ = match board.entry(state.seed, state.level, player) {
+            Some(entry) => writeln!(
+                body,
+                "{{{}}}Player {}{{/}}: {} moves (par {}), {:.1}s, {} points",
+                name,
+                player + 1,
+                entry.moves,
+                entry.par_moves,
+                score::seconds(entry.ticks),
+                entry.points(),
             ),
-            xy: DrawXY { x: left_text_x, y },
-            wh: DrawWH {
-                w: state.sizes.play_xywh.w,
-                h: small_section_h
+            None => writeln!(
+                body,
+                "{{{}}}Player {}{{/}}: did not finish",
+                name,
+                player + 1,
+            ),
+        };
+    }
+
+    body
+}
+
+fn push_menu_commands(
+    state: &State,
+    scene: scene::Scene,
+    commands: &mut dyn ClearableStorage<draw::Command>,
+) {
+    use draw::TextKind;
+
+    let layer = if scene.is_overlay() {
+        // Dim whatever is underneath.
+        commands.push(draw::Command::Rect(draw::RectSpec {
+            xy: DrawXY::default(),
+            wh: state.sizes.draw_wh,
+            colour: draw::Colour {
+                a: 0xc0,
+                ..draw::Colour::BACKGROUND
             },
-            kind: TextKind::UI,
+            layer: draw::OVERLAY_LAYER,
         }));
 
-        y += small_section_h;
+        draw::OVERLAY_TEXT_LAYER
+    } else {
+        draw::TEXT_LAYER
+    };
+
+    let play = &state.sizes.play_xywh;
+    let title_h = play.h / 3.;
+
+    push_text(
+        commands,
+        &state.sizes,
+        scene.title(),
+        DrawXY { x: play.x, y: play.y },
+        DrawWH { w: play.w, h: title_h },
+        TextKind::Title,
+        layer,
+    );
+
+    let mut options_y = play.y + title_h;
+
+    if let Some(body) = menu_body(state, scene) {
+        let body_h = (play.h - title_h) / 2.;
+
+        push_text(
+            commands,
+            &state.sizes,
+            &body,
+            DrawXY { x: play.x, y: options_y },
+            DrawWH { w: play.w, h: body_h },
+            TextKind::Menu,
+            layer,
+        );
+
+        options_y += body_h;
+    }
+
+    let selected = scene.selected().map(usize::from);
 
-        commands.push(Text(TextSpec{
-            text: format!(
-                "sizes: {:?}\nanimation_timer: {:?}",
-                state.sizes,
-                state.animation_timer
-            ),
-            xy: DrawXY { x: left_text_x, y },
-            wh: DrawWH {
-                w: state.sizes.play_xywh.w,
-                h: state.sizes.play_xywh.h - y
+    let mut options = draw::LongStrBuf::new();
+    for (i, option) in scene.options().iter().enumerate() {
+        let label = option.label(state);
+        if Some(i) == selected {
+            options.push_str("{yellow}> ");
+            options.push_str(&label);
+            options.push_str(" <{/}");
+        } else {
+            options.push_str(&label);
+        }
+        options.push('\n');
+    }
+
+    push_text(
+        commands,
+        &state.sizes,
+        &options,
+        DrawXY { x: play.x, y: options_y },
+        DrawWH { w: play.w, h: play.y + play.h - options_y },
+        TextKind::Menu,
+        layer,
+    );
+}
+
+fn push_ui_commands(
+    state: &State,
+    commands: &mut dyn ClearableStorage<draw::Command>,
+    #[cfg_attr(not(feature = "debug-hud"), allow(unused_variables))]
+    inputs: [Input; MAX_PLAYERS],
+) {
+    use draw::TextKind;
+
+    const MARGIN: f32 = 16.;
+
+    let small_section_h = state.sizes.tile_side_length * 2.;
+
+    #[cfg(feature = "debug-hud")]
+    if state.inspector.is_open {
+        use draw::Command::*;
+
+        let camera = &state.camera;
+
+        let panel = &state.sizes.first_panel_xywh;
+
+        let y = panel.y + MARGIN;
+
+        let text: draw::LongStrBuf = str_buf!(
+            "inputs: {:?}\n{}",
+            inputs,
+            inspector::text(state)
+        );
+
+        push_text(
+            commands,
+            &state.sizes,
+            &text,
+            DrawXY { x: panel.x + MARGIN, y },
+            DrawWH {
+                w: panel.w - MARGIN * 2.,
+                h: panel.y + panel.h - MARGIN - y
             },
-            kind: TextKind::UI,
+            TextKind::Debug,
+            draw::TEXT_LAYER,
+        );
+
+        let xy = draw_xy_from_tile(&state.sizes, camera, state.inspector.cursor);
+        if is_on_board(&state.sizes, camera, xy) {
+            commands.push(Sprite(SpriteSpec{
+                sprite: state.board.tiles.tiles[tile::xy_to_i(state.inspector.cursor)].sprite(),
+                xy,
+                tint: draw::Colour::BLUE,
+                scale: camera.zoom,
+                layer: draw::HIGHLIGHT_LAYER,
+                ..<_>::default()
+            }));
+        }
+    }
+
+    {
+        let panel = &state.sizes.second_panel_xywh;
+
+        let text: draw::LongStrBuf = if state.board.solved {
+            "{green}Solved!{/}".into()
+        } else {
+            str_buf!(
+                "{{red}}Red{{/}} arrows only let you leave their way, {{green}}green{{/}} arrows also let you go back. Reach the {{green}}goal{{/}} in {} moves.",
+                state.board.metrics.min_moves,
+            )
+        };
+
+        push_text(
+            commands,
+            &state.sizes,
+            &text,
+            DrawXY { x: panel.x + MARGIN, y: panel.y + MARGIN },
+            DrawWH {
+                w: panel.w - MARGIN * 2.,
+                h: small_section_h * 2.
+            },
+            TextKind::Body,
+            draw::TEXT_LAYER,
+        );
+
+        if state.scenes.base() == scene::Scene::Gameplay {
+            let y = panel.y + MARGIN + small_section_h * 2.;
+
+            push_text(
+                commands,
+                &state.sizes,
+                &gameplay_stats(state),
+                DrawXY { x: panel.x + MARGIN, y },
+                DrawWH {
+                    w: panel.w - MARGIN * 2.,
+                    h: small_section_h * 3.,
+                },
+                TextKind::Body,
+                draw::TEXT_LAYER,
+            );
+        }
+    }
+}
+
+fn gameplay_stats(state: &State) -> draw::LongStrBuf {
+    use core::fmt::Write;
+
+    let board = &state.board;
+
+    let mut stats: draw::LongStrBuf = str_buf!(
+        "Seed {}, level {}\n",
+        seed_code(state),
+        state.level
+    );
+
+    // Anything past the end of the buffer is cut off.
+    let _ = if board.players.is_multiplayer() {
+        for (player, eye) in board.eyes().iter().enumerate() {
+            let _ = writeln!(
+                stats,
+                "{{{}}}P{}{{/}}: {} moves (par {}){}",
+                players::COLOUR_NAMES[player],
+                player + 1,
+                eye.moves,
+                eye.par_moves,
+                if eye.finished_at.is_some() { ", done" } else { "" },
+            );
+        }
+
+        #[cfg(feature = "alloc")]
+        if let Some(netplay::Netplay::Playing(session)) = &state.netplay {
+            let _ = writeln!(
+                stats,
+                "Online as P{}: {} rollbacks, {} waits",
+                session.local_player() + 1,
+                session.stats.rollbacks,
+                session.stats.waits,
+            );
+        }
+
+        write!(stats, "Time: {:.1}s", score::seconds(board.ticks))
+    } else {
+        let eye = &board.eyes[0];
+
+        write!(
+            stats,
+            "Moves: {} (par {})\nTime: {:.1}s",
+            eye.moves,
+            eye.par_moves,
+            score::seconds(board.ticks),
+        )
+    };
+
+    stats
+}
+
+/// We want text to be readable on all reasonable screen sizes, so we try to
+/// keep it from getting too small, even if it then won't fit.
+const MIN_FONT_SIZE: text::FontSize = 8.;
+
+fn push_text(
+    commands: &mut dyn ClearableStorage<draw::Command>,
+    sizes: &Sizes,
+    text: &str,
+    xy: DrawXY,
+    wh: DrawWH,
+    kind: draw::TextKind,
+    layer: draw::Layer,
+) {
+    let style = kind.style();
+
+    text::layout(text, &text::LayoutSpec {
+        xy,
+        wh,
+        // Tiny windows have tiny tiles, but the text should still be readable.
+        max_size: f32::max(sizes.tile_side_length * style.size_per_tile, MIN_FONT_SIZE),
+        min_size: MIN_FONT_SIZE,
+        h_align: style.h_align,
+        v_align: style.v_align,
+        colour: style.colour,
+        markup: style.markup,
+        ..<_>::default()
+    }, |layout, run| {
+        commands.push(draw::Command::Text(draw::TextSpec {
+            text: run.text.into(),
+            xy: run.xy,
+            size: layout.size,
+            advance: layout.advance,
+            colour: run.colour,
+            kind,
+            layer,
         }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xy(x: usize, y: usize) -> tile::XY {
+        tile::i_to_xy(tile::xy_to_i_usize((x, y)))
+    }
+
+    /// A state with a floor board, apart from the given tiles, and an eye at
+    /// each of `starts`.
+    fn state_with(
+        mode: players::Mode,
+        tiles: &[((usize, usize), TileKind)],
+        starts: &[(usize, usize)],
+    ) -> State {
+        let mut state = State::default();
+        let board = &mut state.board;
+
+        for &(xy, kind) in tiles {
+            board.tiles.tiles[tile::xy_to_i_usize(xy)].kind = kind;
+        }
+        board.players = players::Setup {
+            count: starts.len() as players::PlayerCount,
+            mode,
+        };
+        for (eye, &(x, y)) in board.eyes.iter_mut().zip(starts) {
+            eye.xy = xy(x, y);
+        }
+
+        state
+    }
+
+    /// Gives `player` the input for one step, then steps until every eye has
+    /// finished moving.
+    fn step_until_still(state: &mut State, player: PlayerIndex, input: Input) {
+        let mut inputs = [Input::NoChange; MAX_PLAYERS];
+        inputs[player] = input;
+        update_step(state, inputs);
+
+        // Much longer than sliding across the whole board takes.
+        for _ in 0..TILES_LENGTH * 32 {
+            let is_still = state.board.eyes().iter()
+                .all(|eye| eye.offset_xy == o_xy!{} && eye.arriving.is_none());
+            if is_still {
+                break
+            }
+
+            update_step(state, [Input::NoChange; MAX_PLAYERS]);
+        }
+    }
+
+    fn xys(state: &State) -> Vec<tile::XY> {
+        state.board.eyes().iter().map(|eye| eye.xy).collect()
+    }
+
+    #[test]
+    fn eyes_cannot_move_onto_each_other() {
+        let mut state = state_with(players::Mode::CoOp, &[], &[(0, 0), (1, 0)]);
+
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+        assert_eq!(xys(&state), [xy(0, 0), xy(1, 0)]);
+        // The eye still looks that way, to show the input was read.
+        assert_eq!(state.board.eyes[0].state, EyeState::Moved(Dir::Right));
+        assert_eq!(state.board.eyes[0].moves, 0);
+
+        step_until_still(&mut state, 1, Input::Dir(Dir::Right));
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+        assert_eq!(xys(&state), [xy(1, 0), xy(2, 0)]);
+        assert_eq!(state.board.eyes[0].moves, 1);
+    }
+
+    #[test]
+    fn eyes_stop_slides_and_teleports_onto_each_other() {
+        let mut state = state_with(
+            players::Mode::CoOp,
+            &[
+                ((1, 0), TileKind::Ice),
+                ((2, 0), TileKind::Ice),
+                ((0, 2), TileKind::Teleporter(0)),
+                ((5, 5), TileKind::Teleporter(0)),
+            ],
+            &[(0, 0), (3, 0), (5, 5)],
+        );
+
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+        assert_eq!(state.board.eyes[0].xy, xy(2, 0));
+
+        // The partner teleporter has an eye on it, so this eye stays put.
+        step_until_still(&mut state, 0, Input::Dir(Dir::DownLeft));
+        step_until_still(&mut state, 0, Input::Dir(Dir::Left));
+        step_until_still(&mut state, 0, Input::Dir(Dir::Down));
+        assert_eq!(state.board.eyes[0].xy, xy(0, 2));
+    }
+
+    #[test]
+    fn finished_eyes_stop_blocking() {
+        let mut state = state_with(
+            players::Mode::CoOp,
+            &[((1, 0), TileKind::Goal)],
+            &[(0, 0), (1, 0)],
+        );
+
+        step_until_still(&mut state, 1, Input::NoChange);
+        assert!(state.board.eyes[1].finished_at.is_some());
+        assert!(!state.board.solved);
+
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+        assert_eq!(xys(&state), [xy(1, 0), xy(1, 0)]);
+    }
+
+    #[test]
+    fn co_op_boards_are_solved_once_every_eye_finishes() {
+        let mut state = state_with(
+            players::Mode::CoOp,
+            &[((1, 0), TileKind::Goal), ((6, 6), TileKind::Goal)],
+            &[(0, 0), (5, 5)],
+        );
+
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+        assert!(state.board.eyes[0].finished_at.is_some());
+        assert!(!state.board.solved);
+
+        step_until_still(&mut state, 1, Input::Dir(Dir::DownRight));
+        assert!(state.board.solved);
+    }
+
+    #[test]
+    fn versus_boards_are_solved_once_any_eye_finishes() {
+        let mut state = state_with(
+            players::Mode::Versus,
+            &[((1, 0), TileKind::Goal), ((6, 6), TileKind::Goal)],
+            &[(0, 0), (5, 5)],
+        );
+
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+        assert!(state.board.solved);
+
+        // Nobody moves once the board is solved.
+        step_until_still(&mut state, 1, Input::Dir(Dir::DownRight));
+        assert_eq!(state.board.eyes[1].xy, xy(5, 5));
+        assert_eq!(state.board.eyes[1].finished_at, None);
+    }
+
+    #[test]
+    fn each_player_gets_their_own_start_and_par() {
+        for count in 1..=MAX_PLAYERS as players::PlayerCount {
+            let players = players::Setup { count, mode: players::Mode::CoOp };
+            let level = level::generate([count; 16], 4);
+            let board = Board::from_seed([count; 16], 4, players);
+
+            let eyes = board.eyes();
+            assert_eq!(eyes.len(), usize::from(count));
+            assert_eq!(eyes[0].xy, level.start);
+            assert_eq!(eyes[0].par_moves, level.metrics.min_moves);
+
+            for (i, eye) in eyes.iter().enumerate() {
+                assert_eq!(
+                    Some(eye.par_moves),
+                    level::solve(&board.tiles.tiles, eye.xy).min_moves,
+                    "player {}",
+                    i + 1
+                );
+                assert!(
+                    eyes[..i].iter().all(|other| other.xy != eye.xy),
+                    "player {} shares a start",
+                    i + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn each_player_gets_their_own_moves_and_points() {
+        let mut state = state_with(
+            players::Mode::CoOp,
+            &[((2, 0), TileKind::Goal), ((8, 8), TileKind::Goal)],
+            &[(0, 0), (6, 8)],
+        );
+        state.board.eyes[0].par_moves = 2;
+        state.board.eyes[1].par_moves = 2;
+
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+        step_until_still(&mut state, 0, Input::Dir(Dir::Right));
+
+        // Player 2 takes the long way round.
+        for dir in [Dir::Up, Dir::Right, Dir::Right, Dir::Down] {
+            step_until_still(&mut state, 1, Input::Dir(dir));
+        }
+        assert!(state.board.solved);
+
+        let entry = |player| state.board.entry(state.seed, state.level, player).unwrap();
+        let (first, second) = (entry(0), entry(1));
+        assert_eq!((first.moves, first.par_moves), (2, 2));
+        assert_eq!((second.moves, second.par_moves), (4, 2));
+        assert!(first.ticks < second.ticks);
+        assert!(first.points() > second.points());
+
+        assert_eq!(state.board.entry(state.seed, state.level, 2), None);
+    }
+
+    #[test]
+    fn text_is_never_smaller_than_the_minimum() {
+        for w in [0., 1., 16., 1280.] {
+            let sizes = draw::fresh_sizes(DrawWH { w, h: w * 9. / 16. });
+
+            let mut commands = storage::ArrayStorage::<draw::Command, 16>::new();
+            push_text(
+                &mut commands,
+                &sizes,
+                "Readable",
+                DrawXY::default(),
+                sizes.draw_wh,
+                draw::TextKind::Body,
+                0,
+            );
+
+            assert!(!commands.is_empty(), "{}", w);
+            for command in commands.iter() {
+                match command {
+                    draw::Command::Text(spec) => {
+                        assert!(spec.size >= MIN_FONT_SIZE, "{}: {:?}", w, spec)
+                    },
+                    _ => panic!("{:?}", command),
+                }
+            }
+        }
     }
 }
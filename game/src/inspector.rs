@@ -0,0 +1,337 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use crate::{
+    cycle,
+    tile,
+    ArrowKind,
+    Dir,
+    EyeState,
+    InputFlags,
+    PlayerIndex,
+    TileKind,
+    State,
+    ANIMATION_TIMER_LENGTH,
+    INPUT_UP_PRESSED,
+    INPUT_DOWN_PRESSED,
+    INPUT_LEFT_PRESSED,
+    INPUT_RIGHT_PRESSED,
+    INPUT_INTERACT_PRESSED,
+};
+
+use core::fmt::Write;
+
+macro_rules! groups_def {
+    ($( $group: ident { $( $field: ident ),+ $(,)? } )+) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        enum Group {
+            $( $group ),+
+        }
+
+        impl Group {
+            const ALL: &'static [Self] = &[
+                $(Self::$group,)+
+            ];
+
+            fn fields(self) -> &'static [Field] {
+                match self {
+                    $(
+                        Self::$group => &[$(Field::$field,)+],
+                    )+
+                }
+            }
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        enum Field {
+            $( $( $field ),+ ),+
+        }
+    }
+}
+
+groups_def!{
+    Sizes {
+        Orientation,
+        DrawWH,
+        PlayXYWH,
+        BoardXYWH,
+        TileSideLength,
+    }
+    Board {
+        CursorX,
+        CursorY,
+        TileType,
+        TileDir,
+        TileArrowKind,
+    }
+    Eye {
+        EyePlayer,
+        EyeX,
+        EyeY,
+        EyeOffsetXY,
+        EyeAnim,
+    }
+    Camera {
+        CameraCenter,
+        CameraZoom,
+        CameraPan,
+    }
+    Timers {
+        AnimationTimer,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Row {
+    Group(Group),
+    Field(Field),
+}
+
+#[derive(Debug, Default)]
+pub struct Inspector {
+    pub is_open: bool,
+    /// The tile whose data we show, and can edit.
+    pub cursor: tile::XY,
+    /// Whose eye we show, and can edit.
+    player: PlayerIndex,
+    /// Indexed in the same order as `Group::ALL`.
+    expanded: [bool; Group::ALL.len()],
+    /// An index into the currently visible rows.
+    selected: usize,
+}
+
+impl Inspector {
+    fn rows(&self) -> impl Iterator<Item = Row> + '_ {
+        Group::ALL.iter().enumerate().flat_map(move |(i, &group)| {
+            let fields: &[Field] = if self.expanded[i] {
+                group.fields()
+            } else {
+                &[]
+            };
+
+            core::iter::once(Row::Group(group))
+                .chain(fields.iter().map(|&field| Row::Field(field)))
+        })
+    }
+
+    fn toggle_group(&mut self, group: Group) {
+        if let Some(i) = Group::ALL.iter().position(|&g| g == group) {
+            self.expanded[i] = !self.expanded[i];
+        }
+    }
+}
+
+/// The player whose eye is shown, kept in range in case there are fewer
+/// players than when they were picked.
+fn eye_player(state: &State) -> PlayerIndex {
+    core::cmp::min(state.inspector.player, state.board.eyes().len() - 1)
+}
+
+const EYE_STATES: [EyeState; 8] = [
+    EyeState::Idle,
+    EyeState::Moved(Dir::Up),
+    EyeState::NarrowAnimLeft,
+    EyeState::NarrowAnimCenter,
+    EyeState::NarrowAnimRight,
+    EyeState::SmallPupil,
+    EyeState::Closed,
+    EyeState::HalfLid,
+];
+
+fn edit(state: &mut State, field: Field, forward: bool) {
+    use Field::*;
+
+    macro_rules! step {
+        ($value: expr, $add: ident, $sub: ident) => {
+            $value = if forward { $value.$add() } else { $value.$sub() }
+        }
+    }
+
+    let cursor_i = tile::xy_to_i(state.inspector.cursor);
+    let player = eye_player(state);
+
+    match field {
+        CursorX => step!(
+            state.inspector.cursor.x,
+            saturating_add_one,
+            saturating_sub_one
+        ),
+        CursorY => step!(
+            state.inspector.cursor.y,
+            saturating_add_one,
+            saturating_sub_one
+        ),
+        TileType => {
+            let tile_data = &mut state.board.tiles.tiles[cursor_i];
+            tile_data.kind = tile_data.kind.cycled(forward);
+        },
+        TileDir => {
+            let tile_data = &mut state.board.tiles.tiles[cursor_i];
+            if let TileKind::Arrow(dir, kind) = tile_data.kind {
+                tile_data.kind = TileKind::Arrow(cycle(&Dir::ALL, dir, forward), kind);
+            }
+        },
+        TileArrowKind => {
+            let tile_data = &mut state.board.tiles.tiles[cursor_i];
+            if let TileKind::Arrow(dir, kind) = tile_data.kind {
+                tile_data.kind = TileKind::Arrow(dir, cycle(&ArrowKind::ALL, kind, forward));
+            }
+        },
+        EyePlayer => {
+            let count = state.board.eyes().len();
+            state.inspector.player = if forward {
+                (player + 1) % count
+            } else {
+                player.checked_sub(1).unwrap_or(count - 1)
+            };
+        },
+        EyeX => step!(
+            state.board.eyes[player].xy.x,
+            saturating_add_one,
+            saturating_sub_one
+        ),
+        EyeY => step!(
+            state.board.eyes[player].xy.y,
+            saturating_add_one,
+            saturating_sub_one
+        ),
+        EyeAnim => {
+            let eye = &mut state.board.eyes[player];
+            eye.state = cycle(&EYE_STATES, eye.state, forward);
+        },
+        CameraZoom => if forward {
+            state.camera.zoom_in();
+        } else {
+            state.camera.zoom_out();
+        },
+        AnimationTimer => {
+            state.animation_timer = if forward {
+                (state.animation_timer + 1) % ANIMATION_TIMER_LENGTH
+            } else {
+                state.animation_timer
+                    .checked_sub(1)
+                    .unwrap_or(ANIMATION_TIMER_LENGTH - 1)
+            };
+        },
+        // Everything else is derived from other values, so editing it directly
+        // would either do nothing, or get overwritten right away.
+        Orientation
+        | DrawWH
+        | PlayXYWH
+        | BoardXYWH
+        | TileSideLength
+        | EyeOffsetXY
+        | CameraCenter
+        | CameraPan => {},
+    }
+}
+
+/// Handles the inspector's input, while it is open. Should be called once per
+/// frame so that each press only does one thing.
+pub fn update(state: &mut State, input_flags: InputFlags) {
+    let row_count = state.inspector.rows().count();
+
+    if input_flags & INPUT_UP_PRESSED != 0 {
+        state.inspector.selected = state.inspector.selected
+            .checked_sub(1)
+            .unwrap_or(row_count - 1);
+    }
+    if input_flags & INPUT_DOWN_PRESSED != 0 {
+        state.inspector.selected = (state.inspector.selected + 1) % row_count;
+    }
+
+    let selected_row = state.inspector.rows().nth(state.inspector.selected);
+
+    match selected_row {
+        Some(Row::Group(group)) => {
+            if input_flags & (INPUT_INTERACT_PRESSED | INPUT_LEFT_PRESSED | INPUT_RIGHT_PRESSED) != 0 {
+                state.inspector.toggle_group(group);
+            }
+        },
+        Some(Row::Field(field)) => {
+            if input_flags & INPUT_LEFT_PRESSED != 0 {
+                edit(state, field, false);
+            }
+            if input_flags & INPUT_RIGHT_PRESSED != 0 {
+                edit(state, field, true);
+            }
+        },
+        None => {
+            // The selection can end up past the end after collapsing a group.
+            state.inspector.selected = 0;
+        },
+    }
+}
+
+fn write_value(output: &mut crate::draw::LongStrBuf, state: &State, field: Field) -> core::fmt::Result {
+    use Field::*;
+
+    let sizes = &state.sizes;
+    let tile_data = &state.board.tiles.tiles[tile::xy_to_i(state.inspector.cursor)];
+    let player = eye_player(state);
+    let eye = &state.board.eyes[player];
+
+    match field {
+        Orientation => write!(output, "{:?}", sizes.orientation),
+        DrawWH => write!(output, "{:?}", sizes.draw_wh),
+        PlayXYWH => write!(output, "{:?}", sizes.play_xywh),
+        BoardXYWH => write!(output, "{:?}", sizes.board_xywh),
+        TileSideLength => write!(output, "{:?}", sizes.tile_side_length),
+        CursorX => write!(output, "{:?}", tile::Coord::from(state.inspector.cursor.x)),
+        CursorY => write!(output, "{:?}", tile::Coord::from(state.inspector.cursor.y)),
+        TileType => write!(output, "{:?}", tile_data.kind),
+        TileDir => match tile_data.kind {
+            TileKind::Arrow(dir, _) => write!(output, "{:?}", dir),
+            _ => write!(output, "-"),
+        },
+        TileArrowKind => match tile_data.kind {
+            TileKind::Arrow(_, kind) => write!(output, "{:?}", kind),
+            _ => write!(output, "-"),
+        },
+        // Shown starting from 1, like everywhere else players are named.
+        EyePlayer => write!(output, "{} of {}", player + 1, state.board.eyes().len()),
+        EyeX => write!(output, "{:?}", tile::Coord::from(eye.xy.x)),
+        EyeY => write!(output, "{:?}", tile::Coord::from(eye.xy.y)),
+        EyeOffsetXY => write!(output, "{:?}", eye.offset_xy),
+        EyeAnim => write!(output, "{:?}", eye.state),
+        CameraCenter => write!(output, "{:?}", state.camera.center),
+        CameraZoom => write!(output, "{:?}", state.camera.zoom),
+        CameraPan => write!(output, "{:?}", state.camera.pan),
+        AnimationTimer => write!(output, "{:?}", state.animation_timer),
+    }
+}
+
+/// Returns the text to show for the inspector, one row per line.
+pub fn text(state: &State) -> crate::draw::LongStrBuf {
+    let mut output = crate::draw::LongStrBuf::new();
+
+    for (i, row) in state.inspector.rows().enumerate() {
+        let marker = if i == state.inspector.selected { ">" } else { " " };
+
+        // Anything past the end of the buffer is cut off, so we ignore the
+        // results.
+        let _ = match row {
+            Row::Group(group) => {
+                let expanded = Group::ALL.iter()
+                    .position(|&g| g == group)
+                    .map(|i| state.inspector.expanded[i])
+                    .unwrap_or_default();
+
+                writeln!(
+                    output,
+                    "{}{} {:?}",
+                    marker,
+                    if expanded { "-" } else { "+" },
+                    group
+                )
+            },
+            Row::Field(field) => {
+                let _ = write!(output, "{}   {:?}: ", marker, field);
+                let _ = write_value(&mut output, state, field);
+                writeln!(output)
+            },
+        };
+    }
+
+    output
+}
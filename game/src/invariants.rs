@@ -0,0 +1,210 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Properties that should hold whatever `update` is given, checked by the
+//! property tests below, and by the fuzz target in `fuzz/`.
+
+use crate::{
+    draw,
+    tile,
+    DeltaTimeInSeconds,
+    DrawWH,
+    Input,
+    PlayerInputs,
+    Seed,
+    State,
+    MAX_PLAYERS,
+    TILE_OFFSET,
+};
+
+use alloc::vec::Vec;
+
+/// The arguments for one call to `update`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Update {
+    pub player_inputs: PlayerInputs,
+    pub draw_wh: DrawWH,
+    pub dt: DeltaTimeInSeconds,
+}
+
+/// Long enough for an eye to finish any move, including sliding all the way
+/// across the board on ice.
+const SETTLE_STEPS: usize = (TILE_OFFSET as usize + 1)
+    * (tile::X::COUNT + tile::Y::COUNT) as usize;
+
+/// Runs the updates on the first level for the seed, panicking if any of the
+/// properties don't hold.
+pub fn check(seed: Seed, updates: &[Update]) {
+    let mut state = State::from_seed(seed);
+    // Most of what could go wrong is on a board, rather than in the menus.
+    crate::start_level(&mut state, 1);
+
+    let mut commands: Vec<draw::Command> = Vec::new();
+
+    for (i, update) in updates.iter().enumerate() {
+        crate::update(
+            &mut state,
+            &mut commands,
+            update.player_inputs,
+            update.draw_wh,
+            update.dt,
+        );
+
+        check_eyes(&state, i);
+        check_commands(&commands, i);
+    }
+
+    // However the eyes were left, they should come to rest once nobody is
+    // pressing anything.
+    for _ in 0..SETTLE_STEPS {
+        crate::update_step(&mut state, [Input::NoChange; MAX_PLAYERS]);
+    }
+
+    for (player, eye) in state.board.eyes().iter().enumerate() {
+        assert_eq!(
+            eye.offset_xy,
+            <_>::default(),
+            "player {}'s eye did not settle after {} steps: {:?}",
+            player,
+            SETTLE_STEPS,
+            eye
+        );
+    }
+}
+
+fn check_eyes(state: &State, update_index: usize) {
+    for (player, eye) in state.board.eyes().iter().enumerate() {
+        assert!(
+            tile::Coord::from(eye.xy.x) <= tile::X::MAX
+            && tile::Coord::from(eye.xy.y) <= tile::Y::MAX,
+            "after update {}, player {}'s eye is off the board: {:?}",
+            update_index,
+            player,
+            eye
+        );
+
+        assert!(
+            eye.offset_xy.x.0.unsigned_abs() <= TILE_OFFSET.unsigned_abs()
+            && eye.offset_xy.y.0.unsigned_abs() <= TILE_OFFSET.unsigned_abs(),
+            "after update {}, player {}'s eye is more than a tile from where it \
+            is meant to be: {:?}",
+            update_index,
+            player,
+            eye
+        );
+    }
+}
+
+fn check_commands(commands: &[draw::Command], update_index: usize) {
+    for command in commands {
+        let xy = match command {
+            draw::Command::Sprite(spec) => spec.xy,
+            draw::Command::Text(spec) => spec.xy,
+            draw::Command::Rect(spec) => spec.xy,
+        };
+
+        assert!(
+            xy.x.is_finite() && xy.y.is_finite(),
+            "after update {}, a command has a position that isn't finite: {:?}",
+            update_index,
+            command
+        );
+    }
+}
+
+/// How many bytes `check_bytes` reads for each update.
+const UPDATE_LENGTH: usize = 4 * MAX_PLAYERS + 4 * 3;
+
+/// Reads a seed, then as many updates as there are bytes for, and passes them
+/// to `check`, so any bytes at all make a valid run.
+pub fn check_bytes(bytes: &[u8]) {
+    let (seed_bytes, rest) = bytes.split_at(bytes.len().min(16));
+    let mut seed = Seed::default();
+    seed[..seed_bytes.len()].copy_from_slice(seed_bytes);
+
+    let updates: Vec<Update> = rest.chunks_exact(UPDATE_LENGTH)
+        .map(|chunk| {
+            let word = |i: usize| u32::from_le_bytes([
+                chunk[4 * i],
+                chunk[4 * i + 1],
+                chunk[4 * i + 2],
+                chunk[4 * i + 3],
+            ]);
+
+            let mut update = Update {
+                draw_wh: DrawWH {
+                    w: f32::from_bits(word(MAX_PLAYERS)),
+                    h: f32::from_bits(word(MAX_PLAYERS + 1)),
+                },
+                dt: f32::from_bits(word(MAX_PLAYERS + 2)),
+                ..<_>::default()
+            };
+            for (player, flags) in update.player_inputs.iter_mut().enumerate() {
+                *flags = word(player);
+            }
+
+            update
+        })
+        .collect();
+
+    check(seed, &updates);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{draw::DrawLength, InputFlags};
+    use proptest::prelude::*;
+
+    /// Mostly ordinary lengths, with arbitrary bit patterns now and then, so we
+    /// cover NaNs, infinities, negative numbers and subnormals too.
+    fn length() -> impl Strategy<Value = DrawLength> {
+        prop_oneof![
+            3 => 0. ..4096f32,
+            1 => any::<f32>(),
+        ]
+    }
+
+    fn dt() -> impl Strategy<Value = DeltaTimeInSeconds> {
+        prop_oneof![
+            3 => 0. ..0.1f32,
+            1 => any::<f32>(),
+        ]
+    }
+
+    /// Mostly directions and interact, since with arbitrary flags the menu
+    /// would be opened and closed half the time, and the eyes would hardly
+    /// move.
+    fn flags() -> impl Strategy<Value = InputFlags> {
+        prop_oneof![
+            3 => 0..0x200 as InputFlags,
+            1 => any::<InputFlags>(),
+        ]
+    }
+
+    fn update() -> impl Strategy<Value = Update> {
+        (prop::array::uniform4(flags()), length(), length(), dt())
+            .prop_map(|(player_inputs, w, h, dt)| Update {
+                player_inputs,
+                draw_wh: DrawWH { w, h },
+                dt,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn update_keeps_the_invariants(
+            seed in any::<Seed>(),
+            updates in prop::collection::vec(update(), 0..64),
+        ) {
+            check(seed, &updates);
+        }
+
+        #[test]
+        fn any_bytes_make_a_valid_run(
+            bytes in prop::collection::vec(any::<u8>(), 0..1024),
+        ) {
+            check_bytes(&bytes);
+        }
+    }
+}
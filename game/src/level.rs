@@ -0,0 +1,550 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Generates levels that are guaranteed to be solvable, by laying down a route
+//! from the start to the goal first, then checking the result with a solver.
+//!
+//! The rules are:
+//! * The eye can only leave a red arrow's tile in the direction the arrow
+//!   points, and can only leave a green arrow's tile in the direction the arrow
+//!   points, or the opposite one.
+//! * Floor, ice, teleporter and goal tiles can be left in any direction.
+//! * Walls can't be entered.
+//! * After moving onto ice, the eye keeps sliding in the same direction until
+//!   it reaches a tile that isn't ice, or can't go any further.
+//! * After moving onto a teleporter, the eye is moved to the other teleporter
+//!   with the same id, if there is one.
+//! * Reaching a goal tile solves the level.
+
+use crate::{
+    tile,
+    xs_from_seed,
+    xs_u32,
+    ArrowKind,
+    Dir,
+    Seed,
+    TeleporterId,
+    TileData,
+    TileDataArray,
+    TileKind,
+    Tiles,
+    Xs,
+    TILES_LENGTH,
+    TELEPORTER_PAIR_COUNT,
+};
+
+pub type MoveCount = u16;
+
+/// Higher is harder. Level numbers are used directly as difficulties.
+pub type Difficulty = u8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Params {
+    /// How many moves the route laid down from the start to the goal takes.
+    pub route_length: MoveCount,
+    /// Layouts that can be solved in fewer moves than this are rejected, unless
+    /// we run out of attempts.
+    pub min_moves: MoveCount,
+    /// Out of 256. How likely each arrow is to be green. Green arrows give the
+    /// player more choices.
+    pub green_chance: u8,
+    /// Out of 256. How likely each tile off of the route is to be a wall.
+    pub wall_chance: u8,
+    /// Out of 256. How likely each tile off of the route is to be ice.
+    pub ice_chance: u8,
+    /// Out of 256. How likely each tile is to be floor, instead of an arrow.
+    pub floor_chance: u8,
+    /// Capped at `TELEPORTER_PAIR_COUNT`.
+    pub teleporter_pairs: TeleporterId,
+    pub max_attempts: u16,
+}
+
+impl Params {
+    pub fn from_difficulty(difficulty: Difficulty) -> Self {
+        let moves = MoveCount::from(difficulty);
+        Self {
+            route_length: 4 + moves * 4,
+            min_moves: 2 + moves * 3,
+            green_chance: 96,
+            wall_chance: 48,
+            ice_chance: core::cmp::min(difficulty.saturating_mul(8), 48),
+            floor_chance: 16,
+            teleporter_pairs: difficulty / 2,
+            max_attempts: 64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The fewest moves it takes to get from the start to the goal.
+    pub min_moves: MoveCount,
+    /// How many tiles can be reached from the start at all, including the
+    /// start. More reachable tiles means more places to get lost.
+    pub reachable: tile::Count,
+    /// Which of the generated layouts this one was, starting from 1.
+    pub attempts: u16,
+}
+
+/// A generated board, for one player.
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub tiles: Tiles,
+    pub start: tile::XY,
+    pub metrics: Metrics,
+}
+
+fn dir_delta(dir: Dir) -> (i8, i8) {
+    use Dir::*;
+    match dir {
+        Up => (0, -1),
+        UpRight => (1, -1),
+        Right => (1, 0),
+        DownRight => (1, 1),
+        Down => (0, 1),
+        DownLeft => (-1, 1),
+        Left => (-1, 0),
+        UpLeft => (-1, -1),
+    }
+}
+
+fn opposite(dir: Dir) -> Dir {
+    use Dir::*;
+    match dir {
+        Up => Down,
+        UpRight => DownLeft,
+        Right => Left,
+        DownRight => UpLeft,
+        Down => Up,
+        DownLeft => UpRight,
+        Left => Right,
+        UpLeft => DownRight,
+    }
+}
+
+/// Where moving in `dir` from `xy` ends up. Like the eye's movement, each axis
+/// stops at the edge of the board separately, so a diagonal move along an edge
+/// only moves along the edge. Returns `None` if the move goes nowhere.
+pub(crate) fn neighbour(xy: tile::XY, dir: Dir) -> Option<tile::XY> {
+    let (dx, dy) = dir_delta(dir);
+
+    let clamp = |coord: tile::Coord, delta: i8, max: tile::Coord| {
+        (coord as i16 + delta as i16).clamp(0, max as i16) as usize
+    };
+
+    let x = clamp(xy.x.into(), dx, tile::X::MAX);
+    let y = clamp(xy.y.into(), dy, tile::Y::MAX);
+
+    let output = tile::i_to_xy(tile::xy_to_i_usize((x, y)));
+
+    if output == xy {
+        None
+    } else {
+        Some(output)
+    }
+}
+
+pub(crate) fn can_leave(tile_data: TileData, dir: Dir) -> bool {
+    use TileKind::*;
+    match tile_data.kind {
+        Floor | Goal | Ice | Teleporter(_) => true,
+        // We shouldn't ever be on a wall, but if we are, let us get off.
+        Wall => true,
+        Arrow(arrow_dir, ArrowKind::Red) => arrow_dir == dir,
+        Arrow(arrow_dir, ArrowKind::Green) => arrow_dir == dir || opposite(arrow_dir) == dir,
+    }
+}
+
+/// Like `neighbour`, but returns `None` if the neighbour is a wall.
+pub(crate) fn enterable_neighbour(
+    tiles: &TileDataArray,
+    xy: tile::XY,
+    dir: Dir,
+) -> Option<tile::XY> {
+    neighbour(xy, dir)
+        .filter(|next| tiles[tile::xy_to_i(*next)].kind != TileKind::Wall)
+}
+
+/// Whether the player can start a move in `dir` from `xy`.
+pub(crate) fn can_move(tiles: &TileDataArray, xy: tile::XY, dir: Dir) -> bool {
+    can_leave(tiles[tile::xy_to_i(xy)], dir)
+    && enterable_neighbour(tiles, xy, dir).is_some()
+}
+
+/// The other teleporter with the same id as the one at `xy`, if there is one.
+pub(crate) fn teleporter_partner(tiles: &TileDataArray, xy: tile::XY) -> Option<tile::XY> {
+    let xy_i = tile::xy_to_i(xy);
+    let id = match tiles[xy_i].kind {
+        TileKind::Teleporter(id) => id,
+        _ => return None,
+    };
+
+    tiles.iter()
+        .enumerate()
+        .position(|(i, tile_data)| {
+            i != xy_i && tile_data.kind == TileKind::Teleporter(id)
+        })
+        .map(tile::i_to_xy)
+}
+
+/// Where the eye ends up after a move in `dir` onto `xy`, once it has finished
+/// sliding and teleporting. This does all at once what `update_step` does over
+/// several steps, so the two need to be kept in sync.
+pub(crate) fn resting_place(tiles: &TileDataArray, mut xy: tile::XY, dir: Dir) -> tile::XY {
+    loop {
+        match tiles[tile::xy_to_i(xy)].kind {
+            TileKind::Ice => match enterable_neighbour(tiles, xy, dir) {
+                Some(next) => xy = next,
+                None => return xy,
+            },
+            TileKind::Teleporter(_) => {
+                return teleporter_partner(tiles, xy).unwrap_or(xy)
+            },
+            TileKind::Floor
+            | TileKind::Wall
+            | TileKind::Goal
+            | TileKind::Arrow(..) => return xy,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Solution {
+    /// `None` if no goal can be reached.
+    pub min_moves: Option<MoveCount>,
+    pub reachable: tile::Count,
+}
+
+/// A breadth first search over the tiles, which doesn't allocate. Finds the
+/// fewest moves to the nearest goal tile.
+pub(crate) fn solve(tiles: &TileDataArray, start: tile::XY) -> Solution {
+    let mut distances: [Option<MoveCount>; TILES_LENGTH] = [None; TILES_LENGTH];
+    // Each tile is queued at most once, so this is always big enough.
+    let mut queue = [0usize; TILES_LENGTH];
+    let mut queue_start = 0;
+    let mut queue_end = 0;
+
+    let mut min_moves = None;
+
+    let start_i = tile::xy_to_i(start);
+    distances[start_i] = Some(0);
+    queue[queue_end] = start_i;
+    queue_end += 1;
+
+    while queue_start < queue_end {
+        let i = queue[queue_start];
+        queue_start += 1;
+
+        let distance = distances[i].unwrap_or_default();
+
+        // The queue is in order of distance, so the first goal is the nearest.
+        if min_moves.is_none() && tiles[i].kind == TileKind::Goal {
+            min_moves = Some(distance);
+        }
+
+        let xy = tile::i_to_xy(i);
+
+        for dir in Dir::ALL {
+            if !can_move(tiles, xy, dir) {
+                continue
+            }
+
+            if let Some(next) = enterable_neighbour(tiles, xy, dir) {
+                let next = resting_place(tiles, next, dir);
+                let next_i = tile::xy_to_i(next);
+                if distances[next_i].is_none() {
+                    distances[next_i] = Some(distance + 1);
+                    queue[queue_end] = next_i;
+                    queue_end += 1;
+                }
+            }
+        }
+    }
+
+    Solution {
+        min_moves,
+        reachable: queue_end as tile::Count,
+    }
+}
+
+/// Picks where other eyes should start, for multiplayer. Each is as close as
+/// possible to being `min_moves` from a goal, like the eye at `start`, so no
+/// player gets an easier board, with ties going to tiles nearer to `start`.
+/// Each slot in `starts` gets a different tile, and its own fewest moves, until
+/// we run out of suitable tiles, after which the rest are left as `None`.
+pub(crate) fn other_starts(
+    tiles: &TileDataArray,
+    start: tile::XY,
+    min_moves: MoveCount,
+    starts: &mut [Option<(tile::XY, MoveCount)>],
+) {
+    let mut moves_from: [Option<MoveCount>; TILES_LENGTH] = [None; TILES_LENGTH];
+    for (i, moves) in moves_from.iter_mut().enumerate() {
+        // Eyes shouldn't start partway through a slide or teleport.
+        if let TileKind::Floor | TileKind::Arrow(..) = tiles[i].kind {
+            *moves = solve(tiles, tile::i_to_xy(i)).min_moves;
+        }
+    }
+    moves_from[tile::xy_to_i(start)] = None;
+
+    let distance_to_start = |xy: tile::XY| {
+        let d = |a: tile::Coord, b: tile::Coord| a.abs_diff(b);
+        core::cmp::max(d(xy.x.into(), start.x.into()), d(xy.y.into(), start.y.into()))
+    };
+
+    for slot in starts.iter_mut() {
+        let best = moves_from.iter()
+            .enumerate()
+            .filter_map(|(i, moves)| moves.map(|moves| (i, moves)))
+            .min_by_key(|&(i, moves)| (
+                moves.abs_diff(min_moves),
+                distance_to_start(tile::i_to_xy(i)),
+                i,
+            ));
+
+        *slot = best.map(|(i, moves)| {
+            moves_from[i] = None;
+            (tile::i_to_xy(i), moves)
+        });
+    }
+}
+
+fn chance(rng: &mut Xs, out_of_256: u8) -> bool {
+    xs_u32(rng, 0, 256) < u32::from(out_of_256)
+}
+
+fn arrow_or_floor(rng: &mut Xs, params: &Params, dir: Dir) -> TileKind {
+    if chance(rng, params.floor_chance) {
+        TileKind::Floor
+    } else if chance(rng, params.green_chance) {
+        TileKind::Arrow(dir, ArrowKind::Green)
+    } else {
+        TileKind::Arrow(dir, ArrowKind::Red)
+    }
+}
+
+/// Lays down a route from the start, made of arrows pointing along it and
+/// floor, with a goal at the end. Everything else is filled in at random. The
+/// route never crosses itself and is never overwritten, and none of its tiles
+/// do anything when moved onto, so the result is always solvable.
+fn attempt(rng: &mut Xs, params: &Params) -> (TileDataArray, tile::XY) {
+    let mut tiles = [TileData::default(); TILES_LENGTH];
+    let mut on_route = [false; TILES_LENGTH];
+
+    for tile_data in tiles.iter_mut() {
+        let roll = xs_u32(rng, 0, 256);
+        let wall_max = u32::from(params.wall_chance);
+        let ice_max = wall_max + u32::from(params.ice_chance);
+
+        tile_data.kind = if roll < wall_max {
+            TileKind::Wall
+        } else if roll < ice_max {
+            TileKind::Ice
+        } else {
+            let dir = Dir::from_rng(rng);
+            arrow_or_floor(rng, params, dir)
+        };
+    }
+
+    let start = tile::XY::from_rng(rng);
+    let mut current = start;
+    on_route[tile::xy_to_i(start)] = true;
+
+    for _ in 0..params.route_length {
+        let mut options = [Dir::Up; Dir::COUNT];
+        let mut option_count = 0;
+        for dir in Dir::ALL {
+            if let Some(next) = neighbour(current, dir) {
+                if !on_route[tile::xy_to_i(next)] {
+                    options[option_count] = dir;
+                    option_count += 1;
+                }
+            }
+        }
+
+        if option_count == 0 {
+            // We've walked into a dead end, so stop the route here.
+            break
+        }
+
+        let dir = options[xs_u32(rng, 0, option_count as u32) as usize];
+
+        tiles[tile::xy_to_i(current)].kind = arrow_or_floor(rng, params, dir);
+
+        current = neighbour(current, dir).unwrap_or(current);
+        on_route[tile::xy_to_i(current)] = true;
+    }
+
+    let goal = current;
+    tiles[tile::xy_to_i(goal)].kind = TileKind::Goal;
+
+    for id in 0..core::cmp::min(params.teleporter_pairs, TELEPORTER_PAIR_COUNT) {
+        for _ in 0..2 {
+            // Give up eventually, rather than looping forever on a full board.
+            for _ in 0..TILES_LENGTH {
+                let i = xs_u32(rng, 0, TILES_LENGTH as u32) as usize;
+                let is_free = !on_route[i]
+                    && !matches!(tiles[i].kind, TileKind::Teleporter(_));
+                if is_free {
+                    tiles[i].kind = TileKind::Teleporter(id);
+                    break
+                }
+            }
+        }
+    }
+
+    (tiles, start)
+}
+
+/// The level a board with this seed and difficulty starts from.
+pub fn generate(seed: Seed, difficulty: Difficulty) -> Level {
+    generate_with_params(seed, &Params::from_difficulty(difficulty))
+}
+
+/// Like `generate`, for trying out params other than the ones used in the game.
+pub fn generate_with_params(seed: Seed, params: &Params) -> Level {
+    let rng = &mut xs_from_seed(seed);
+    let mut best: Option<Level> = None;
+
+    for attempts in 1..=params.max_attempts.max(1) {
+        let (tiles, start) = attempt(rng, params);
+
+        let solution = solve(&tiles, start);
+
+        let min_moves = match solution.min_moves {
+            // A zero move level is not much of a level.
+            Some(0) | None => continue,
+            Some(min_moves) => min_moves,
+        };
+
+        let is_better = best.as_ref()
+            .is_none_or(|level| min_moves > level.metrics.min_moves);
+
+        if is_better {
+            best = Some(Level {
+                tiles: Tiles { tiles },
+                start,
+                metrics: Metrics {
+                    min_moves,
+                    reachable: solution.reachable,
+                    attempts,
+                },
+            });
+        }
+
+        if min_moves >= params.min_moves {
+            break
+        }
+    }
+
+    best.unwrap_or_else(|| {
+        // Only possible if every route hit a dead end right away, which can't
+        // happen on a board this size, but we still want to return something
+        // solvable.
+        let start = tile::XY::default();
+        let mut tiles = [TileData::default(); TILES_LENGTH];
+        let goal = neighbour(start, Dir::Right).unwrap_or(start);
+        tiles[tile::xy_to_i(goal)].kind = TileKind::Goal;
+        let solution = solve(&tiles, start);
+        Level {
+            tiles: Tiles { tiles },
+            start,
+            metrics: Metrics {
+                min_moves: solution.min_moves.unwrap_or_default(),
+                reachable: solution.reachable,
+                attempts: params.max_attempts,
+            },
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Input, State, MAX_PLAYERS};
+
+    #[test]
+    fn generated_levels_take_at_least_one_move() {
+        for difficulty in 0..=16 {
+            for seed_byte in 0..32 {
+                let level = generate([seed_byte; 16], difficulty);
+
+                let solution = solve(&level.tiles.tiles, level.start);
+                assert!(
+                    solution.min_moves >= Some(1),
+                    "difficulty {}, seed byte {}: {:?}",
+                    difficulty,
+                    seed_byte,
+                    solution,
+                );
+                assert_eq!(solution.min_moves, Some(level.metrics.min_moves));
+            }
+        }
+    }
+
+    /// Moves an eye one way from `xy` with `update_step`, and returns where it
+    /// stops once it has finished sliding and teleporting.
+    fn stepped_resting_place(
+        state: &mut State,
+        tiles: &Tiles,
+        xy: tile::XY,
+        dir: Dir,
+    ) -> tile::XY {
+        state.board.tiles = tiles.clone();
+        state.board.solved = false;
+        state.board.eyes[0] = <_>::default();
+        state.board.eyes[0].xy = xy;
+
+        let mut inputs = [Input::NoChange; MAX_PLAYERS];
+        inputs[0] = Input::Dir(dir);
+        crate::update_step(state, inputs);
+
+        // Much longer than sliding across the whole board takes.
+        for _ in 0..TILES_LENGTH * 32 {
+            let eye = &state.board.eyes[0];
+            if eye.offset_xy == <_>::default() && eye.arriving.is_none() {
+                break
+            }
+            crate::update_step(state, [Input::NoChange; MAX_PLAYERS]);
+        }
+
+        state.board.eyes[0].xy
+    }
+
+    #[test]
+    fn resting_place_agrees_with_update_step() {
+        let mut state = State::from_seed([0; 16]);
+
+        // High enough difficulties to have plenty of ice and teleporters.
+        for difficulty in [6, 10, 14] {
+            for seed_byte in 0..4 {
+                let tiles = generate([seed_byte; 16], difficulty).tiles;
+
+                for i in 0..TILES_LENGTH {
+                    let xy = tile::i_to_xy(i);
+                    // Eyes only ever start moves from tiles they can rest on.
+                    if !matches!(tiles.tiles[i].kind, TileKind::Floor | TileKind::Arrow(..)) {
+                        continue
+                    }
+
+                    for dir in Dir::ALL {
+                        let Some(next) = enterable_neighbour(&tiles.tiles, xy, dir)
+                            .filter(|_| can_move(&tiles.tiles, xy, dir)) else {
+                            continue
+                        };
+
+                        assert_eq!(
+                            stepped_resting_place(&mut state, &tiles, xy, dir),
+                            resting_place(&tiles.tiles, next, dir),
+                            "difficulty {}, seed byte {}, from {:?} going {:?}",
+                            difficulty,
+                            seed_byte,
+                            xy,
+                            dir,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
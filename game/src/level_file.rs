@@ -0,0 +1,382 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! A plain text format for levels, so they can be edited by hand too. It looks
+//! like this, with one line of two character tiles per row of the board:
+//!
+//! ```text
+//! version = 1
+//! start = 0, 0
+//! .. ## GG ~~ T0 r2 g6 ...
+//! ...
+//! ```
+//!
+//! The `version` line has to come first, apart from blank lines and comments.
+//! `..` is floor, `##` is a wall, `GG` is a goal, `~~` is ice, `T` followed by
+//! a digit is a teleporter with that id, and `r` or `g` followed by a digit is a
+//! red or green arrow, pointing in the direction with that index in `Dir::ALL`.
+
+use crate::{
+    tile,
+    ArrowKind,
+    Dir,
+    TileData,
+    TileDataArray,
+    TileKind,
+    TILES_LENGTH,
+    TELEPORTER_PAIR_COUNT,
+};
+
+pub type Version = u32;
+
+pub const CURRENT_VERSION: Version = 1;
+
+pub type LineNumber = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    MissingVersion,
+    UnknownVersion,
+    BadStart,
+    BadTile,
+    WrongRowLength,
+    WrongRowCount,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Starting from 1, like text editors do.
+    pub line: LineNumber,
+    pub kind: ParseErrorKind,
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use ParseErrorKind::*;
+        let description = match self.kind {
+            MissingVersion => "expected `version = 1` first",
+            UnknownVersion => "unknown version",
+            BadStart => "expected `start = x, y` within the board",
+            BadTile => "unknown tile",
+            WrongRowLength => "wrong number of tiles in row",
+            WrongRowCount => "wrong number of rows",
+        };
+
+        write!(f, "line {}: {}", self.line, description)
+    }
+}
+
+fn tile_code(kind: TileKind) -> [char; 2] {
+    fn digit(n: usize) -> char {
+        char::from_digit(n as u32, 10).unwrap_or('?')
+    }
+
+    use TileKind::*;
+    match kind {
+        Floor => ['.', '.'],
+        Wall => ['#', '#'],
+        Goal => ['G', 'G'],
+        Ice => ['~', '~'],
+        Teleporter(id) => ['T', digit(usize::from(id))],
+        Arrow(dir, arrow_kind) => [
+            match arrow_kind {
+                ArrowKind::Red => 'r',
+                ArrowKind::Green => 'g',
+            },
+            digit(Dir::ALL.iter().position(|&d| d == dir).unwrap_or(0)),
+        ],
+    }
+}
+
+fn parse_tile(code: &str) -> Option<TileKind> {
+    let mut chars = code.chars();
+    let (first, second) = (chars.next()?, chars.next()?);
+    if chars.next().is_some() {
+        return None
+    }
+
+    let digit = second.to_digit(10).map(|d| d as usize);
+
+    use TileKind::*;
+    match (first, second) {
+        ('.', '.') => Some(Floor),
+        ('#', '#') => Some(Wall),
+        ('G', 'G') => Some(Goal),
+        ('~', '~') => Some(Ice),
+        ('T', _) => digit
+            .filter(|&d| d < usize::from(TELEPORTER_PAIR_COUNT))
+            .map(|d| Teleporter(d as _)),
+        ('r', _) => Some(Arrow(*Dir::ALL.get(digit?)?, ArrowKind::Red)),
+        ('g', _) => Some(Arrow(*Dir::ALL.get(digit?)?, ArrowKind::Green)),
+        _ => None,
+    }
+}
+
+pub fn write(
+    w: &mut dyn core::fmt::Write,
+    tiles: &TileDataArray,
+    start: tile::XY,
+) -> core::fmt::Result {
+    writeln!(w, "version = {}", CURRENT_VERSION)?;
+    writeln!(
+        w,
+        "start = {}, {}",
+        tile::Coord::from(start.x),
+        tile::Coord::from(start.y)
+    )?;
+
+    for row in tiles.chunks(tile::X::COUNT as usize) {
+        let mut needs_space = false;
+        for tile_data in row {
+            if needs_space {
+                w.write_char(' ')?;
+            }
+            needs_space = true;
+
+            let [c0, c1] = tile_code(tile_data.kind);
+            w.write_char(c0)?;
+            w.write_char(c1)?;
+        }
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
+
+/// Blank lines and lines starting with `#` followed by a space are ignored,
+/// as are unknown keys.
+pub fn parse(text: &str) -> Result<(TileDataArray, tile::XY), ParseError> {
+    let mut tiles = [TileData::default(); TILES_LENGTH];
+    let mut start = tile::XY::default();
+    let mut row_count = 0;
+    let mut last_line = 0;
+    let mut has_version = false;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        last_line = line_number;
+        let error = |kind| ParseError { line: line_number, kind };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("# ") {
+            continue
+        }
+
+        let key_value = line.split_once('=');
+
+        if !has_version {
+            match key_value {
+                Some((key, value)) if key.trim() == "version" => {
+                    if value.trim().parse() != Ok(CURRENT_VERSION) {
+                        return Err(error(ParseErrorKind::UnknownVersion))
+                    }
+                    has_version = true;
+                    continue
+                },
+                _ => return Err(error(ParseErrorKind::MissingVersion)),
+            }
+        }
+
+        if let Some((key, value)) = key_value {
+            match key.trim() {
+                "version" if value.trim().parse() != Ok(CURRENT_VERSION) => {
+                    return Err(error(ParseErrorKind::UnknownVersion))
+                },
+                "start" => {
+                    let (x, y) = value.split_once(',')
+                        .ok_or(error(ParseErrorKind::BadStart))?;
+                    let parse_coord = |s: &str, max: tile::Coord| {
+                        s.trim()
+                            .parse::<tile::Coord>()
+                            .ok()
+                            .filter(|&c| c <= max)
+                            .ok_or(error(ParseErrorKind::BadStart))
+                    };
+                    let x = parse_coord(x, tile::X::MAX)?;
+                    let y = parse_coord(y, tile::Y::MAX)?;
+                    start = tile::i_to_xy(
+                        tile::xy_to_i_usize((usize::from(x), usize::from(y)))
+                    );
+                },
+                _ => {},
+            }
+            continue
+        }
+
+        if row_count >= tile::Y::COUNT as usize {
+            return Err(error(ParseErrorKind::WrongRowCount))
+        }
+
+        let mut column_count = 0;
+        for code in line.split_whitespace() {
+            if column_count >= tile::X::COUNT as usize {
+                return Err(error(ParseErrorKind::WrongRowLength))
+            }
+
+            let kind = parse_tile(code).ok_or(error(ParseErrorKind::BadTile))?;
+            tiles[tile::xy_to_i_usize((column_count, row_count))].kind = kind;
+            column_count += 1;
+        }
+
+        if column_count != tile::X::COUNT as usize {
+            return Err(error(ParseErrorKind::WrongRowLength))
+        }
+
+        row_count += 1;
+    }
+
+    if !has_version {
+        return Err(ParseError {
+            line: last_line.max(1),
+            kind: ParseErrorKind::MissingVersion,
+        })
+    }
+
+    if row_count != tile::Y::COUNT as usize {
+        return Err(ParseError {
+            line: last_line,
+            kind: ParseErrorKind::WrongRowCount,
+        })
+    }
+
+    Ok((tiles, start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::LongStrBuf;
+
+    fn every_kind() -> impl Iterator<Item = TileKind> + Clone {
+        use TileKind::*;
+
+        [Floor, Wall, Goal, Ice].into_iter()
+            .chain((0..TELEPORTER_PAIR_COUNT).map(Teleporter))
+            .chain(Dir::ALL.into_iter().flat_map(|dir| [
+                Arrow(dir, ArrowKind::Red),
+                Arrow(dir, ArrowKind::Green),
+            ]))
+    }
+
+    fn kinds(tiles: &TileDataArray) -> [TileKind; TILES_LENGTH] {
+        tiles.map(|tile_data| tile_data.kind)
+    }
+
+    /// A row of floor tiles.
+    fn floor_row() -> String {
+        vec![".."; tile::X::COUNT as usize].join(" ")
+    }
+
+    /// A level with all floor tiles, with `row` put in instead of the row at
+    /// index `row_index`.
+    fn level_with_row(row_index: usize, row: &str) -> String {
+        let floor_row = floor_row();
+        let mut text = String::from("version = 1\nstart = 0, 0\n");
+        for i in 0..tile::Y::COUNT as usize {
+            text.push_str(if i == row_index { row } else { &floor_row });
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn written_levels_parse_back_the_same() {
+        let mut tiles = [TileData::default(); TILES_LENGTH];
+        for (tile_data, kind) in tiles.iter_mut().zip(every_kind().cycle()) {
+            tile_data.kind = kind;
+        }
+        let start = tile::i_to_xy(TILES_LENGTH - 2);
+
+        let mut text = LongStrBuf::new();
+        write(&mut text, &tiles, start).unwrap();
+
+        let (parsed_tiles, parsed_start) = parse(&text).unwrap();
+        assert_eq!(kinds(&parsed_tiles), kinds(&tiles));
+        assert_eq!(parsed_start, start);
+    }
+
+    #[test]
+    fn comments_blank_lines_and_unknown_keys_are_skipped() {
+        let text = format!(
+            "# A comment\n\n{}",
+            level_with_row(3, &floor_row().replacen("..", "GG", 1))
+                .replacen("\n", "\nauthor = someone\n\n# Another comment\n", 1),
+        );
+
+        let (tiles, _) = parse(&text).unwrap();
+        assert_eq!(tiles[tile::xy_to_i_usize((0, 3))].kind, TileKind::Goal);
+    }
+
+    #[test]
+    fn a_missing_start_is_the_top_left_corner() {
+        let text = level_with_row(0, &floor_row()).replace("start = 0, 0\n", "");
+
+        assert_eq!(parse(&text).map(|(_, start)| start), Ok(tile::XY::default()));
+    }
+
+    #[test]
+    fn bad_lines_give_the_line_and_kind() {
+        use ParseErrorKind::*;
+
+        let too_long = format!("{} ..", floor_row());
+        let too_short = floor_row().replacen(".. ", "", 1);
+        let bad_teleporter = floor_row().replacen("..", "T9", 1);
+        let bad_arrow = floor_row().replacen("..", "r9", 1);
+        let unknown = floor_row().replacen("..", "??", 1);
+        let too_wide = floor_row().replacen("..", "...", 1);
+
+        // The rows start on line 3.
+        for (text, line, kind) in [
+            (level_with_row(0, &too_long), 3, WrongRowLength),
+            (level_with_row(4, &too_short), 7, WrongRowLength),
+            (level_with_row(1, &bad_teleporter), 4, BadTile),
+            (level_with_row(1, &bad_arrow), 4, BadTile),
+            (level_with_row(2, &unknown), 5, BadTile),
+            (level_with_row(2, &too_wide), 5, BadTile),
+            (level_with_row(0, &floor_row()).replace("0, 0", "16, 0"), 2, BadStart),
+            (level_with_row(0, &floor_row()).replace("0, 0", "0"), 2, BadStart),
+            (level_with_row(0, &floor_row()).replace("0, 0", "x, 0"), 2, BadStart),
+            (format!("{}{}\n", level_with_row(0, &floor_row()), floor_row()), 19, WrongRowCount),
+        ] {
+            assert_eq!(parse(&text).err(), Some(ParseError { line, kind }), "{}", text);
+        }
+
+        let missing_row = level_with_row(0, &floor_row())
+            .replacen(&format!("{}\n", floor_row()), "", 1);
+        assert_eq!(
+            parse(&missing_row).err(),
+            Some(ParseError { line: 17, kind: WrongRowCount })
+        );
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        for version in ["0", "2", "one", ""] {
+            let text = level_with_row(0, &floor_row())
+                .replace("version = 1", &format!("version = {}", version));
+
+            assert_eq!(
+                parse(&text).err(),
+                Some(ParseError { line: 1, kind: ParseErrorKind::UnknownVersion })
+            );
+        }
+    }
+
+    #[test]
+    fn the_version_is_required_and_comes_first() {
+        use ParseErrorKind::*;
+
+        let level = level_with_row(0, &floor_row());
+        let without_version = level.replacen("version = 1\n", "", 1);
+        let version_last = format!("{}version = 1\n", without_version);
+
+        for (text, line) in [
+            (without_version.as_str(), 1),
+            (version_last.as_str(), 1),
+            ("# Just a comment\n\n", 2),
+            ("", 1),
+        ] {
+            assert_eq!(parse(text).err(), Some(ParseError { line, kind: MissingVersion }), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,835 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Rollback networking, for two players on different computers.
+//!
+//! `update_step` gives the same result on every computer, given the same
+//! inputs, so the only thing the peers need to send each other is their
+//! inputs. Rather than waiting for the other player's input each update, we
+//! guess it, and keep going. When their actual input arrives, and it differs
+//! from the guess, we go back to a snapshot from before the guess, and run the
+//! updates since then again, with the right input.
+//!
+//! Nothing here touches the network itself. The platform layer passes packets
+//! in with `receive_packet` and sends the ones from `take_packet`, so the same
+//! code can be run against `SimulatedLink` to try out bad connections on
+//! localhost.
+
+use crate::{
+    checksum::{self, Checksum},
+    players,
+    scene::LevelNumber,
+    xorshift,
+    xs_from_seed,
+    AnimationTimer,
+    Board,
+    Input,
+    InputFlags,
+    PlayerIndex,
+    Seed,
+    State,
+    Xs,
+    MAX_PLAYERS,
+    INPUT_UP_DOWN,
+    INPUT_DOWN_DOWN,
+    INPUT_LEFT_DOWN,
+    INPUT_RIGHT_DOWN,
+    INPUT_INTERACT_PRESSED,
+};
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+/// An update step, counting from the start of a session.
+pub type Frame = u32;
+
+pub type Packet = Vec<u8>;
+
+/// How many frames of inputs and snapshots are kept.
+const HISTORY_LENGTH: usize = 128;
+
+/// How far we will run ahead of the last input we have from the other player.
+/// Past this we wait for them, since rolling back further would take too long,
+/// and the guesses would mostly be wrong anyway. At `UPDATES_PER_SECOND` this
+/// is a fifth of a second.
+pub const MAX_PREDICTION: Frame = 48;
+
+/// Delaying local inputs by a few frames gives the other player's inputs time
+/// to arrive, so fewer guesses are needed, at the cost of some responsiveness.
+pub const DEFAULT_INPUT_DELAY: Frame = 4;
+
+pub const MAX_INPUT_DELAY: Frame = 8;
+
+/// Only these flags change what `update_step` does, so other flags are not
+/// sent, and don't cause rollbacks.
+const SIMULATED_FLAGS: InputFlags = INPUT_UP_DOWN
+    | INPUT_DOWN_DOWN
+    | INPUT_LEFT_DOWN
+    | INPUT_RIGHT_DOWN
+    | INPUT_INTERACT_PRESSED;
+
+/// Held directions are likely to still be held, but presses only last for the
+/// frame they happened on, so we don't guess that they repeat.
+const PREDICTED_FLAGS: InputFlags = INPUT_UP_DOWN
+    | INPUT_DOWN_DOWN
+    | INPUT_LEFT_DOWN
+    | INPUT_RIGHT_DOWN;
+
+/// Sent first, in case something other than this game sends us a packet.
+const MAGIC: [u8; 2] = *b"Ey";
+
+const PROTOCOL_VERSION: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// Sent by the joining peer until a `Start` arrives.
+    Join,
+    /// Sent by the hosting peer in reply to each `Join`. The host is player 1
+    /// and the joining peer is player 2.
+    Start {
+        seed: Seed,
+        level: LevelNumber,
+        mode: players::Mode,
+    },
+    /// Sent after each `update`, with every input the other peer hasn't said
+    /// it has yet, so lost packets don't need to be resent separately.
+    Inputs {
+        /// The sender's current frame.
+        frame: Frame,
+        /// How many frames the sender is ahead of the receiver, as far as the
+        /// sender can tell.
+        advantage: i32,
+        /// The first frame the sender still needs the receiver's input for.
+        ack: Frame,
+        /// The frame of the first of `inputs`.
+        start: Frame,
+        inputs: Vec<InputFlags>,
+    },
+    /// The sender has stopped playing.
+    Leave,
+}
+
+const JOIN: u8 = 0;
+const START: u8 = 1;
+const INPUTS: u8 = 2;
+const LEAVE: u8 = 3;
+
+impl Message {
+    pub fn encode(&self) -> Packet {
+        let mut packet = Vec::with_capacity(64);
+        packet.extend_from_slice(&MAGIC);
+        packet.push(PROTOCOL_VERSION);
+
+        match self {
+            Self::Join => packet.push(JOIN),
+            Self::Start { seed, level, mode } => {
+                packet.push(START);
+                packet.extend_from_slice(seed);
+                packet.push(*level);
+                packet.push(match mode {
+                    players::Mode::CoOp => 0,
+                    players::Mode::Versus => 1,
+                });
+            },
+            Self::Inputs { frame, advantage, ack, start, inputs } => {
+                packet.push(INPUTS);
+                packet.extend_from_slice(&frame.to_le_bytes());
+                packet.extend_from_slice(&advantage.to_le_bytes());
+                packet.extend_from_slice(&ack.to_le_bytes());
+                packet.extend_from_slice(&start.to_le_bytes());
+                // The sender never has more than `HISTORY_LENGTH` to send.
+                packet.push(inputs.len().min(HISTORY_LENGTH) as u8);
+                for flags in inputs.iter().take(HISTORY_LENGTH) {
+                    // All the `SIMULATED_FLAGS` fit in the low bits.
+                    packet.extend_from_slice(&((flags & SIMULATED_FLAGS) as u16).to_le_bytes());
+                }
+            },
+            Self::Leave => packet.push(LEAVE),
+        }
+
+        packet
+    }
+
+    /// Returns `None` for anything that isn't a message from a compatible
+    /// version of the game. Stray packets are expected with UDP, so they are
+    /// just ignored.
+    pub fn decode(packet: &[u8]) -> Option<Self> {
+        let mut reader = Reader(packet);
+
+        if reader.bytes::<2>()? != MAGIC || reader.u8()? != PROTOCOL_VERSION {
+            return None
+        }
+
+        let message = match reader.u8()? {
+            JOIN => Self::Join,
+            START => Self::Start {
+                seed: reader.bytes()?,
+                level: reader.u8()?,
+                mode: match reader.u8()? {
+                    0 => players::Mode::CoOp,
+                    1 => players::Mode::Versus,
+                    _ => return None,
+                },
+            },
+            INPUTS => {
+                let frame = Frame::from_le_bytes(reader.bytes()?);
+                let advantage = i32::from_le_bytes(reader.bytes()?);
+                let ack = Frame::from_le_bytes(reader.bytes()?);
+                let start = Frame::from_le_bytes(reader.bytes()?);
+                let count = reader.u8()?;
+                let mut inputs = Vec::with_capacity(usize::from(count));
+                for _ in 0..count {
+                    let flags = InputFlags::from(u16::from_le_bytes(reader.bytes()?));
+                    inputs.push(flags & SIMULATED_FLAGS);
+                }
+
+                Self::Inputs { frame, advantage, ack, start, inputs }
+            },
+            LEAVE => Self::Leave,
+            _ => return None,
+        };
+
+        if reader.0.is_empty() { Some(message) } else { None }
+    }
+}
+
+struct Reader<'packet>(&'packet [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[byte]| byte)
+    }
+}
+
+/// A ring buffer of values for the most recent `HISTORY_LENGTH` frames. The
+/// frame is stored with each value, so values from a previous trip around the
+/// ring are not mistaken for current ones.
+#[derive(Clone, Debug)]
+struct History<A> {
+    entries: Vec<Option<(Frame, A)>>,
+}
+
+impl<A: Clone> Default for History<A> {
+    fn default() -> Self {
+        Self {
+            entries: vec![None; HISTORY_LENGTH],
+        }
+    }
+}
+
+impl<A> History<A> {
+    fn get(&self, frame: Frame) -> Option<&A> {
+        match &self.entries[frame as usize % HISTORY_LENGTH] {
+            Some((f, a)) if *f == frame => Some(a),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, frame: Frame, a: A) {
+        self.entries[frame as usize % HISTORY_LENGTH] = Some((frame, a));
+    }
+}
+
+/// The parts of `State` that `update_step` changes.
+#[derive(Clone, Debug, Default)]
+struct Snapshot {
+    board: Board,
+    animation_timer: AnimationTimer,
+}
+
+impl Snapshot {
+    fn take(state: &State) -> Self {
+        Self {
+            board: state.board.clone(),
+            animation_timer: state.animation_timer,
+        }
+    }
+
+    fn restore(self, state: &mut State) {
+        state.board = self.board;
+        state.animation_timer = self.animation_timer;
+    }
+}
+
+/// Counts of how the connection is going, for showing on screen.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub rollbacks: u32,
+    /// The total of how many frames each rollback ran again.
+    pub resimulated_frames: u32,
+    /// Frames we waited for the other player, instead of running.
+    pub waits: u32,
+}
+
+/// A game in progress between this peer and one other.
+#[derive(Debug)]
+pub struct Session {
+    local: PlayerIndex,
+    remote: PlayerIndex,
+    input_delay: Frame,
+    /// The next frame to run.
+    frame: Frame,
+    local_inputs: History<InputFlags>,
+    remote_inputs: History<InputFlags>,
+    /// What was used for the other player's input when each frame was run,
+    /// whether it was their actual input or a guess.
+    used_remote_inputs: History<InputFlags>,
+    /// The state from just before each frame was run.
+    snapshots: History<Snapshot>,
+    /// The checksum from just after each frame was run, if checksums are
+    /// being logged.
+    checksums: History<Checksum>,
+    /// Checksums have been logged for every frame before this one.
+    logged: Frame,
+    /// We have the other player's inputs for every frame before this one.
+    confirmed: Frame,
+    /// The other player has our inputs for every frame before this one.
+    remote_confirmed: Frame,
+    /// The latest frame the other player said they were on.
+    remote_frame: Frame,
+    /// How far ahead of us the other player thinks they are.
+    remote_advantage: i32,
+    /// The earliest frame that was run with a wrong guess, if any.
+    rollback_from: Option<Frame>,
+    /// The frame the board was solved on, if it has been, which may have been
+    /// run with a guess.
+    solved_at: Option<Frame>,
+    /// Whether `take_final_solve` has returned `true` yet.
+    took_final_solve: bool,
+    /// Counts calls to `step`, so waiting to let the other player catch up can
+    /// be spread out.
+    steps: u32,
+    pub stats: Stats,
+}
+
+impl Session {
+    pub fn new(local: PlayerIndex, input_delay: Frame) -> Self {
+        Self {
+            local,
+            // With two players, the other one is whichever we aren't.
+            remote: 1 - local.min(1),
+            input_delay: input_delay.min(MAX_INPUT_DELAY),
+            frame: 0,
+            local_inputs: <_>::default(),
+            remote_inputs: <_>::default(),
+            used_remote_inputs: <_>::default(),
+            snapshots: <_>::default(),
+            checksums: <_>::default(),
+            logged: 0,
+            confirmed: 0,
+            remote_confirmed: 0,
+            remote_frame: 0,
+            remote_advantage: 0,
+            rollback_from: None,
+            solved_at: None,
+            took_final_solve: false,
+            steps: 0,
+            stats: <_>::default(),
+        }
+    }
+
+    pub fn local_player(&self) -> PlayerIndex {
+        self.local
+    }
+
+    /// How many frames we are running on guesses.
+    pub fn predicted_frames(&self) -> Frame {
+        self.frame.saturating_sub(self.confirmed)
+    }
+
+    /// How far ahead of the other player we are, as far as we can tell.
+    fn advantage(&self) -> i32 {
+        self.frame as i32 - self.remote_frame as i32
+    }
+
+    /// If we are further ahead of the other player than they are of us, then
+    /// we are using more guesses than we need to, so we wait now and then to
+    /// let them catch up.
+    fn should_wait_for_remote(&self) -> bool {
+        const WAIT_INTERVAL: u32 = 4;
+
+        (self.advantage() - self.remote_advantage) / 2 > 0
+        && self.steps.is_multiple_of(WAIT_INTERVAL)
+    }
+
+    fn predicted_remote_input(&self) -> InputFlags {
+        self.confirmed.checked_sub(1)
+            .and_then(|frame| self.remote_inputs.get(frame))
+            .map_or(0, |flags| flags & PREDICTED_FLAGS)
+    }
+
+    /// Runs one frame, after rolling back if a guess turned out to be wrong.
+    /// Returns `false` if we had to wait for the other player instead.
+    pub fn step(&mut self, state: &mut State, local_flags: InputFlags) -> bool {
+        self.steps = self.steps.wrapping_add(1);
+
+        if let Some(from) = self.rollback_from.take() {
+            if let Some(snapshot) = self.snapshots.get(from) {
+                snapshot.clone().restore(state);
+                if self.solved_at.is_some_and(|at| at >= from) {
+                    self.solved_at = None;
+                }
+                for frame in from..self.frame {
+                    self.run_frame(state, frame);
+                }
+                self.stats.rollbacks += 1;
+                self.stats.resimulated_frames += self.frame - from;
+            }
+        }
+
+        if self.predicted_frames() >= MAX_PREDICTION
+        || self.should_wait_for_remote() {
+            self.stats.waits += 1;
+            return false
+        }
+
+        self.local_inputs.set(self.frame + self.input_delay, local_flags & SIMULATED_FLAGS);
+
+        self.run_frame(state, self.frame);
+        self.frame += 1;
+
+        self.log_checksums(state);
+
+        true
+    }
+
+    /// Frames before this have been run with the other player's actual input,
+    /// and aren't waiting to be rolled back, so they will never change.
+    fn one_past_final(&self) -> Frame {
+        self.confirmed
+            .min(self.frame)
+            .min(self.rollback_from.unwrap_or(Frame::MAX))
+    }
+
+    /// Returns `true` once, when the frame the board was solved on has become
+    /// final, so both peers agree that it was, and how. That can happen when
+    /// a packet arrives, not only during a `step`.
+    pub fn take_final_solve(&mut self) -> bool {
+        let is_final = self.solved_at.is_some_and(|at| at < self.one_past_final());
+        if is_final && !self.took_final_solve {
+            self.took_final_solve = true;
+            return true
+        }
+
+        false
+    }
+
+    /// Both peers should have the same checksums for final frames.
+    fn log_checksums(&mut self, state: &mut State) {
+        let one_past_final = self.one_past_final();
+
+        while self.logged < one_past_final {
+            if let Some(&checksum) = self.checksums.get(self.logged) {
+                // The step count includes the frame itself.
+                checksum::log_entry(state, self.logged + 1, checksum);
+            }
+            self.logged += 1;
+        }
+    }
+
+    fn run_frame(&mut self, state: &mut State, frame: Frame) {
+        self.snapshots.set(frame, Snapshot::take(state));
+
+        // Frames before the input delay has passed have no local input.
+        let local_flags = self.local_inputs.get(frame).copied().unwrap_or(0);
+        let remote_flags = self.remote_inputs.get(frame).copied()
+            .unwrap_or_else(|| self.predicted_remote_input());
+        self.used_remote_inputs.set(frame, remote_flags);
+
+        let mut inputs = [Input::NoChange; MAX_PLAYERS];
+        inputs[self.local] = Input::from_flags(local_flags);
+        inputs[self.remote] = Input::from_flags(remote_flags);
+
+        let was_solved = state.board.solved;
+
+        crate::update_step(state, inputs);
+
+        if !was_solved && state.board.solved {
+            self.solved_at = Some(frame);
+        }
+
+        if state.checksum_log.is_some() {
+            self.checksums.set(frame, checksum::compute(state));
+        }
+    }
+
+    /// Takes in the other player's inputs, noting any that were guessed wrong.
+    pub fn receive(&mut self, message: &Message) {
+        let Message::Inputs { frame, advantage, ack, start, inputs } = message else {
+            return
+        };
+
+        if *frame >= self.remote_frame {
+            self.remote_frame = *frame;
+            self.remote_advantage = *advantage;
+        }
+        self.remote_confirmed = self.remote_confirmed.max(*ack);
+
+        // Frames this far back may still be rolled back to, so the slots they
+        // use must not be overwritten yet.
+        let one_past_storable = (self.frame + HISTORY_LENGTH as Frame)
+            .saturating_sub(MAX_PREDICTION + 2);
+
+        for (frame, &flags) in (*start..).zip(inputs) {
+            if frame >= one_past_storable {
+                break
+            }
+            if frame < self.confirmed || self.remote_inputs.get(frame).is_some() {
+                continue
+            }
+
+            if frame < self.frame
+            && self.used_remote_inputs.get(frame) != Some(&flags) {
+                self.rollback_from = Some(
+                    self.rollback_from.map_or(frame, |from| from.min(frame))
+                );
+            }
+
+            self.remote_inputs.set(frame, flags);
+        }
+
+        while self.remote_inputs.get(self.confirmed).is_some() {
+            self.confirmed += 1;
+        }
+    }
+
+    /// Our inputs that the other player doesn't have yet.
+    pub fn inputs_message(&self) -> Message {
+        let one_past_last = self.frame + self.input_delay;
+        let start = self.remote_confirmed
+            .max(one_past_last.saturating_sub(HISTORY_LENGTH as Frame));
+
+        Message::Inputs {
+            frame: self.frame,
+            advantage: self.advantage(),
+            ack: self.confirmed,
+            start,
+            inputs: (start..one_past_last)
+                .map(|frame| self.local_inputs.get(frame).copied().unwrap_or(0))
+                .collect(),
+        }
+    }
+}
+
+/// Where this peer is in connecting to the other one.
+#[derive(Debug)]
+pub enum Netplay {
+    /// Waiting for a `Join`.
+    Hosting { input_delay: Frame },
+    /// Sending `Join`s until a `Start` arrives.
+    Joining { input_delay: Frame },
+    Playing(Session),
+}
+
+/// Milliseconds, from whenever the caller likes, as long as it is consistent.
+pub type Millis = u64;
+
+/// Network conditions to pretend to have, for testing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Conditions {
+    /// How long each packet is held before being sent.
+    pub latency: Millis,
+    /// From 0 to 100.
+    pub loss_percent: u8,
+}
+
+/// Holds back and drops packets according to some `Conditions`. Putting this
+/// between `take_packet` and the socket makes a connection to localhost behave
+/// like one over a bad network.
+#[derive(Debug)]
+pub struct SimulatedLink {
+    conditions: Conditions,
+    rng: Xs,
+    /// Packets with the time they should be sent, in the order they will be.
+    delayed: VecDeque<(Millis, Packet)>,
+}
+
+impl SimulatedLink {
+    pub fn new(conditions: Conditions, seed: Seed) -> Self {
+        Self {
+            conditions,
+            rng: xs_from_seed(seed),
+            delayed: <_>::default(),
+        }
+    }
+
+    pub fn send(&mut self, now: Millis, packet: Packet) {
+        if xorshift(&mut self.rng) % 100 < u32::from(self.conditions.loss_percent) {
+            return
+        }
+
+        // Every packet has the same delay, so they stay in order.
+        self.delayed.push_back((now + self.conditions.latency, packet));
+    }
+
+    /// Returns the next packet that is due to be sent, if any.
+    pub fn take_due(&mut self, now: Millis) -> Option<Packet> {
+        match self.delayed.front() {
+            Some((due, _)) if *due <= now => self.delayed.pop_front().map(|(_, packet)| packet),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        is_netplaying,
+        level,
+        storage::CountingStorage,
+        Dir,
+        DrawWH,
+        PlayerInputs,
+        S_PER_UPDATE,
+    };
+
+    const WH: DrawWH = DrawWH { w: 800., h: 600. };
+
+    fn dir_flags(dir: Dir) -> InputFlags {
+        use Dir::*;
+        match dir {
+            Up => INPUT_UP_DOWN,
+            UpRight => INPUT_UP_DOWN | INPUT_RIGHT_DOWN,
+            Right => INPUT_RIGHT_DOWN,
+            DownRight => INPUT_DOWN_DOWN | INPUT_RIGHT_DOWN,
+            Down => INPUT_DOWN_DOWN,
+            DownLeft => INPUT_DOWN_DOWN | INPUT_LEFT_DOWN,
+            Left => INPUT_LEFT_DOWN,
+            UpLeft => INPUT_UP_DOWN | INPUT_LEFT_DOWN,
+        }
+    }
+
+    /// Holds a way the player's eye can move, as they see it, for a random
+    /// length of time, like a person trying things out would.
+    fn scripted_flags(
+        rng: &mut Xs,
+        held: &mut (InputFlags, u32),
+        state: &State,
+    ) -> InputFlags {
+        if held.1 > 0 {
+            held.1 -= 1;
+            return held.0
+        }
+
+        let player = session(state).local_player();
+        let xy = state.board.eyes[player].xy;
+        let dirs: Vec<Dir> = Dir::ALL.into_iter()
+            .filter(|&dir| level::can_move(&state.board.tiles.tiles, xy, dir))
+            .collect();
+
+        *held = match dirs.len() {
+            0 => (0, 0),
+            len => (
+                dir_flags(dirs[xorshift(rng) as usize % len]),
+                xorshift(rng) % 100,
+            ),
+        };
+
+        held.0
+    }
+
+    fn play_over(conditions: Conditions) -> [State; 2] {
+        let seed = [0x4E; 16];
+        let mut states = [State::from_seed(seed), State::from_seed(seed)];
+        crate::host_netplay(&mut states[0], DEFAULT_INPUT_DELAY);
+        crate::join_netplay(&mut states[1], DEFAULT_INPUT_DELAY);
+        for state in states.iter_mut() {
+            crate::enable_checksum_log(state);
+        }
+
+        let mut links = [
+            SimulatedLink::new(conditions, [1; 16]),
+            SimulatedLink::new(conditions, [2; 16]),
+        ];
+        let mut rng = xs_from_seed([3; 16]);
+        let mut held = [(0, 0); 2];
+
+        const SCRIPTED_TICKS: u32 = 4000;
+        // Long enough for every input to arrive, and be caught up with.
+        const SETTLING_TICKS: u32 = 2000;
+
+        for tick in 0..SCRIPTED_TICKS + SETTLING_TICKS {
+            // Close enough to `UPDATES_PER_SECOND`.
+            let now = Millis::from(tick) * 4;
+
+            for i in 0..2 {
+                let mut inputs: PlayerInputs = [0; MAX_PLAYERS];
+                if tick < SCRIPTED_TICKS && is_netplaying(&states[i]) {
+                    inputs[0] = scripted_flags(&mut rng, &mut held[i], &states[i]);
+                }
+
+                crate::update(&mut states[i], &mut CountingStorage::default(), inputs, WH, S_PER_UPDATE);
+
+                while let Some(packet) = crate::take_packet(&mut states[i]) {
+                    links[i].send(now, packet);
+                }
+            }
+
+            for i in 0..2 {
+                while let Some(packet) = links[i].take_due(now) {
+                    crate::receive_packet(&mut states[1 - i], &packet);
+                }
+            }
+        }
+
+        states
+    }
+
+    fn session(state: &State) -> &Session {
+        match &state.netplay {
+            Some(Netplay::Playing(session)) => session,
+            other => panic!("expected to be playing, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn peers_agree_over_a_bad_connection() {
+        let states = play_over(Conditions { latency: 60, loss_percent: 20 });
+
+        assert_eq!(session(&states[0]).local_player(), 0);
+        assert_eq!(session(&states[1]).local_player(), 1);
+        assert_eq!(session(&states[0]).frame, session(&states[1]).frame);
+        assert!(
+            session(&states[0]).stats.rollbacks > 0
+            && session(&states[1]).stats.rollbacks > 0,
+            "the guesses should have been wrong at least once"
+        );
+        assert!(
+            states[0].board.eyes().iter().all(|eye| eye.moves > 0),
+            "both players should have moved"
+        );
+
+        assert_eq!(
+            format!("{:?}", states[0].board),
+            format!("{:?}", states[1].board),
+        );
+
+        let [left, right] = states.map(|mut state| {
+            checksum::parse_log(&crate::take_checksum_log(&mut state)).unwrap()
+        });
+        assert!(left.len().min(right.len()) > 1000, "{} {}", left.len(), right.len());
+        assert_eq!(checksum::first_divergence(&left, &right), None);
+    }
+
+    /// Runs one tick on both peers, with each peer's packets added to its
+    /// outbox, to be delivered whenever the test likes.
+    fn tick(states: &mut [State; 2], inputs: [InputFlags; 2], outboxes: &mut [Vec<Packet>; 2]) {
+        for i in 0..2 {
+            let mut player_inputs: PlayerInputs = [0; MAX_PLAYERS];
+            player_inputs[0] = inputs[i];
+
+            crate::update(&mut states[i], &mut CountingStorage::default(), player_inputs, WH, S_PER_UPDATE);
+
+            while let Some(packet) = crate::take_packet(&mut states[i]) {
+                outboxes[i].push(packet);
+            }
+        }
+    }
+
+    fn deliver(states: &mut [State; 2], outboxes: &mut [Vec<Packet>; 2], from: usize) {
+        for packet in outboxes[from].drain(..) {
+            crate::receive_packet(&mut states[1 - from], &packet);
+        }
+    }
+
+    fn tick_and_deliver(states: &mut [State; 2], inputs: [InputFlags; 2], outboxes: &mut [Vec<Packet>; 2]) {
+        tick(states, inputs, outboxes);
+        deliver(states, outboxes, 0);
+        deliver(states, outboxes, 1);
+    }
+
+    #[test]
+    fn boards_solved_on_a_wrong_guess_are_not_finished() {
+        use crate::{scene::Scene, tile, TileData, TileKind};
+
+        let seed = [0x4E; 16];
+        let mut states = [State::from_seed(seed), State::from_seed(seed)];
+        states[0].lobby.mode = players::Mode::Versus;
+        crate::host_netplay(&mut states[0], 0);
+        crate::join_netplay(&mut states[1], 0);
+        let mut outboxes = [Vec::new(), Vec::new()];
+
+        while !(is_netplaying(&states[0]) && is_netplaying(&states[1])) {
+            tick_and_deliver(&mut states, [0, 0], &mut outboxes);
+        }
+
+        // The joiner's eye is two moves to the left of a goal, with nowhere
+        // else to go.
+        let at = |x, y| tile::i_to_xy(tile::xy_to_i_usize((x, y)));
+        for state in states.iter_mut() {
+            for tile in state.board.tiles.tiles.iter_mut() {
+                *tile = TileData { kind: TileKind::Wall };
+            }
+            for (x, kind) in [(1, TileKind::Floor), (2, TileKind::Floor), (3, TileKind::Goal)] {
+                state.board.tiles.tiles[tile::xy_to_i_usize((x, 1))] = TileData { kind };
+            }
+            state.board.eyes[0].xy = at(0, 0);
+            state.board.eyes[1].xy = at(1, 1);
+        }
+        for _ in 0..100 {
+            tick_and_deliver(&mut states, [0, 0], &mut outboxes);
+        }
+
+        // The host gets the joiner's press, then nothing more, so it guesses
+        // that right is still held, and the eye carries on into the goal.
+        tick_and_deliver(&mut states, [0, INPUT_RIGHT_DOWN], &mut outboxes);
+        for _ in 0..60 {
+            tick(&mut states, [0, 0], &mut outboxes);
+            deliver(&mut states, &mut outboxes, 0);
+        }
+        assert_eq!(states[0].board.eyes[1].xy, at(3, 1));
+        assert!(states[0].board.solved);
+        assert_eq!(states[0].scenes.top(), Scene::Gameplay);
+
+        for _ in 0..100 {
+            tick_and_deliver(&mut states, [0, 0], &mut outboxes);
+        }
+        for state in &states {
+            assert_eq!(state.board.eyes[1].xy, at(2, 1));
+            assert!(!state.board.solved);
+            assert_eq!(state.scenes.top(), Scene::Gameplay);
+        }
+
+        // Actually reaching the goal still finishes the board, for both.
+        tick_and_deliver(&mut states, [0, INPUT_RIGHT_DOWN], &mut outboxes);
+        for _ in 0..100 {
+            tick_and_deliver(&mut states, [0, 0], &mut outboxes);
+        }
+        for state in &states {
+            assert!(state.board.solved);
+            assert!(matches!(state.scenes.top(), Scene::Results(_)), "{:?}", state.scenes.top());
+        }
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Message::Join,
+            Message::Start {
+                seed: [0xAB; 16],
+                level: 3,
+                mode: players::Mode::Versus,
+            },
+            Message::Inputs {
+                frame: 1234,
+                advantage: -5,
+                ack: 1200,
+                start: 1190,
+                inputs: vec![0, INPUT_UP_DOWN, INPUT_LEFT_DOWN | INPUT_INTERACT_PRESSED],
+            },
+            Message::Leave,
+        ];
+
+        for message in messages {
+            let packet = message.encode();
+            assert_eq!(Message::decode(&packet), Some(message));
+            assert_eq!(Message::decode(&packet[..packet.len() - 1]), None);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Who is playing, for local multiplayer. Each player controls their own eye,
+//! with their own input flags.
+
+use crate::draw::Colour;
+
+/// An index into the players, starting from 0 for player 1.
+pub type PlayerIndex = usize;
+
+pub type PlayerCount = u8;
+
+pub const MAX_PLAYERS: usize = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Every eye needs to reach the goal. Eyes that have reached it stop
+    /// blocking the others, so they can follow.
+    #[default]
+    CoOp,
+    /// The first eye to reach the goal wins.
+    Versus,
+}
+
+impl Mode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::CoOp => Self::Versus,
+            Self::Versus => Self::CoOp,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::CoOp => "Co-op",
+            Self::Versus => "Versus",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Setup {
+    /// From 1 to `MAX_PLAYERS`.
+    pub count: PlayerCount,
+    pub mode: Mode,
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            mode: Mode::default(),
+        }
+    }
+}
+
+impl Setup {
+    /// Cycles from 2 players up to `MAX_PLAYERS`, since one player is not
+    /// multiplayer.
+    pub fn next_count(&mut self) {
+        self.count = if usize::from(self.count) >= MAX_PLAYERS {
+            2
+        } else {
+            self.count + 1
+        };
+    }
+
+    pub fn is_multiplayer(&self) -> bool {
+        self.count > 1
+    }
+}
+
+/// Each player's eye is tinted with their colour, so they can tell which one
+/// is theirs. Player 1 gets white, which leaves the sprite as it is.
+pub const COLOURS: [Colour; MAX_PLAYERS] = [
+    Colour::WHITE,
+    Colour::BLUE,
+    Colour::YELLOW,
+    Colour::RED,
+];
+
+/// The names of `COLOURS` in text markup.
+pub const COLOUR_NAMES: [&str; MAX_PLAYERS] = [
+    "white",
+    "blue",
+    "yellow",
+    "red",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_counts_cycle_through_the_multiplayer_counts() {
+        let mut setup = Setup { count: 2, ..<_>::default() };
+
+        let mut counts = [0; MAX_PLAYERS];
+        for count in counts.iter_mut() {
+            assert!(setup.is_multiplayer());
+            *count = setup.count;
+            setup.next_count();
+        }
+
+        assert_eq!(counts, [2, 3, 4, 2]);
+        assert!(!Setup::default().is_multiplayer());
+    }
+
+    #[test]
+    fn modes_cycle() {
+        assert_eq!(Mode::CoOp.next(), Mode::Versus);
+        assert_eq!(Mode::Versus.next(), Mode::CoOp);
+    }
+}
@@ -0,0 +1,334 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+/// We leave telling the time to the platform layer, since how to do that varies.
+/// The only requirement is that later calls never return smaller values than
+/// earlier ones.
+pub type Nanos = u64;
+
+pub type Clock = fn() -> Nanos;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeId {
+    Update,
+    UpdateStep,
+    CameraStep,
+    Commands,
+    TextFormatting,
+}
+
+impl ScopeId {
+    pub fn name(self) -> &'static str {
+        use ScopeId::*;
+        match self {
+            Update => "update",
+            UpdateStep => "update_step",
+            CameraStep => "camera_step",
+            Commands => "commands",
+            TextFormatting => "text_formatting",
+        }
+    }
+}
+
+pub type Depth = u8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub id: ScopeId,
+    pub start: Nanos,
+    pub end: Nanos,
+    /// How many scopes this one is nested inside of.
+    pub depth: Depth,
+}
+
+const BLANK_RECORD: Record = Record {
+    id: ScopeId::Update,
+    start: 0,
+    end: 0,
+    depth: 0,
+};
+
+/// Enough for a frame with a lot of update steps in it.
+pub const MAX_RECORDS: usize = 256;
+
+/// Records how long named scopes took, without allocating. It does nothing until
+/// it is given a clock.
+#[derive(Debug)]
+pub struct Profiler {
+    clock: Option<Clock>,
+    records: [Record; MAX_RECORDS],
+    len: usize,
+    /// How many scopes didn't fit in `records` this frame.
+    dropped: usize,
+    depth: Depth,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            clock: None,
+            records: [BLANK_RECORD; MAX_RECORDS],
+            len: 0,
+            dropped: 0,
+            depth: 0,
+        }
+    }
+}
+
+/// Returned by `Profiler::begin`, and should be passed to `Profiler::end`.
+#[must_use]
+pub struct Scope {
+    index: Option<usize>,
+}
+
+impl Profiler {
+    pub fn set_clock(&mut self, clock: Option<Clock>) {
+        self.clock = clock;
+    }
+
+    /// Forgets all the records, ready for a new frame.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.dropped = 0;
+        self.depth = 0;
+    }
+
+    pub fn begin(&mut self, id: ScopeId) -> Scope {
+        let clock = match self.clock {
+            Some(clock) => clock,
+            None => return Scope { index: None },
+        };
+
+        let depth = self.depth;
+        self.depth = self.depth.saturating_add(1);
+
+        if self.len >= MAX_RECORDS {
+            self.dropped += 1;
+            return Scope { index: None };
+        }
+
+        let index = self.len;
+        self.len += 1;
+
+        let start = clock();
+        self.records[index] = Record {
+            id,
+            start,
+            end: start,
+            depth,
+        };
+
+        Scope { index: Some(index) }
+    }
+
+    pub fn end(&mut self, scope: Scope) {
+        let clock = match self.clock {
+            Some(clock) => clock,
+            None => return,
+        };
+
+        self.depth = self.depth.saturating_sub(1);
+
+        if let Some(index) = scope.index {
+            self.records[index].end = clock();
+        }
+    }
+
+    /// The records of the scopes in the current frame, in the order they began.
+    pub fn records(&self) -> &[Record] {
+        &self.records[..self.len]
+    }
+
+    /// How many scopes in the current frame were left out of `records`,
+    /// because it was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+/// Times `$body`, which should be a block, as a scope with the given
+/// `ScopeId` variant name.
+#[macro_export]
+macro_rules! profile_scope {
+    ($profiler: expr, $id: ident, $body: block) => {{
+        let scope = $profiler.begin($crate::profile::ScopeId::$id);
+        let output = $body;
+        $profiler.end(scope);
+        output
+    }}
+}
+
+/// Writes the given records as the elements of a Chrome trace-event format
+/// JSON array, not including the surrounding brackets, so records from several
+/// frames can be written into one array. `needs_comma` should be true if
+/// something has already been written into the array. If `dropped` is not
+/// zero, an instant event saying how many scopes are missing is written after
+/// the records, so an incomplete trace doesn't look complete.
+///
+/// See https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+pub fn write_chrome_trace_events(
+    w: &mut dyn core::fmt::Write,
+    records: &[Record],
+    dropped: usize,
+    mut needs_comma: bool,
+) -> core::fmt::Result {
+    // The format uses microseconds, but allows fractional values.
+    fn write_micros(w: &mut dyn core::fmt::Write, nanos: Nanos) -> core::fmt::Result {
+        write!(w, "{}.{:03}", nanos / 1000, nanos % 1000)
+    }
+
+    for record in records {
+        if needs_comma {
+            w.write_str(",\n")?;
+        }
+        needs_comma = true;
+
+        write!(w, r#"{{"name":"{}","cat":"game","ph":"X","ts":"#, record.id.name())?;
+        write_micros(w, record.start)?;
+        w.write_str(r#","dur":"#)?;
+        write_micros(w, record.end.saturating_sub(record.start))?;
+        w.write_str(r#","pid":0,"tid":0}"#)?;
+    }
+
+    if dropped > 0 {
+        if needs_comma {
+            w.write_str(",\n")?;
+        }
+
+        let last_end = records.iter().map(|record| record.end).max().unwrap_or(0);
+
+        w.write_str(r#"{"name":"dropped_scopes","cat":"game","ph":"i","s":"g","ts":"#)?;
+        write_micros(w, last_end)?;
+        write!(w, r#","pid":0,"tid":0,"args":{{"count":{}}}}}"#, dropped)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    std::thread_local! {
+        static NOW: Cell<Nanos> = const { Cell::new(0) };
+    }
+
+    /// Goes up by 1500 nanoseconds each call, so the times are predictable.
+    fn fake_clock() -> Nanos {
+        NOW.with(|now| {
+            now.set(now.get() + 1500);
+            now.get()
+        })
+    }
+
+    fn profiler() -> Profiler {
+        NOW.with(|now| now.set(0));
+
+        let mut profiler = Profiler::default();
+        profiler.set_clock(Some(fake_clock));
+        profiler
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_a_clock() {
+        let mut profiler = Profiler::default();
+        profile_scope!(profiler, Update, {});
+
+        assert_eq!(profiler.records(), []);
+        assert_eq!(profiler.dropped(), 0);
+    }
+
+    #[test]
+    fn scopes_nest() {
+        let mut profiler = profiler();
+
+        profile_scope!(profiler, Update, {
+            profile_scope!(profiler, UpdateStep, {
+                profile_scope!(profiler, CameraStep, {});
+            });
+            profile_scope!(profiler, Commands, {});
+        });
+        profile_scope!(profiler, TextFormatting, {});
+
+        let records = profiler.records();
+        let ids_and_depths: Vec<_> = records.iter()
+            .map(|record| (record.id, record.depth))
+            .collect();
+        assert_eq!(
+            ids_and_depths,
+            [
+                (ScopeId::Update, 0),
+                (ScopeId::UpdateStep, 1),
+                (ScopeId::CameraStep, 2),
+                (ScopeId::Commands, 1),
+                (ScopeId::TextFormatting, 0),
+            ]
+        );
+
+        // Each scope lies within the closest one before it with less depth.
+        for (i, inner) in records.iter().enumerate() {
+            assert!(inner.start < inner.end, "{:?}", inner);
+
+            if let Some(outer) = records[..i].iter().rev().find(|r| r.depth < inner.depth) {
+                assert!(outer.start < inner.start && inner.end < outer.end, "{:?} {:?}", outer, inner);
+            }
+        }
+    }
+
+    #[test]
+    fn scopes_past_the_limit_are_counted_as_dropped() {
+        let mut profiler = profiler();
+
+        profile_scope!(profiler, Update, {
+            for _ in 0..MAX_RECORDS + 2 {
+                profile_scope!(profiler, UpdateStep, {});
+            }
+        });
+        // Dropped scopes still count towards the depth of later ones.
+        let scope = profiler.begin(ScopeId::Commands);
+        profiler.end(scope);
+
+        assert_eq!(profiler.records().len(), MAX_RECORDS);
+        assert_eq!(profiler.dropped(), 4);
+        assert!(profiler.records()[1..].iter().all(|record| record.depth == 1));
+
+        profiler.clear();
+        assert_eq!(profiler.records(), []);
+        assert_eq!(profiler.dropped(), 0);
+
+        profile_scope!(profiler, Update, {});
+        assert_eq!(profiler.records()[0].depth, 0);
+    }
+
+    #[test]
+    fn chrome_trace_events_are_well_formed() {
+        let mut profiler = profiler();
+        profile_scope!(profiler, Update, {
+            profile_scope!(profiler, CameraStep, {});
+        });
+
+        let mut json = String::from("[");
+        write_chrome_trace_events(&mut json, profiler.records(), 0, false).unwrap();
+        write_chrome_trace_events(&mut json, &profiler.records()[1..], 3, true).unwrap();
+        json.push(']');
+
+        assert_eq!(
+            json,
+            concat!(
+                r#"[{"name":"update","cat":"game","ph":"X","ts":1.500,"dur":4.500,"pid":0,"tid":0},"#,
+                "\n",
+                r#"{"name":"camera_step","cat":"game","ph":"X","ts":3.000,"dur":1.500,"pid":0,"tid":0},"#,
+                "\n",
+                r#"{"name":"camera_step","cat":"game","ph":"X","ts":3.000,"dur":1.500,"pid":0,"tid":0},"#,
+                "\n",
+                r#"{"name":"dropped_scopes","cat":"game","ph":"i","s":"g","ts":4.500,"pid":0,"tid":0,"args":{"count":3}}]"#,
+            )
+        );
+
+        // Nothing to write means nothing is written, so no stray commas.
+        let mut json = String::new();
+        write_chrome_trace_events(&mut json, &[], 0, true).unwrap();
+        assert_eq!(json, "");
+    }
+}
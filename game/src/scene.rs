@@ -0,0 +1,534 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use crate::{
+    draw::{str_buf, StrBuf},
+    State,
+    InputFlags,
+    INPUT_UP_PRESSED,
+    INPUT_DOWN_PRESSED,
+    INPUT_INTERACT_PRESSED,
+    INPUT_MENU_PRESSED,
+};
+
+pub type MenuIndex = u8;
+
+pub type LevelNumber = u8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scene {
+    Title(MenuIndex),
+    LevelSelect(MenuIndex),
+    Gameplay,
+    Pause(MenuIndex),
+    Settings(MenuIndex),
+    ConfirmQuit(MenuIndex),
+    Editor,
+    EditorMenu(MenuIndex),
+    /// Playing the level from the editor.
+    Playtest,
+    EnterSeed(MenuIndex),
+    /// Shown over a board once it is solved.
+    Results(MenuIndex),
+    /// Choosing how many players there are, and how they play.
+    Multiplayer(MenuIndex),
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::Title(0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuOption {
+    Play,
+    Daily,
+    EnterSeed,
+    /// Shows what has been typed so far.
+    SeedEntry,
+    Editor,
+    Level(LevelNumber),
+    Resume,
+    Settings,
+    Fullscreen,
+    TargetFps,
+    ShowStats,
+    Back,
+    QuitToTitle,
+    Quit,
+    Yes,
+    No,
+    Test,
+    Save,
+    Load,
+    Slot,
+    New,
+    NextLevel,
+    Retry,
+    Multiplayer,
+    PlayerCount,
+    Mode,
+    Start,
+}
+
+impl MenuOption {
+    pub fn label(self, state: &State) -> StrBuf {
+        let settings = &state.settings;
+
+        fn on_off(b: bool) -> &'static str {
+            if b { "on" } else { "off" }
+        }
+
+        use MenuOption::*;
+        match self {
+            Play => "Play".into(),
+            Daily => "Daily challenge".into(),
+            EnterSeed => "Enter seed".into(),
+            SeedEntry => match state.seed_entry_error {
+                Some(err) => str_buf!("{}_ ({})", state.seed_entry, err),
+                None => str_buf!("{}_", state.seed_entry),
+            },
+            Editor => "Editor".into(),
+            Level(level) => str_buf!("Level {}", level),
+            Resume => "Resume".into(),
+            Settings => "Settings".into(),
+            Fullscreen => str_buf!("Fullscreen: {}", on_off(settings.fullscreen)),
+            TargetFps => match settings.target_fps {
+                0 => "FPS: unlimited".into(),
+                fps => str_buf!("FPS: {}", fps),
+            },
+            ShowStats => str_buf!("Show stats: {}", on_off(settings.show_stats)),
+            Back => "Back".into(),
+            QuitToTitle => "Quit to title".into(),
+            Quit => "Quit".into(),
+            Yes => "Yes".into(),
+            No => "No".into(),
+            Test => "Test".into(),
+            Save => "Save".into(),
+            Load => "Load".into(),
+            Slot => str_buf!("Slot: {}", state.editor.slot),
+            New => "New".into(),
+            NextLevel => "Next level".into(),
+            Retry => "Retry".into(),
+            Multiplayer => "Local multiplayer".into(),
+            PlayerCount => str_buf!("Players: {}", state.lobby.count),
+            Mode => str_buf!("Mode: {}", state.lobby.mode.label()),
+            Start => "Start".into(),
+        }
+    }
+}
+
+/// Something a scene wants to happen, which needs more than the scene itself
+/// to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Push(Scene),
+    Pop,
+    /// Clears the stack, leaving only this scene.
+    Reset(Scene),
+    /// Picks a level for one player.
+    PlaySolo,
+    /// Picks a level for the players chosen in the multiplayer menu.
+    PlayTogether,
+    NextPlayerCount,
+    NextMode,
+    StartLevel(LevelNumber),
+    StartDaily,
+    UseEnteredSeed,
+    ToggleFullscreen,
+    NextTargetFps,
+    ToggleShowStats,
+    Quit,
+    StartPlaytest,
+    SaveLevel,
+    LoadLevel,
+    NextSlot,
+    NewLevel,
+    NextLevel,
+    Retry,
+}
+
+impl Scene {
+    pub fn options(self) -> &'static [MenuOption] {
+        use MenuOption::*;
+        match self {
+            Self::Title(_) => &[
+                Play,
+                Multiplayer,
+                Daily,
+                EnterSeed,
+                Editor,
+                Settings,
+                Quit,
+            ],
+            Self::LevelSelect(_) => &[
+                Level(1),
+                Level(2),
+                Level(3),
+                Level(4),
+                Level(5),
+                Back,
+            ],
+            Self::Gameplay => &[],
+            Self::Pause(_) => &[Resume, Settings, QuitToTitle, Quit],
+            Self::Settings(_) => &[Fullscreen, TargetFps, ShowStats, Back],
+            Self::ConfirmQuit(_) => &[No, Yes],
+            Self::Editor | Self::Playtest => &[],
+            Self::EditorMenu(_) => &[Resume, Test, Save, Load, Slot, New, QuitToTitle],
+            Self::EnterSeed(_) => &[SeedEntry, Back],
+            Self::Results(_) => &[NextLevel, Retry, QuitToTitle],
+            Self::Multiplayer(_) => &[PlayerCount, Mode, Start, Back],
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Title(_) => crate::TITLE,
+            Self::LevelSelect(_) => "Select a level",
+            Self::Gameplay | Self::Editor | Self::Playtest => "",
+            Self::Pause(_) => "Paused",
+            Self::Settings(_) => "Settings",
+            Self::ConfirmQuit(_) => "Really quit?",
+            Self::EditorMenu(_) => "Editor",
+            Self::EnterSeed(_) => "Enter a seed",
+            Self::Results(_) => "Solved!",
+            Self::Multiplayer(_) => "Local multiplayer",
+        }
+    }
+
+    /// Overlays are drawn on top of the scene below them, instead of replacing
+    /// it.
+    pub fn is_overlay(self) -> bool {
+        matches!(
+            self,
+            Self::Pause(_)
+            | Self::Settings(_)
+            | Self::ConfirmQuit(_)
+            | Self::EditorMenu(_)
+            | Self::Results(_)
+        )
+    }
+
+    pub fn selected(self) -> Option<MenuIndex> {
+        match self {
+            Self::Title(selected)
+            | Self::LevelSelect(selected)
+            | Self::Pause(selected)
+            | Self::Settings(selected)
+            | Self::ConfirmQuit(selected)
+            | Self::EditorMenu(selected)
+            | Self::EnterSeed(selected)
+            | Self::Results(selected)
+            | Self::Multiplayer(selected) => Some(selected),
+            Self::Gameplay | Self::Editor | Self::Playtest => None,
+        }
+    }
+
+    fn selected_mut(&mut self) -> Option<&mut MenuIndex> {
+        match self {
+            Self::Title(selected)
+            | Self::LevelSelect(selected)
+            | Self::Pause(selected)
+            | Self::Settings(selected)
+            | Self::ConfirmQuit(selected)
+            | Self::EditorMenu(selected)
+            | Self::EnterSeed(selected)
+            | Self::Results(selected)
+            | Self::Multiplayer(selected) => Some(selected),
+            Self::Gameplay | Self::Editor | Self::Playtest => None,
+        }
+    }
+
+    /// What happens when the menu button is pressed in this scene.
+    fn back_action(self) -> Action {
+        match self {
+            Self::Title(_) => Action::Push(Self::ConfirmQuit(0)),
+            // The board is finished, so there's nothing to go back to.
+            Self::Results(_) => Action::Reset(Self::Title(0)),
+            Self::Gameplay => Action::Push(Self::Pause(0)),
+            Self::Editor => Action::Push(Self::EditorMenu(0)),
+            Self::LevelSelect(_)
+            | Self::Pause(_)
+            | Self::Settings(_)
+            | Self::ConfirmQuit(_)
+            | Self::EditorMenu(_)
+            | Self::Playtest
+            | Self::EnterSeed(_)
+            | Self::Multiplayer(_) => Action::Pop,
+        }
+    }
+
+    /// Handles the menu input for this scene. Should be called once per frame,
+    /// so that each press only does one thing.
+    pub fn update(&mut self, input_flags: InputFlags) -> Option<Action> {
+        if input_flags & INPUT_MENU_PRESSED != 0 {
+            return Some(self.back_action())
+        }
+
+        let options = self.options();
+        let option_count = options.len() as MenuIndex;
+
+        let selected = self.selected_mut()?;
+
+        if input_flags & INPUT_UP_PRESSED != 0 {
+            *selected = selected.checked_sub(1).unwrap_or(option_count - 1);
+        }
+        if input_flags & INPUT_DOWN_PRESSED != 0 {
+            *selected = (*selected + 1) % option_count;
+        }
+
+        if input_flags & INPUT_INTERACT_PRESSED == 0 {
+            return None
+        }
+
+        use MenuOption::*;
+        Some(match options.get(usize::from(*selected))? {
+            Play => Action::PlaySolo,
+            Daily => Action::StartDaily,
+            EnterSeed => Action::Push(Self::EnterSeed(0)),
+            SeedEntry => Action::UseEnteredSeed,
+            Editor => Action::Push(Self::Editor),
+            Level(level) => Action::StartLevel(*level),
+            Resume | Back | No => Action::Pop,
+            Settings => Action::Push(Self::Settings(0)),
+            Fullscreen => Action::ToggleFullscreen,
+            TargetFps => Action::NextTargetFps,
+            ShowStats => Action::ToggleShowStats,
+            QuitToTitle => Action::Reset(Self::Title(0)),
+            Quit => Action::Push(Self::ConfirmQuit(0)),
+            Yes => Action::Quit,
+            Test => Action::StartPlaytest,
+            Save => Action::SaveLevel,
+            Load => Action::LoadLevel,
+            Slot => Action::NextSlot,
+            New => Action::NewLevel,
+            NextLevel => Action::NextLevel,
+            Retry => Action::Retry,
+            Multiplayer => Action::Push(Self::Multiplayer(0)),
+            PlayerCount => Action::NextPlayerCount,
+            Mode => Action::NextMode,
+            Start => Action::PlayTogether,
+        })
+    }
+}
+
+/// More than enough for any sequence of menus we currently have.
+pub const MAX_DEPTH: usize = 8;
+
+/// A stack of scenes, which is never empty. Only the top scene gets updated.
+#[derive(Debug)]
+pub struct Stack {
+    scenes: [Scene; MAX_DEPTH],
+    len: usize,
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self {
+            scenes: [Scene::default(); MAX_DEPTH],
+            len: 1,
+        }
+    }
+}
+
+impl Stack {
+    pub fn top(&self) -> Scene {
+        self.scenes[self.len - 1]
+    }
+
+    pub fn top_mut(&mut self) -> &mut Scene {
+        &mut self.scenes[self.len - 1]
+    }
+
+    /// If the stack is full, the top scene is replaced instead.
+    pub fn push(&mut self, scene: Scene) {
+        if self.len < MAX_DEPTH {
+            self.len += 1;
+        }
+        *self.top_mut() = scene;
+    }
+
+    /// Does nothing if there is only one scene left.
+    pub fn pop(&mut self) {
+        if self.len > 1 {
+            self.len -= 1;
+        }
+    }
+
+    pub fn reset(&mut self, scene: Scene) {
+        self.len = 1;
+        self.scenes[0] = scene;
+    }
+
+    /// Returns the highest scene that isn't an overlay. This, and the top scene
+    /// if it is an overlay, are the scenes that should be drawn.
+    pub fn base(&self) -> Scene {
+        self.scenes[..self.len]
+            .iter()
+            .rev()
+            .find(|scene| !scene.is_overlay())
+            .copied()
+            .unwrap_or(self.scenes[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushing_onto_a_full_stack_replaces_the_top() {
+        let mut stack = Stack::default();
+        assert_eq!(stack.top(), Scene::Title(0));
+
+        for i in 1..=MAX_DEPTH as MenuIndex + 1 {
+            stack.push(Scene::LevelSelect(i));
+            assert_eq!(stack.top(), Scene::LevelSelect(i));
+        }
+
+        // The last three pushes all went into the top slot.
+        stack.pop();
+        assert_eq!(stack.top(), Scene::LevelSelect(MAX_DEPTH as MenuIndex - 2));
+    }
+
+    #[test]
+    fn popping_the_root_scene_does_nothing() {
+        let mut stack = Stack::default();
+        stack.push(Scene::Settings(1));
+        stack.pop();
+        assert_eq!(stack.top(), Scene::Title(0));
+
+        stack.pop();
+        stack.pop();
+        assert_eq!(stack.top(), Scene::Title(0));
+
+        stack.push(Scene::ConfirmQuit(0));
+        assert_eq!(stack.top(), Scene::ConfirmQuit(0));
+    }
+
+    #[test]
+    fn the_base_is_the_highest_scene_that_is_not_an_overlay() {
+        let mut stack = Stack::default();
+        stack.push(Scene::Gameplay);
+        stack.push(Scene::Pause(0));
+        stack.push(Scene::Settings(0));
+        assert_eq!(stack.base(), Scene::Gameplay);
+
+        stack.reset(Scene::Results(0));
+        assert_eq!(stack.top(), Scene::Results(0));
+        // There's always something to draw.
+        assert_eq!(stack.base(), Scene::Results(0));
+    }
+
+    /// Selects each option of `scene` in turn, from the top, and returns what
+    /// choosing it does.
+    fn actions(scene: Scene) -> Vec<Option<Action>> {
+        let mut scene = scene;
+        let mut actions = Vec::new();
+
+        for _ in scene.options() {
+            let mut chosen = scene;
+            actions.push(chosen.update(INPUT_INTERACT_PRESSED));
+            assert_eq!(scene.update(INPUT_DOWN_PRESSED), None);
+        }
+
+        // Moving down from the last option goes back to the top.
+        assert_eq!(scene.selected(), Some(0));
+
+        actions
+    }
+
+    #[test]
+    fn menu_selections_wrap_around() {
+        let mut scene = Scene::Pause(0);
+
+        assert_eq!(scene.update(INPUT_UP_PRESSED), None);
+        assert_eq!(scene, Scene::Pause(3));
+        assert_eq!(scene.update(INPUT_DOWN_PRESSED), None);
+        assert_eq!(scene, Scene::Pause(0));
+
+        // Scenes without a menu don't have a selection to move.
+        let mut scene = Scene::Gameplay;
+        assert_eq!(scene.update(INPUT_DOWN_PRESSED | INPUT_INTERACT_PRESSED), None);
+        assert_eq!(scene, Scene::Gameplay);
+    }
+
+    #[test]
+    fn title_menu_options_do_what_they_say() {
+        assert_eq!(
+            actions(Scene::Title(0)),
+            [
+                Some(Action::PlaySolo),
+                Some(Action::Push(Scene::Multiplayer(0))),
+                Some(Action::StartDaily),
+                Some(Action::Push(Scene::EnterSeed(0))),
+                Some(Action::Push(Scene::Editor)),
+                Some(Action::Push(Scene::Settings(0))),
+                Some(Action::Push(Scene::ConfirmQuit(0))),
+            ]
+        );
+        assert_eq!(
+            Scene::Title(3).update(INPUT_MENU_PRESSED),
+            Some(Action::Push(Scene::ConfirmQuit(0)))
+        );
+    }
+
+    #[test]
+    fn level_select_options_do_what_they_say() {
+        assert_eq!(
+            actions(Scene::LevelSelect(0)),
+            [
+                Some(Action::StartLevel(1)),
+                Some(Action::StartLevel(2)),
+                Some(Action::StartLevel(3)),
+                Some(Action::StartLevel(4)),
+                Some(Action::StartLevel(5)),
+                Some(Action::Pop),
+            ]
+        );
+        assert_eq!(Scene::LevelSelect(2).update(INPUT_MENU_PRESSED), Some(Action::Pop));
+    }
+
+    #[test]
+    fn pause_menu_options_do_what_they_say() {
+        assert_eq!(
+            actions(Scene::Pause(0)),
+            [
+                Some(Action::Pop),
+                Some(Action::Push(Scene::Settings(0))),
+                Some(Action::Reset(Scene::Title(0))),
+                Some(Action::Push(Scene::ConfirmQuit(0))),
+            ]
+        );
+        assert_eq!(Scene::Pause(1).update(INPUT_MENU_PRESSED), Some(Action::Pop));
+        assert_eq!(
+            Scene::Gameplay.update(INPUT_MENU_PRESSED),
+            Some(Action::Push(Scene::Pause(0)))
+        );
+    }
+
+    #[test]
+    fn settings_options_do_what_they_say() {
+        assert_eq!(
+            actions(Scene::Settings(0)),
+            [
+                Some(Action::ToggleFullscreen),
+                Some(Action::NextTargetFps),
+                Some(Action::ToggleShowStats),
+                Some(Action::Pop),
+            ]
+        );
+        assert_eq!(Scene::Settings(2).update(INPUT_MENU_PRESSED), Some(Action::Pop));
+    }
+
+    #[test]
+    fn quitting_needs_confirming() {
+        assert_eq!(
+            actions(Scene::ConfirmQuit(0)),
+            [Some(Action::Pop), Some(Action::Quit)]
+        );
+        // Backing out never quits.
+        assert_eq!(Scene::ConfirmQuit(1).update(INPUT_MENU_PRESSED), Some(Action::Pop));
+    }
+}
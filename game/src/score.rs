@@ -0,0 +1,371 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! How well a board was played, and the best results so far for each board.
+
+use crate::{
+    level::MoveCount,
+    scene::LevelNumber,
+    seed,
+    Seed,
+    TILES_LENGTH,
+    UPDATES_PER_SECOND,
+};
+
+/// Update steps, which happen `UPDATES_PER_SECOND` times a second.
+pub type Ticks = u32;
+
+pub type Points = u32;
+
+/// Taking longer than this per move, on average, costs time points.
+pub const PAR_TICKS_PER_MOVE: Ticks = 2 * UPDATES_PER_SECOND;
+
+/// For finishing a board in the fewest moves possible.
+pub const MOVE_POINTS: Points = 1000;
+
+/// For finishing a board within the par time.
+pub const TIME_POINTS: Points = 500;
+
+pub fn seconds(ticks: Ticks) -> f32 {
+    ticks as f32 / UPDATES_PER_SECOND as f32
+}
+
+/// What happened on a finished board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub seed: Seed,
+    pub level: LevelNumber,
+    pub moves: MoveCount,
+    /// The fewest moves the board could be finished in.
+    pub par_moves: MoveCount,
+    pub ticks: Ticks,
+}
+
+impl Entry {
+    /// Which board this entry is for.
+    fn board(&self) -> (Seed, LevelNumber) {
+        (self.seed, self.level)
+    }
+
+    pub fn par_ticks(&self) -> Ticks {
+        Ticks::from(self.par_moves).saturating_mul(PAR_TICKS_PER_MOVE)
+    }
+
+    /// Each part of the score goes down in proportion to how far over par it
+    /// was, so taking twice as long as par gets half the time points. Higher
+    /// levels are worth more, so harder boards score higher.
+    pub fn points(&self) -> Points {
+        // Done in `u64`, since `points * par` doesn't fit in a `Points` for
+        // large enough pars.
+        fn fraction_of(points: Points, par: u64, actual: u64) -> u64 {
+            let par = par.max(1);
+            u64::from(points) * par / actual.max(par)
+        }
+
+        let move_points = fraction_of(
+            MOVE_POINTS,
+            u64::from(self.par_moves),
+            u64::from(self.moves)
+        );
+        let time_points = fraction_of(
+            TIME_POINTS,
+            u64::from(self.par_moves) * u64::from(PAR_TICKS_PER_MOVE),
+            u64::from(self.ticks)
+        );
+
+        let total = (move_points + time_points) * u64::from(self.level.max(1));
+
+        Points::try_from(total).unwrap_or(Points::MAX)
+    }
+}
+
+/// How many entries are kept for each board.
+pub const ENTRIES_PER_BOARD: usize = 5;
+
+/// Without an allocator, the table has a fixed size, and once it is full, only
+/// boards already in it get new entries.
+#[cfg(not(feature = "alloc"))]
+pub const MAX_ENTRIES: usize = 32 * ENTRIES_PER_BOARD;
+
+#[cfg(feature = "alloc")]
+type Entries = alloc::vec::Vec<Entry>;
+#[cfg(not(feature = "alloc"))]
+type Entries = crate::storage::ArrayVec<Entry, MAX_ENTRIES>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HighScores {
+    /// Sorted by board, then from the highest points to the lowest, with at
+    /// most `ENTRIES_PER_BOARD` for each board.
+    entries: Entries,
+}
+
+/// The position of an entry in the table for its board, starting from 0.
+pub type Rank = usize;
+
+impl HighScores {
+    /// The entries for the given board, from the highest points to the lowest.
+    pub fn for_board(
+        &self,
+        seed: Seed,
+        level: LevelNumber,
+    ) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+            .filter(move |entry| entry.board() == (seed, level))
+    }
+
+    /// Returns where the entry ended up in the table for its board, or `None`
+    /// if it did not score high enough to be kept.
+    pub fn insert(&mut self, entry: Entry) -> Option<Rank> {
+        let board = entry.board();
+        let points = entry.points();
+
+        // Going after entries with equal points means ties don't push out
+        // older entries.
+        let index = self.entries.iter()
+            .position(|other| {
+                other.board() > board
+                || (other.board() == board && other.points() < points)
+            })
+            .unwrap_or(self.entries.len());
+
+        let first = self.entries.iter()
+            .position(|other| other.board() == board)
+            .unwrap_or(index);
+        let rank = index - first;
+
+        if rank >= ENTRIES_PER_BOARD {
+            return None
+        }
+
+        // Making room before inserting means a full table can still take
+        // entries for boards already in it.
+        let last_kept = first + ENTRIES_PER_BOARD - 1;
+        if self.entries.get(last_kept)
+            .is_some_and(|other| other.board() == board) {
+            self.entries.remove(last_kept);
+        }
+
+        #[cfg(feature = "alloc")]
+        self.entries.insert(index, entry);
+        #[cfg(not(feature = "alloc"))]
+        self.entries.insert(index, entry).ok()?;
+
+        Some(rank)
+    }
+}
+
+pub type Version = u32;
+
+pub const CURRENT_VERSION: Version = 1;
+
+pub type LineNumber = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnknownVersion,
+    /// Entries have `seed level moves par_moves ticks`.
+    WrongFieldCount,
+    BadSeed(seed::ParseError),
+    BadNumber,
+    /// No board has more tiles than this, so no board could need more moves.
+    ParMovesTooHigh,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Starting from 1, like text editors do.
+    pub line: LineNumber,
+    pub kind: ParseErrorKind,
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use ParseErrorKind::*;
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            UnknownVersion => write!(f, "unknown version"),
+            WrongFieldCount => write!(f, "expected `seed level moves par_moves ticks`"),
+            BadSeed(err) => write!(f, "bad seed: {}", err),
+            BadNumber => write!(f, "expected a whole number"),
+            ParMovesTooHigh => write!(f, "par_moves can be at most {}", TILES_LENGTH),
+        }
+    }
+}
+
+/// Points are not stored, since they can be worked out from the rest, so
+/// changing the formula doesn't leave old scores inconsistent.
+pub fn write(
+    w: &mut dyn core::fmt::Write,
+    high_scores: &HighScores,
+) -> core::fmt::Result {
+    writeln!(w, "version = {}", CURRENT_VERSION)?;
+    writeln!(w, "# seed level moves par_moves ticks")?;
+    for entry in &high_scores.entries {
+        writeln!(
+            w,
+            "{} {} {} {} {}",
+            seed::encode(entry.seed),
+            entry.level,
+            entry.moves,
+            entry.par_moves,
+            entry.ticks,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Blank lines and lines starting with `#` are ignored.
+pub fn parse(text: &str) -> Result<HighScores, ParseError> {
+    let mut high_scores = HighScores::default();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |kind| ParseError { line: line_number, kind };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+
+        if let Some((_, value)) = line.split_once('=') {
+            if value.trim().parse() != Ok(CURRENT_VERSION) {
+                return Err(error(ParseErrorKind::UnknownVersion))
+            }
+            continue
+        }
+
+        let mut fields = line.split_whitespace();
+        let (
+            Some(seed_code),
+            Some(level),
+            Some(moves),
+            Some(par_moves),
+            Some(ticks),
+            None,
+        ) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            return Err(error(ParseErrorKind::WrongFieldCount))
+        };
+
+        let bad_number = |_| error(ParseErrorKind::BadNumber);
+
+        let par_moves: MoveCount = par_moves.parse().map_err(bad_number)?;
+        if usize::from(par_moves) > TILES_LENGTH {
+            return Err(error(ParseErrorKind::ParMovesTooHigh))
+        }
+
+        // Inserting, rather than pushing, keeps the entries sorted even if
+        // the file was edited by hand.
+        high_scores.insert(Entry {
+            seed: seed::parse(seed_code)
+                .map_err(|err| error(ParseErrorKind::BadSeed(err)))?,
+            level: level.parse().map_err(bad_number)?,
+            moves: moves.parse().map_err(bad_number)?,
+            par_moves,
+            ticks: ticks.parse().map_err(bad_number)?,
+        });
+    }
+
+    Ok(high_scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(seed_byte: u8, level: LevelNumber, moves: MoveCount) -> Entry {
+        Entry {
+            seed: [seed_byte; 16],
+            level,
+            moves,
+            par_moves: 4,
+            ticks: 4 * PAR_TICKS_PER_MOVE,
+        }
+    }
+
+    #[test]
+    fn points_do_not_overflow_for_large_pars() {
+        let entry = Entry {
+            par_moves: MoveCount::MAX,
+            moves: MoveCount::MAX,
+            ticks: Ticks::MAX,
+            level: LevelNumber::MAX,
+            ..Entry::default()
+        };
+
+        assert!(entry.points() > 0);
+    }
+
+    #[test]
+    fn entries_are_kept_from_the_highest_points_to_the_lowest() {
+        let mut high_scores = HighScores::default();
+
+        assert_eq!(high_scores.insert(entry(0, 1, 8)), Some(0));
+        assert_eq!(high_scores.insert(entry(0, 1, 4)), Some(0));
+        assert_eq!(high_scores.insert(entry(0, 1, 6)), Some(1));
+        // Ties go after the existing entry.
+        assert_eq!(high_scores.insert(entry(0, 1, 6)), Some(2));
+        // Other boards don't affect the ranks.
+        assert_eq!(high_scores.insert(entry(1, 1, 5)), Some(0));
+        assert_eq!(high_scores.insert(entry(0, 2, 5)), Some(0));
+
+        let moves: Vec<_> = high_scores.for_board([0; 16], 1)
+            .map(|entry| entry.moves)
+            .collect();
+        assert_eq!(moves, [4, 6, 6, 8]);
+    }
+
+    #[test]
+    fn only_the_best_entries_for_each_board_are_kept() {
+        let mut high_scores = HighScores::default();
+        let worst_kept = 4 + ENTRIES_PER_BOARD as MoveCount - 1;
+
+        for moves in 4..=worst_kept {
+            assert!(high_scores.insert(entry(0, 1, moves)).is_some());
+        }
+        assert_eq!(high_scores.insert(entry(1, 1, 4)), Some(0));
+
+        assert_eq!(high_scores.insert(entry(0, 1, worst_kept + 1)), None);
+        assert_eq!(high_scores.insert(entry(0, 1, 5)), Some(2));
+
+        let moves: Vec<_> = high_scores.for_board([0; 16], 1)
+            .map(|entry| entry.moves)
+            .collect();
+        assert_eq!(moves, [4, 5, 5, 6, 7]);
+        assert_eq!(high_scores.for_board([1; 16], 1).count(), 1);
+    }
+
+    #[test]
+    fn written_high_scores_parse_back_the_same() {
+        let mut high_scores = HighScores::default();
+        for (i, moves) in [9, 4, 6].into_iter().enumerate() {
+            high_scores.insert(entry(i as u8, 3, moves));
+            high_scores.insert(entry(0, i as LevelNumber + 1, moves));
+        }
+
+        let mut text = String::new();
+        write(&mut text, &high_scores).unwrap();
+
+        assert_eq!(parse(&text), Ok(high_scores));
+    }
+
+    #[test]
+    fn impossible_pars_are_rejected() {
+        let text = format!(
+            "version = 1\n{} 1 1 20000 1\n",
+            seed::encode([0; 16])
+        );
+
+        assert_eq!(
+            parse(&text),
+            Err(ParseError { line: 2, kind: ParseErrorKind::ParMovesTooHigh })
+        );
+    }
+}
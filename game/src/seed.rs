@@ -0,0 +1,276 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Seeds as short codes that people can read out or type in, and seeds for
+//! the daily challenge.
+//!
+//! Codes use Crockford's base 32 alphabet, which leaves out letters that are
+//! easily confused with digits, followed by two check characters, so that a
+//! mistyped code is rejected instead of silently giving a different game. Seeds
+//! made from the time have lots of leading zeroes, which are left out, so those
+//! codes are about 15 characters long.
+
+use crate::{
+    draw::StrBuf,
+    new_seed,
+    xorshift,
+    xs_from_seed,
+    Seed,
+};
+
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+const BITS_PER_CHAR: u32 = 5;
+
+/// A prime, so that changing any single character, or swapping two adjacent
+/// ones, always changes the check value. It is less than 32 * 32, so it fits
+/// in two characters.
+const CHECK_MODULUS: u128 = 1021;
+
+const CHECK_LENGTH: usize = 2;
+
+/// Enough characters for all 128 bits.
+const MAX_DATA_LENGTH: usize = 128_usize.div_ceil(BITS_PER_CHAR as usize);
+
+/// How many characters go between each `-`, to make codes easier to read.
+const GROUP_LENGTH: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Holds the first character that is not part of the alphabet.
+    BadChar(char),
+    TooShort,
+    TooLong,
+    /// The code was probably mistyped.
+    BadCheck,
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use ParseError::*;
+        match self {
+            BadChar(c) => write!(f, "{:?} is not used in seed codes", c),
+            TooShort => write!(f, "too short"),
+            TooLong => write!(f, "too long"),
+            BadCheck => write!(f, "typo?"),
+        }
+    }
+}
+
+fn check_value(n: u128) -> u128 {
+    n % CHECK_MODULUS
+}
+
+/// Returns the value of `c` in the alphabet, accepting lower case too, and the
+/// letters that Crockford's scheme treats as look-alikes of digits.
+pub fn char_value(c: char) -> Option<u8> {
+    let c = match c.to_ascii_uppercase() {
+        'O' => '0',
+        'I' | 'L' => '1',
+        c => c,
+    };
+
+    ALPHABET.iter()
+        .position(|&a| char::from(a) == c)
+        .map(|i| i as u8)
+}
+
+/// Characters that `parse` skips, so codes can be grouped however people like.
+pub fn is_separator(c: char) -> bool {
+    c == '-' || c.is_whitespace()
+}
+
+pub fn encode(seed: Seed) -> StrBuf {
+    let n = u128::from_le_bytes(seed);
+
+    let mut values = [0u8; MAX_DATA_LENGTH + CHECK_LENGTH];
+    let mut len = 0;
+
+    let mut check = check_value(n);
+    for _ in 0..CHECK_LENGTH {
+        values[len] = (check % 32) as u8;
+        check /= 32;
+        len += 1;
+    }
+
+    let mut remaining = n;
+    loop {
+        values[len] = (remaining % 32) as u8;
+        remaining /= 32;
+        len += 1;
+
+        if remaining == 0 {
+            break
+        }
+    }
+
+    let mut output = StrBuf::new();
+    for (i, &value) in values[..len].iter().rev().enumerate() {
+        if i > 0 && i % GROUP_LENGTH == 0 {
+            output.push('-');
+        }
+        output.push(char::from(ALPHABET[usize::from(value)]));
+    }
+
+    output
+}
+
+pub fn parse(code: &str) -> Result<Seed, ParseError> {
+    let mut n: u128 = 0;
+    // The last `CHECK_LENGTH` values read, oldest first.
+    let mut check = [0u8; CHECK_LENGTH];
+    let mut len = 0;
+
+    for c in code.chars().filter(|&c| !is_separator(c)) {
+        let value = char_value(c).ok_or(ParseError::BadChar(c))?;
+
+        if len >= CHECK_LENGTH {
+            n = n.checked_mul(32)
+                .and_then(|n| n.checked_add(u128::from(check[0])))
+                .ok_or(ParseError::TooLong)?;
+        }
+        check.rotate_left(1);
+        check[CHECK_LENGTH - 1] = value;
+        len += 1;
+    }
+
+    if len <= CHECK_LENGTH {
+        return Err(ParseError::TooShort)
+    }
+
+    let expected = check.iter()
+        .fold(0, |acc, &value| acc * 32 + u128::from(value));
+    if check_value(n) != expected {
+        return Err(ParseError::BadCheck)
+    }
+
+    Ok(n.to_le_bytes())
+}
+
+/// A day on the calendar. Platform layers should use the player's local date,
+/// or the date in UTC if the local time zone can't be found. See `set_date`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Date {
+    pub year: u16,
+    /// From 1 to 12.
+    pub month: u8,
+    /// From 1 to 31.
+    pub day: u8,
+}
+
+/// Everyone playing on the same date gets the same seed.
+pub fn daily(date: Date) -> Seed {
+    let n = u128::from(date.year) * 10_000
+        + u128::from(date.month) * 100
+        + u128::from(date.day);
+
+    let mut rng = xs_from_seed((n ^ 0xDA11_5EED).to_le_bytes());
+    // The first outputs from a seed with so few bits set still look a lot like
+    // it, so we skip those, so consecutive days don't have similar seeds.
+    for _ in 0..16 {
+        xorshift(&mut rng);
+    }
+
+    new_seed(&mut rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{xs_from_seed, Xs};
+
+    fn some_seeds() -> impl Iterator<Item = Seed> {
+        let mut rng: Xs = xs_from_seed([0x5E; 16]);
+
+        [[0; 16], [0xFF; 16], 1u128.to_le_bytes(), 32u128.to_le_bytes()]
+            .into_iter()
+            .chain((0..64).map(move |_| new_seed(&mut rng)))
+    }
+
+    /// The code without separators, as a list of chars.
+    fn code_chars(seed: Seed) -> Vec<char> {
+        encode(seed).chars().filter(|&c| !is_separator(c)).collect()
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        for seed in some_seeds() {
+            let code = encode(seed);
+            assert_eq!(parse(&code), Ok(seed), "{}", code);
+            assert_eq!(parse(&code.to_ascii_lowercase()), Ok(seed), "{}", code);
+            assert_eq!(parse(&code.replace('-', " ")), Ok(seed), "{}", code);
+        }
+    }
+
+    #[test]
+    fn look_alike_letters_are_read_as_digits() {
+        // Has both a 1 and a 0 in it.
+        let seed = 32u128.to_le_bytes();
+        let code = encode(seed);
+        assert!(code.starts_with("10"), "{}", code);
+
+        for (one, zero) in [("I", "O"), ("L", "o"), ("i", "O"), ("l", "o")] {
+            let typed = code.replacen('1', one, 1).replacen('0', zero, 1);
+            assert_eq!(parse(&typed), Ok(seed), "{}", typed);
+        }
+    }
+
+    #[test]
+    fn bad_lengths_and_chars_are_rejected() {
+        assert_eq!(parse(""), Err(ParseError::TooShort));
+        assert_eq!(parse("1-2"), Err(ParseError::TooShort));
+        assert_eq!(parse(&"Z".repeat(MAX_DATA_LENGTH + CHECK_LENGTH)), Err(ParseError::TooLong));
+        assert_eq!(parse("12U4"), Err(ParseError::BadChar('U')));
+    }
+
+    #[test]
+    fn single_character_mistakes_are_rejected() {
+        for seed in some_seeds() {
+            let chars = code_chars(seed);
+
+            for i in 0..chars.len() {
+                for &replacement in ALPHABET {
+                    let replacement = char::from(replacement);
+                    if replacement == chars[i] {
+                        continue
+                    }
+
+                    let mut typed = chars.clone();
+                    typed[i] = replacement;
+                    let typed: String = typed.into_iter().collect();
+
+                    assert!(
+                        matches!(parse(&typed), Err(ParseError::BadCheck | ParseError::TooLong)),
+                        "{} was accepted, instead of {}",
+                        typed,
+                        encode(seed),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transpositions_are_rejected() {
+        for seed in some_seeds() {
+            let chars = code_chars(seed);
+
+            for i in 1..chars.len() {
+                if chars[i - 1] == chars[i] {
+                    continue
+                }
+
+                let mut typed = chars.clone();
+                typed.swap(i - 1, i);
+                let typed: String = typed.into_iter().collect();
+
+                assert!(
+                    matches!(parse(&typed), Err(ParseError::BadCheck | ParseError::TooLong)),
+                    "{} was accepted, instead of {}",
+                    typed,
+                    encode(seed),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+/// Frames per second. Zero means no limit.
+pub type TargetFps = u32;
+
+/// The frame rates the settings menu cycles through.
+pub const TARGET_FPS_CHOICES: [TargetFps; 5] = [30, 60, 120, 144, 0];
+
+/// A percentage, from 0 to `MAX_VOLUME`.
+pub type Volume = u8;
+
+pub const MAX_VOLUME: Volume = 100;
+
+/// Options the player can change. The game only stores these; it is up to the
+/// platform layer to act on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settings {
+    pub fullscreen: bool,
+    pub target_fps: TargetFps,
+    pub show_stats: bool,
+    /// Not used for anything yet, since there is no sound yet.
+    pub volume: Volume,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fullscreen: true,
+            target_fps: 60,
+            show_stats: false,
+            volume: 80,
+        }
+    }
+}
+
+impl Settings {
+    pub fn next_target_fps(&mut self) {
+        let index = TARGET_FPS_CHOICES.iter()
+            .position(|&fps| fps == self.target_fps)
+            .map_or(0, |i| (i + 1) % TARGET_FPS_CHOICES.len());
+
+        self.target_fps = TARGET_FPS_CHOICES[index];
+    }
+}
+
+pub type Version = u32;
+
+/// The history of the file format:
+/// 1. `fullscreen`, `fps` and `show_stats`.
+/// 2. `fps` renamed to `target_fps`, and `volume` added.
+pub const CURRENT_VERSION: Version = 2;
+
+pub type LineNumber = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The line was not blank, a comment, or a `key = value` pair.
+    NotAKeyValuePair,
+    BadVersion,
+    BadBool,
+    BadNumber,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Starting from 1, like text editors do.
+    pub line: LineNumber,
+    pub kind: ParseErrorKind,
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use ParseErrorKind::*;
+        let description = match self.kind {
+            NotAKeyValuePair => "expected `key = value`",
+            BadVersion => "expected a version number",
+            BadBool => "expected `true` or `false`",
+            BadNumber => "expected a whole number",
+        };
+
+        write!(f, "line {}: {}", self.line, description)
+    }
+}
+
+/// Migrates a key from an older version of the format, to the key it has in
+/// the current version.
+fn migrate_key(version: Version, key: &str) -> &str {
+    match (version, key) {
+        (1, "fps") => "target_fps",
+        _ => key,
+    }
+}
+
+/// The lines that aren't blank or comments, trimmed, with their line numbers.
+fn content_lines(text: &str) -> impl Iterator<Item = (LineNumber, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Parses the `key = value` format that `write` produces, starting from the
+/// defaults, so missing keys keep their default values. Unknown keys are
+/// ignored, so that files from newer versions can still be read. Files without
+/// a version are treated as version 1.
+pub fn parse(text: &str) -> Result<Settings, ParseError> {
+    let mut settings = Settings::default();
+
+    // The version decides what the other keys mean, wherever it is in the
+    // file, so it is found first.
+    let mut version: Version = 1;
+    for (line_number, line) in content_lines(text) {
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "version" {
+                version = value.trim().parse()
+                    .map_err(|_| ParseError {
+                        line: line_number,
+                        kind: ParseErrorKind::BadVersion,
+                    })?;
+                break
+            }
+        }
+    }
+
+    for (line_number, line) in content_lines(text) {
+        let error = |kind| ParseError { line: line_number, kind };
+
+        let (key, value) = line.split_once('=')
+            .ok_or(error(ParseErrorKind::NotAKeyValuePair))?;
+        let (key, value) = (key.trim(), value.trim());
+
+        let parse_bool = || match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(error(ParseErrorKind::BadBool)),
+        };
+
+        match migrate_key(version, key) {
+            // Already handled above.
+            "version" => {},
+            "fullscreen" => {
+                settings.fullscreen = parse_bool()?;
+            },
+            "target_fps" => {
+                settings.target_fps = value.parse()
+                    .map_err(|_| error(ParseErrorKind::BadNumber))?;
+            },
+            "show_stats" => {
+                settings.show_stats = parse_bool()?;
+            },
+            "volume" => {
+                let volume: u64 = value.parse()
+                    .map_err(|_| error(ParseErrorKind::BadNumber))?;
+                settings.volume = core::cmp::min(volume, MAX_VOLUME.into()) as Volume;
+            },
+            _ => {},
+        }
+    }
+
+    Ok(settings)
+}
+
+/// Writes the settings in the current version of the format.
+pub fn write(
+    w: &mut dyn core::fmt::Write,
+    settings: &Settings,
+) -> core::fmt::Result {
+    writeln!(w, "version = {}", CURRENT_VERSION)?;
+    writeln!(w, "fullscreen = {}", settings.fullscreen)?;
+    writeln!(w, "# 0 means no limit.")?;
+    writeln!(w, "target_fps = {}", settings.target_fps)?;
+    writeln!(w, "show_stats = {}", settings.show_stats)?;
+    writeln!(w, "# From 0 to {}.", MAX_VOLUME)?;
+    writeln!(w, "volume = {}", settings.volume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::LongStrBuf;
+
+    #[test]
+    fn written_settings_parse_back_the_same() {
+        let settings = Settings {
+            fullscreen: false,
+            target_fps: 144,
+            show_stats: true,
+            volume: 35,
+        };
+
+        let mut text = LongStrBuf::new();
+        write(&mut text, &settings).unwrap();
+
+        assert_eq!(parse(&text), Ok(settings));
+    }
+
+    #[test]
+    fn version_1_files_have_fps_moved_to_target_fps() {
+        let expected = Settings {
+            fullscreen: false,
+            target_fps: 30,
+            ..<_>::default()
+        };
+
+        // Version 1 files didn't always say what version they were.
+        assert_eq!(parse("fullscreen = false\nfps = 30\n"), Ok(expected));
+        assert_eq!(parse("version = 1\nfullscreen = false\nfps = 30\n"), Ok(expected));
+
+        // Only version 1 had `fps`, wherever the version line is.
+        for text in ["version = 2\nfps = 30\n", "fps = 30\n# Moved down.\nversion = 2\n"] {
+            assert_eq!(
+                parse(text).map(|settings| settings.target_fps),
+                Ok(Settings::default().target_fps),
+                "{:?}",
+                text
+            );
+        }
+        assert_eq!(
+            parse("fullscreen = false\nfps = 30\nversion = 1\n"),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_missing_keys_are_fine() {
+        let text = "\
+version = 3
+# From a newer version.
+colour_scheme = dark
+show_stats = true
+";
+
+        assert_eq!(
+            parse(text),
+            Ok(Settings { show_stats: true, ..<_>::default() })
+        );
+        assert_eq!(parse(""), Ok(Settings::default()));
+    }
+
+    #[test]
+    fn volumes_past_the_max_are_capped() {
+        assert_eq!(
+            parse("volume = 1000").map(|settings| settings.volume),
+            Ok(MAX_VOLUME)
+        );
+    }
+
+    #[test]
+    fn corrupt_values_give_the_line_and_kind() {
+        use ParseErrorKind::*;
+
+        for (text, line, kind) in [
+            ("fullscreen", 1, NotAKeyValuePair),
+            ("# Comment\n\nversion = two", 3, BadVersion),
+            ("show_stats = true\nfullscreen = yes", 2, BadBool),
+            ("target_fps = fast", 1, BadNumber),
+            ("target_fps = -60", 1, BadNumber),
+            ("volume = 50%", 1, BadNumber),
+        ] {
+            assert_eq!(parse(text), Err(ParseError { line, kind }), "{:?}", text);
+        }
+    }
+}
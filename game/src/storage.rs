@@ -0,0 +1,292 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Collections that don't allocate, for use where the game would otherwise
+//! need a `Vec`, so it can run without an allocator, and ready-made
+//! `ClearableStorage` implementations for platform layers to pass to `update`.
+
+use crate::ClearableStorage;
+
+/// A `Vec`-like list that holds at most `CAPACITY` elements, stored inline.
+/// Operations that would go past that return the element that didn't fit,
+/// rather than panicking.
+///
+/// Slots past the end hold `T::default()`, so `T` needs to implement
+/// `Default`, which saves us from needing `unsafe` code to track which slots
+/// are initialized.
+#[derive(Clone)]
+pub struct ArrayVec<T, const CAPACITY: usize> {
+    items: [T; CAPACITY],
+    len: usize,
+}
+
+impl<T: Default, const CAPACITY: usize> Default for ArrayVec<T, CAPACITY> {
+    fn default() -> Self {
+        Self {
+            items: core::array::from_fn(|_| T::default()),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Default, const CAPACITY: usize> ArrayVec<T, CAPACITY> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len >= CAPACITY
+    }
+
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item)
+        }
+
+        self.items[self.len] = item;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+
+        Some(core::mem::take(&mut self.items[self.len]))
+    }
+
+    /// Inserts `item` at `index`, moving everything after it along by one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`, like `Vec::insert` does.
+    pub fn insert(&mut self, index: usize, item: T) -> Result<(), T> {
+        assert!(index <= self.len, "insertion index {} is past the end", index);
+
+        if self.is_full() {
+            return Err(item)
+        }
+
+        self.items[self.len] = item;
+        self.items[index..=self.len].rotate_right(1);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Removes the element at `index`, moving everything after it back by one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`, like `Vec::remove` does.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "removal index {} is past the end", index);
+
+        self.items[index..self.len].rotate_left(1);
+        self.len -= 1;
+
+        core::mem::take(&mut self.items[self.len])
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T, const CAPACITY: usize> core::ops::Deref for ArrayVec<T, CAPACITY> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items[..self.len]
+    }
+}
+
+impl<T, const CAPACITY: usize> core::ops::DerefMut for ArrayVec<T, CAPACITY> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.items[..self.len]
+    }
+}
+
+impl<T: core::fmt::Debug, const CAPACITY: usize> core::fmt::Debug for ArrayVec<T, CAPACITY> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const CAPACITY: usize> PartialEq for ArrayVec<T, CAPACITY> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, const CAPACITY: usize> Eq for ArrayVec<T, CAPACITY> {}
+
+impl<'vec, T, const CAPACITY: usize> IntoIterator for &'vec ArrayVec<T, CAPACITY> {
+    type Item = &'vec T;
+    type IntoIter = core::slice::Iter<'vec, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Keeps everything, growing as needed.
+#[cfg(feature = "alloc")]
+impl<A> ClearableStorage<A> for alloc::vec::Vec<A> {
+    fn clear(&mut self) {
+        alloc::vec::Vec::clear(self);
+    }
+
+    fn push(&mut self, a: A) {
+        alloc::vec::Vec::push(self, a);
+    }
+}
+
+/// Keeps the first `CAPACITY` elements pushed since the last `clear`, and
+/// counts the rest, so a platform layer without an allocator can notice when
+/// it needs a larger capacity, rather than silently missing things.
+#[derive(Clone, Debug, Default)]
+pub struct ArrayStorage<A, const CAPACITY: usize> {
+    items: ArrayVec<A, CAPACITY>,
+    overflow: usize,
+}
+
+impl<A: Default, const CAPACITY: usize> ArrayStorage<A, CAPACITY> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many elements didn't fit since the last `clear`.
+    pub fn overflow(&self) -> usize {
+        self.overflow
+    }
+}
+
+impl<A: Default, const CAPACITY: usize> ClearableStorage<A> for ArrayStorage<A, CAPACITY> {
+    fn clear(&mut self) {
+        self.items.clear();
+        self.overflow = 0;
+    }
+
+    fn push(&mut self, a: A) {
+        if self.items.push(a).is_err() {
+            self.overflow = self.overflow.saturating_add(1);
+        }
+    }
+}
+
+impl<A, const CAPACITY: usize> core::ops::Deref for ArrayStorage<A, CAPACITY> {
+    type Target = [A];
+
+    fn deref(&self) -> &[A] {
+        &self.items
+    }
+}
+
+/// Allows sorting the elements in place, by layer for example.
+impl<A, const CAPACITY: usize> core::ops::DerefMut for ArrayStorage<A, CAPACITY> {
+    fn deref_mut(&mut self) -> &mut [A] {
+        &mut self.items
+    }
+}
+
+/// Throws everything away, but counts how many elements were pushed since the
+/// last `clear`. Useful for benchmarking `update` without the cost of storing
+/// the draw commands, or for running the game with nothing to draw to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CountingStorage {
+    pub count: usize,
+}
+
+impl<A> ClearableStorage<A> for CountingStorage {
+    fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    fn push(&mut self, _: A) {
+        self.count = self.count.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array_vecs_act_like_vecs_until_they_are_full() {
+        let mut vec = ArrayVec::<u8, 4>::new();
+
+        assert_eq!(vec.push(1), Ok(()));
+        assert_eq!(vec.push(3), Ok(()));
+        assert_eq!(vec.insert(1, 2), Ok(()));
+        assert_eq!(vec.insert(0, 0), Ok(()));
+        assert_eq!(*vec, [0, 1, 2, 3]);
+
+        assert!(vec.is_full());
+        assert_eq!(vec.push(4), Err(4));
+        assert_eq!(vec.insert(2, 4), Err(4));
+        assert_eq!(*vec, [0, 1, 2, 3]);
+
+        assert_eq!(vec.remove(1), 1);
+        assert_eq!(*vec, [0, 2, 3]);
+        assert_eq!(vec.pop(), Some(3));
+        assert_eq!(*vec, [0, 2]);
+
+        vec.clear();
+        assert_eq!(vec.pop(), None);
+        assert!(vec.is_empty());
+    }
+
+    fn push_then_clear_then_push(
+        storage: &mut dyn ClearableStorage<u8>,
+        first: core::ops::Range<u8>,
+        second: core::ops::Range<u8>,
+    ) {
+        for a in first {
+            storage.push(a);
+        }
+        storage.clear();
+        for a in second {
+            storage.push(a);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn vecs_keep_everything_since_the_last_clear() {
+        let mut storage = alloc::vec::Vec::new();
+
+        push_then_clear_then_push(&mut storage, 0..3, 10..15);
+
+        assert_eq!(storage, [10, 11, 12, 13, 14]);
+    }
+
+    #[test]
+    fn array_storage_keeps_what_fits_and_counts_the_rest() {
+        let mut storage = ArrayStorage::<u8, 4>::new();
+
+        push_then_clear_then_push(&mut storage, 0..3, 10..14);
+        assert_eq!(*storage, [10, 11, 12, 13]);
+        assert_eq!(storage.overflow(), 0);
+
+        push_then_clear_then_push(&mut storage, 0..9, 20..27);
+        assert_eq!(*storage, [20, 21, 22, 23]);
+        assert_eq!(storage.overflow(), 3);
+
+        storage.sort_by_key(|&a| core::cmp::Reverse(a));
+        assert_eq!(*storage, [23, 22, 21, 20]);
+
+        ClearableStorage::<u8>::clear(&mut storage);
+        assert!(storage.is_empty());
+        assert_eq!(storage.overflow(), 0);
+    }
+
+    #[test]
+    fn counting_storage_counts_since_the_last_clear() {
+        let mut storage = CountingStorage::default();
+
+        push_then_clear_then_push(&mut storage, 0..9, 10..13);
+
+        assert_eq!(storage.count, 3);
+    }
+}
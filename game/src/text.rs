@@ -0,0 +1,666 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use crate::{
+    draw::{Colour, DrawLength, DrawXY, DrawWH, LongStrBuf, STR_BUF_CAPACITY},
+    storage::ArrayVec,
+};
+
+pub type FontSize = DrawLength;
+
+/// We treat every font as if it were monospaced, and platform layers are
+/// expected to draw each glyph at the position we give them. That way, text
+/// ends up in the same place on every backend, even if the glyphs themselves
+/// look a bit different.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontMetrics {
+    /// The horizontal distance between the left edges of successive glyphs, as
+    /// a multiple of the font size.
+    pub advance_per_size: DrawLength,
+    /// The vertical distance between the tops of successive lines, as a
+    /// multiple of the font size.
+    pub line_height_per_size: DrawLength,
+}
+
+impl FontMetrics {
+    pub fn advance(&self, size: FontSize) -> DrawLength {
+        size * self.advance_per_size
+    }
+
+    pub fn line_height(&self, size: FontSize) -> DrawLength {
+        size * self.line_height_per_size
+    }
+
+    /// How many glyphs fit on a line `w` wide. Always at least one, so that
+    /// wrapping always makes progress.
+    pub fn chars_per_line(&self, size: FontSize, w: DrawLength) -> usize {
+        let count = crate::float::floor(w / self.advance(size));
+
+        if count >= 1. {
+            count as usize
+        } else {
+            // NaN ends up here too.
+            1
+        }
+    }
+}
+
+/// Arrived at by comparing against raylib's default font, which is the only
+/// font any platform layer uses as of this writing.
+pub const DEFAULT_METRICS: FontMetrics = FontMetrics {
+    advance_per_size: 0.6,
+    line_height_per_size: 1.25,
+};
+
+impl Default for FontMetrics {
+    fn default() -> Self {
+        DEFAULT_METRICS
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LayoutSpec {
+    /// The top left corner of the box the text should lie inside.
+    pub xy: DrawXY,
+    /// The size of the box the text should lie inside.
+    pub wh: DrawWH,
+    /// The size we'd like the text to be, if it fits.
+    pub max_size: FontSize,
+    /// We shrink text that doesn't fit, but never below this size.
+    pub min_size: FontSize,
+    pub h_align: HAlign,
+    pub v_align: VAlign,
+    pub metrics: FontMetrics,
+    /// The colour of any text not inside a colour markup tag.
+    pub colour: Colour,
+    /// Whether to interpret the text as markup. See `parse_markup`.
+    pub markup: bool,
+}
+
+/// A run of glyphs on a single line, all the same size and colour, with each
+/// one `Layout::advance` apart. Runs are at most `STR_BUF_CAPACITY` bytes
+/// long, so each fits in a `StrBuf`. Longer stretches of text are split into
+/// several runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphRun<'text> {
+    pub text: &'text str,
+    /// The top left corner of the first glyph.
+    pub xy: DrawXY,
+    pub colour: Colour,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+    pub size: FontSize,
+    pub advance: DrawLength,
+    /// The size of the area actually covered by the runs.
+    pub wh: DrawWH,
+    /// If this is false then the text was laid out at the spec's `min_size`,
+    /// and some of it lies outside the spec's box.
+    pub fits: bool,
+}
+
+/// The text from this change's index onward should be drawn in this colour.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColourChange {
+    /// A byte index into the plain text.
+    pub index: usize,
+    pub colour: Colour,
+}
+
+/// More colour changes than this in one piece of text are ignored.
+pub const MAX_COLOUR_CHANGES: usize = 64;
+
+/// Tags nested deeper than this are left in the text as is.
+pub const MAX_TAG_DEPTH: usize = 8;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Marked {
+    /// The text with all the markup removed.
+    pub text: LongStrBuf,
+    /// Sorted by index.
+    pub changes: ArrayVec<ColourChange, MAX_COLOUR_CHANGES>,
+}
+
+/// Parses our minimal markup language. `{red}` switches to the colour with that
+/// name, (see `Colour::NAMED`,) until the matching `{/}`, which switches back to
+/// whatever colour was in use before. Tags can be nested. `{{` produces a
+/// literal `{`. Anything else, including unknown colour names and unmatched
+/// `{/}`s, is left in the text as is.
+pub fn parse_markup(markup: &str, base: Colour) -> Marked {
+    let mut output = Marked::default();
+    let mut stack = ArrayVec::<Colour, MAX_TAG_DEPTH>::new();
+    // There's always room for the first one.
+    let _ = stack.push(base);
+    // How many tags past the limit are open, so their `{/}`s are left in the
+    // text too, instead of closing tags that are on the stack.
+    let mut too_deep = 0;
+
+    let mut rest = markup;
+    while let Some(open) = rest.find('{') {
+        output.text.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        if let Some(after) = rest.strip_prefix("{{") {
+            output.text.push('{');
+            rest = after;
+            continue
+        }
+
+        let tag = rest.find('}').map(|close| (close, &rest[1..close]));
+
+        let tag_close = match tag {
+            Some((_, "/")) if too_deep > 0 => {
+                too_deep -= 1;
+                None
+            },
+            Some((close, "/")) if stack.len() > 1 => {
+                stack.pop();
+                Some(close)
+            },
+            Some((close, name)) => Colour::from_name(name).and_then(|colour| {
+                if stack.push(colour).is_ok() {
+                    Some(close)
+                } else {
+                    too_deep += 1;
+                    None
+                }
+            }),
+            None => None,
+        };
+
+        match tag_close {
+            Some(close) => {
+                let colour = *stack.last().unwrap_or(&base);
+                match output.changes.last_mut() {
+                    Some(change) if change.index == output.text.len() => {
+                        change.colour = colour;
+                    },
+                    _ => {
+                        // Past the limit, the text just stays in whatever
+                        // colour it was.
+                        let _ = output.changes.push(ColourChange {
+                            index: output.text.len(),
+                            colour,
+                        });
+                    }
+                }
+                rest = &rest[close + 1..];
+            },
+            None => {
+                output.text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.text.push_str(rest);
+
+    output
+}
+
+/// Calls `on_line` with each line of `text`, after wrapping it so that no line
+/// is more than `max_chars` chars long. Lines are broken at whitespace where
+/// possible, and explicit newlines are preserved. Words longer than a line are
+/// broken wherever they need to be.
+pub fn wrap<'text>(text: &'text str, max_chars: usize, mut on_line: impl FnMut(&'text str)) {
+    let max_chars = core::cmp::max(max_chars, 1);
+
+    for paragraph in text.split('\n') {
+        // Byte indexes into `paragraph`.
+        let mut line: Option<(usize, usize)> = None;
+
+        for word in paragraph.split_whitespace() {
+            let mut start = word.as_ptr() as usize - paragraph.as_ptr() as usize;
+            let end = start + word.len();
+
+            if let Some((line_start, line_end)) = line {
+                if paragraph[line_start..end].chars().count() <= max_chars {
+                    line = Some((line_start, end));
+                    continue
+                }
+
+                on_line(&paragraph[line_start..line_end]);
+            }
+
+            loop {
+                let remaining = &paragraph[start..end];
+                match remaining.char_indices().nth(max_chars) {
+                    Some((i, _)) => {
+                        on_line(&remaining[..i]);
+                        start += i;
+                    }
+                    None => {
+                        line = Some((start, end));
+                        break
+                    }
+                }
+            }
+        }
+
+        // We always pass slices of `text` so callers can find where the lines
+        // are in `text`.
+        on_line(line.map(|(s, e)| &paragraph[s..e]).unwrap_or(&paragraph[..0]));
+    }
+}
+
+/// Returns the size of the area `text` would cover at the given size, if
+/// wrapped to fit inside `max_w`.
+pub fn measure(
+    text: &str,
+    size: FontSize,
+    max_w: DrawLength,
+    metrics: &FontMetrics,
+) -> DrawWH {
+    let mut line_count = 0;
+    let mut longest_line = 0;
+    wrap(text, metrics.chars_per_line(size, max_w), |line| {
+        line_count += 1;
+        longest_line = core::cmp::max(longest_line, line.chars().count());
+    });
+
+    DrawWH {
+        w: longest_line as DrawLength * metrics.advance(size),
+        h: line_count as DrawLength * metrics.line_height(size),
+    }
+}
+
+/// How close to the largest size that fits the search for it gets.
+const SIZE_PRECISION: FontSize = 0.25;
+
+/// Enough to get within `SIZE_PRECISION` of the size for any sizes we use,
+/// and bounded so odd specs, like infinite sizes, can't loop forever.
+const MAX_SIZE_SEARCH_STEPS: u32 = 24;
+
+/// Searches between the spec's `min_size` and `max_size` for the largest size
+/// the text fits at. Smaller text fits more chars on each line, so it never
+/// takes more lines, and a binary search works.
+fn fitting_size(text: &str, spec: &LayoutSpec) -> (FontSize, DrawWH, bool) {
+    let measure_at = |size| {
+        let wh = measure(text, size, spec.wh.w, &spec.metrics);
+        (wh, wh.w <= spec.wh.w && wh.h <= spec.wh.h)
+    };
+
+    let (max_wh, max_fits) = measure_at(spec.max_size);
+    // Written this way so NaNs end up here too.
+    if max_fits || spec.max_size.partial_cmp(&spec.min_size) != Some(core::cmp::Ordering::Greater) {
+        return (spec.max_size, max_wh, max_fits)
+    }
+
+    let (min_wh, min_fits) = measure_at(spec.min_size);
+    if !min_fits {
+        return (spec.min_size, min_wh, false)
+    }
+
+    // `fits` always fits, and `too_big` never does.
+    let (mut fits, mut fits_wh) = (spec.min_size, min_wh);
+    let mut too_big = spec.max_size;
+    for _ in 0..MAX_SIZE_SEARCH_STEPS {
+        if too_big - fits <= SIZE_PRECISION {
+            break
+        }
+
+        let middle = fits + (too_big - fits) / 2.;
+        match measure_at(middle) {
+            (wh, true) => (fits, fits_wh) = (middle, wh),
+            (_, false) => too_big = middle,
+        }
+    }
+
+    (fits, fits_wh, true)
+}
+
+/// Splits `text` into pieces of at most `STR_BUF_CAPACITY` bytes, at char
+/// boundaries.
+fn str_buf_sized_pieces(mut text: &str, mut on_piece: impl FnMut(&str)) {
+    while !text.is_empty() {
+        let mut end = core::cmp::min(text.len(), STR_BUF_CAPACITY);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        on_piece(&text[..end]);
+        text = &text[end..];
+    }
+}
+
+/// Calls `on_run` with each run of glyphs, from the top left to the bottom
+/// right, along with what all the runs have in common, which is also returned.
+pub fn layout(
+    text: &str,
+    spec: &LayoutSpec,
+    on_run: impl FnMut(&Layout, GlyphRun),
+) -> Layout {
+    if spec.markup {
+        let marked = parse_markup(text, spec.colour);
+        layout_plain(&marked.text, &marked.changes, spec, on_run)
+    } else {
+        layout_plain(text, &[], spec, on_run)
+    }
+}
+
+fn layout_plain(
+    text: &str,
+    changes: &[ColourChange],
+    spec: &LayoutSpec,
+    mut on_run: impl FnMut(&Layout, GlyphRun),
+) -> Layout {
+    let metrics = &spec.metrics;
+
+    let (size, wh, fits) = fitting_size(text, spec);
+
+    let advance = metrics.advance(size);
+    let layout = Layout {
+        size,
+        advance,
+        wh,
+        fits,
+    };
+    let line_height = metrics.line_height(size);
+
+    let mut y = spec.xy.y + match spec.v_align {
+        VAlign::Top => 0.,
+        VAlign::Middle => (spec.wh.h - wh.h) / 2.,
+        VAlign::Bottom => spec.wh.h - wh.h,
+    };
+
+    let colour_at = |index: usize| {
+        changes.iter()
+            .take_while(|change| change.index <= index)
+            .last()
+            .map(|change| change.colour)
+            .unwrap_or(spec.colour)
+    };
+
+    wrap(text, metrics.chars_per_line(size, spec.wh.w), |line| {
+        let line_w = line.chars().count() as DrawLength * advance;
+
+        let mut x = spec.xy.x + match spec.h_align {
+            HAlign::Left => 0.,
+            HAlign::Center => (spec.wh.w - line_w) / 2.,
+            HAlign::Right => spec.wh.w - line_w,
+        };
+
+        let line_start = line.as_ptr() as usize - text.as_ptr() as usize;
+        let line_end = line_start + line.len();
+
+        let mut run_start = line_start;
+        let split_points = changes.iter()
+            .map(|change| change.index)
+            .filter(|&i| i > line_start && i < line_end)
+            .chain(core::iter::once(line_end));
+
+        for run_end in split_points {
+            let colour = colour_at(run_start);
+
+            str_buf_sized_pieces(&text[run_start..run_end], |run_text| {
+                on_run(&layout, GlyphRun {
+                    text: run_text,
+                    xy: DrawXY { x, y },
+                    colour,
+                });
+
+                x += run_text.chars().count() as DrawLength * advance;
+            });
+
+            run_start = run_end;
+        }
+
+        y += line_height;
+    });
+
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes the arithmetic in the expected values easy to follow.
+    const METRICS: FontMetrics = FontMetrics {
+        advance_per_size: 0.5,
+        line_height_per_size: 1.,
+    };
+
+    fn wrapped(text: &str, max_chars: usize) -> [Option<&str>; 8] {
+        let mut lines = [None; 8];
+        let mut count = 0;
+        wrap(text, max_chars, |line| {
+            lines[count] = Some(line);
+            count += 1;
+        });
+        lines
+    }
+
+    fn spec(w: DrawLength, h: DrawLength) -> LayoutSpec {
+        LayoutSpec {
+            wh: DrawWH { w, h },
+            max_size: 10.,
+            min_size: 10.,
+            metrics: METRICS,
+            ..<_>::default()
+        }
+    }
+
+    fn change(index: usize, colour: Colour) -> ColourChange {
+        ColourChange { index, colour }
+    }
+
+    #[test]
+    fn markup_tags_nest_and_switch_back_when_closed() {
+        let marked = parse_markup("a{red}b{blue}c{/}d{/}e", Colour::WHITE);
+
+        assert_eq!(marked.text, "abcde");
+        assert_eq!(
+            marked.changes[..],
+            [
+                change(1, Colour::RED),
+                change(2, Colour::BLUE),
+                change(3, Colour::RED),
+                change(4, Colour::WHITE),
+            ]
+        );
+    }
+
+    #[test]
+    fn adjacent_markup_tags_make_one_change() {
+        let marked = parse_markup("{red}{/}{green}a", Colour::WHITE);
+
+        assert_eq!(marked.text, "a");
+        assert_eq!(marked.changes[..], [change(0, Colour::GREEN)]);
+    }
+
+    #[test]
+    fn double_braces_are_literal_braces() {
+        let marked = parse_markup("{{red}} {{{red}x{/}", Colour::WHITE);
+
+        assert_eq!(marked.text, "{red}} {x");
+        assert_eq!(
+            marked.changes[..],
+            [change(8, Colour::RED), change(9, Colour::WHITE)]
+        );
+    }
+
+    #[test]
+    fn unknown_unclosed_and_unmatched_markup_is_left_as_is() {
+        for text in ["{purple}a{/}", "a{/}", "{red", "a{", "}"] {
+            let marked = parse_markup(text, Colour::WHITE);
+
+            assert_eq!(marked.text, text);
+            assert!(marked.changes.is_empty(), "{:?}", text);
+        }
+
+        // The tag was never closed, so the colour carries on to the end.
+        let marked = parse_markup("{red}a", Colour::WHITE);
+        assert_eq!(marked.text, "a");
+        assert_eq!(marked.changes[..], [change(0, Colour::RED)]);
+    }
+
+    #[test]
+    fn markup_nested_too_deep_is_left_as_is() {
+        let mut markup = LongStrBuf::new();
+        // The base colour takes up one place.
+        for _ in 1..MAX_TAG_DEPTH {
+            markup.push_str("{red}");
+        }
+        markup.push_str("{blue}a{/}b");
+        for _ in 1..MAX_TAG_DEPTH {
+            markup.push_str("{/}");
+        }
+        markup.push_str("c");
+
+        let marked = parse_markup(&markup, Colour::WHITE);
+
+        assert_eq!(marked.text, "{blue}a{/}bc");
+        assert_eq!(
+            marked.changes[..],
+            [change(0, Colour::RED), change(11, Colour::WHITE)],
+            "the `{{/}}` for the tag that was too deep shouldn't close another"
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_at_whitespace_and_keeps_newlines() {
+        assert_eq!(
+            wrapped("the quick brown fox", 10)[..4],
+            [Some("the quick"), Some("brown fox"), None, None]
+        );
+        assert_eq!(
+            wrapped("one\n\ntwo  three", 20)[..4],
+            [Some("one"), Some(""), Some("two  three"), None]
+        );
+        assert_eq!(wrapped("", 5)[..2], [Some(""), None]);
+    }
+
+    #[test]
+    fn wrap_breaks_long_words_wherever_they_need_to_be() {
+        assert_eq!(
+            wrapped("a abcdefgh", 3)[..5],
+            [Some("a"), Some("abc"), Some("def"), Some("gh"), None]
+        );
+        // Zero is treated as one, so wrapping always makes progress.
+        assert_eq!(wrapped("ab", 0)[..3], [Some("a"), Some("b"), None]);
+    }
+
+    #[test]
+    fn wrap_counts_chars_and_cuts_at_char_boundaries() {
+        // Each of these is more than one byte long.
+        assert_eq!(
+            wrapped("éééé ñ", 3)[..4],
+            [Some("ééé"), Some("é ñ"), None, None]
+        );
+        assert_eq!(
+            wrapped("日本語です", 2)[..4],
+            [Some("日本"), Some("語で"), Some("す"), None]
+        );
+    }
+
+    #[test]
+    fn measure_uses_the_longest_line_and_the_line_count() {
+        assert_eq!(
+            measure("ab\nabcd\nabc", 10., 100., &METRICS),
+            DrawWH { w: 20., h: 30. }
+        );
+    }
+
+    fn run_xys(text: &str, spec: &LayoutSpec) -> [Option<DrawXY>; 4] {
+        let mut xys = [None; 4];
+        let mut count = 0;
+        layout(text, spec, |_, run| {
+            xys[count] = Some(run.xy);
+            count += 1;
+        });
+        xys
+    }
+
+    #[test]
+    fn lines_are_aligned_inside_the_box() {
+        let xy = |x, y| Some(DrawXY { x, y });
+        let text = "abcd\nab";
+        let base = spec(100., 50.);
+
+        assert_eq!(run_xys(text, &base)[..3], [xy(0., 0.), xy(0., 10.), None]);
+
+        let centered = LayoutSpec { h_align: HAlign::Center, v_align: VAlign::Middle, ..base };
+        assert_eq!(run_xys(text, &centered)[..2], [xy(40., 15.), xy(45., 25.)]);
+
+        let right = LayoutSpec {
+            xy: DrawXY { x: 5., y: 7. },
+            h_align: HAlign::Right,
+            v_align: VAlign::Bottom,
+            ..base
+        };
+        assert_eq!(run_xys(text, &right)[..2], [xy(85., 37.), xy(95., 47.)]);
+    }
+
+    #[test]
+    fn text_shrinks_to_nearly_the_largest_size_that_fits() {
+        let spec = LayoutSpec { max_size: 40., min_size: 4., ..spec(100., 10.) };
+
+        let layout = layout("abcdefghij", &spec, |_, _| {});
+
+        // Ten chars fit in 100 wide, on one line 10 high, at size 20, but
+        // the height only allows size 10.
+        assert!(layout.fits);
+        assert!(layout.size <= 10. && layout.size > 10. - SIZE_PRECISION, "{}", layout.size);
+        assert_eq!(layout.advance, layout.size / 2.);
+    }
+
+    #[test]
+    fn text_that_never_fits_is_laid_out_at_the_min_size() {
+        let spec = LayoutSpec { max_size: 40., min_size: 8., ..spec(10., 5.) };
+
+        let layout = layout("abc", &spec, |_, _| {});
+
+        assert!(!layout.fits);
+        assert_eq!(layout.size, 8.);
+    }
+
+    #[test]
+    fn odd_sizes_do_not_hang() {
+        for (max_size, min_size) in [
+            (FontSize::NAN, 1.),
+            (10., FontSize::NAN),
+            (FontSize::INFINITY, 1.),
+            (10., FontSize::NEG_INFINITY),
+            (5., 10.),
+        ] {
+            let spec = LayoutSpec { max_size, min_size, ..spec(10., 10.) };
+            layout("some text", &spec, |_, _| {});
+        }
+    }
+
+    #[test]
+    fn long_lines_are_split_into_runs_that_fit_in_a_str_buf() {
+        let mut text = LongStrBuf::new();
+        for _ in 0..STR_BUF_CAPACITY {
+            // Two bytes each, so the split has to avoid the middle of one.
+            text.push('é');
+        }
+        let mut total = 0;
+        let mut next_x = 0.;
+        layout(&text, &spec(1_000_000., 10.), |layout, run| {
+            assert!(run.text.len() <= STR_BUF_CAPACITY, "{}", run.text.len());
+            assert_eq!(run.xy.x, next_x);
+            next_x += run.text.chars().count() as DrawLength * layout.advance;
+            total += run.text.chars().count();
+        });
+
+        assert_eq!(total, STR_BUF_CAPACITY);
+    }
+}
@@ -0,0 +1,43 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Where screenshots and recordings go. Each gets a name with the time it was
+//! started in it, so nothing gets overwritten.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Relative to the working directory, so captures end up somewhere easy to
+/// find when running with `cargo run`.
+const DIR: &str = "captures";
+
+/// Milliseconds rather than seconds, since taking more than one screenshot
+/// per second is easy to do.
+fn unix_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+}
+
+pub fn screenshot_path() -> io::Result<PathBuf> {
+    fs::create_dir_all(DIR)?;
+
+    Ok(Path::new(DIR).join(format!("screenshot-{}.png", unix_millis())))
+}
+
+/// Makes a new directory to hold the frames of a recording.
+pub fn recording_dir() -> io::Result<PathBuf> {
+    let dir = Path::new(DIR).join(format!("recording-{}", unix_millis()));
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+/// Numbered so that tools like `ffmpeg -i frame-%05d.png` take them in order.
+pub fn frame_path(recording_dir: &Path, index: u32) -> PathBuf {
+    recording_dir.join(format!("frame-{:05}.png", index))
+}
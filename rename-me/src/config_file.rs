@@ -0,0 +1,109 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! The files the game keeps in the per-user config directory, like the
+//! settings and high scores.
+//!
+//! Loading never fails: if a file is missing or can't be read we start from
+//! the defaults. A corrupt file is moved aside first, so the next save doesn't
+//! lose what was in it. Saving writes to a temporary file then renames it over
+//! the real one, so a crash partway through can't leave a half-written file
+//! behind.
+
+use std::{
+    env,
+    ffi::OsString,
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The per-user config directory for this game, following each OS's
+/// conventions. Returns `None` if we can't figure out where that is.
+pub fn dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            })
+    };
+
+    base.map(|base| base.join(game::TITLE))
+}
+
+/// Where the file at `relative_path` inside `dir()` is.
+pub fn path(relative_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    dir()
+        .map(|dir| dir.join(relative_path))
+        .ok_or_else(|| io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not find a config directory",
+        ))
+}
+
+/// `settings.toml` becomes `settings.toml.corrupt`, and so on, so the new
+/// name still says what kind of file it was.
+fn with_added_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(extension);
+
+    path.with_file_name(name)
+}
+
+pub fn load_or_default<A: Default, E: Display>(
+    relative_path: impl AsRef<Path>,
+    parse: impl FnOnce(&str) -> Result<A, E>,
+) -> A {
+    let Ok(path) = path(relative_path) else {
+        return A::default()
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return A::default()
+        },
+        Err(err) => {
+            eprintln!("Could not read {}: {}", path.display(), err);
+            return A::default()
+        },
+    };
+
+    match parse(&text) {
+        Ok(a) => a,
+        Err(err) => {
+            let corrupt_path = with_added_extension(&path, "corrupt");
+            eprintln!(
+                "{} is corrupt ({}), so moving it to {} and using the defaults.",
+                path.display(),
+                err,
+                corrupt_path.display(),
+            );
+            if let Err(err) = fs::rename(&path, &corrupt_path) {
+                eprintln!("Could not move {}: {}", path.display(), err);
+            }
+
+            A::default()
+        },
+    }
+}
+
+pub fn save_atomically(relative_path: impl AsRef<Path>, text: &str) -> io::Result<()> {
+    let path = path(relative_path)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temp_path = with_added_extension(&path, "tmp");
+    fs::write(&temp_path, text)?;
+    fs::rename(&temp_path, path)
+}
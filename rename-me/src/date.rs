@@ -0,0 +1,72 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! The player's local date, so the daily challenge changes at midnight for
+//! them. Where the local time zone can't be found, the date in UTC is used
+//! instead, which is at most a day off.
+
+use std::time::SystemTime;
+
+const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+/// Converts days since the Unix epoch to a date, using the algorithm from
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn date_from_days(days: i64) -> game::seed::Date {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    game::seed::Date {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+    }
+}
+
+#[cfg(any(unix, windows))]
+fn local_date(seconds: i64) -> Option<game::seed::Date> {
+    let time = libc::time_t::try_from(seconds).ok()?;
+
+    // SAFETY: `tm` only holds integers, and on some platforms a pointer, all
+    // of which may be zero.
+    let mut tm: libc::tm = unsafe { core::mem::zeroed() };
+
+    #[cfg(unix)]
+    // SAFETY: Both pointers are valid for the duration of the call.
+    let succeeded = unsafe { !libc::localtime_r(&time, &mut tm).is_null() };
+
+    #[cfg(windows)]
+    // SAFETY: As above.
+    let succeeded = unsafe { libc::localtime_s(&mut tm, &time) == 0 };
+
+    if !succeeded {
+        return None
+    }
+
+    Some(game::seed::Date {
+        year: u16::try_from(tm.tm_year + 1900).ok()?,
+        month: u8::try_from(tm.tm_mon + 1).ok()?,
+        day: u8::try_from(tm.tm_mday).ok()?,
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn local_date(_: i64) -> Option<game::seed::Date> {
+    None
+}
+
+pub fn today() -> game::seed::Date {
+    let seconds = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    };
+
+    local_date(seconds)
+        .unwrap_or_else(|| date_from_days(seconds.div_euclid(SECONDS_PER_DAY)))
+}
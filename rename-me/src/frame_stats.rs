@@ -0,0 +1,278 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use std::time::{Duration, Instant};
+
+pub struct TimeSpan {
+    pub start: Instant,
+    pub end: Instant,
+}
+
+impl Default for TimeSpan {
+    fn default() -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            end: start,
+        }
+    }
+}
+
+impl TimeSpan {
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_duration_since(self.start)
+    }
+}
+
+pub fn ms(duration: Duration) -> f32 {
+    duration.as_micros() as f32 / 1000.0
+}
+
+pub const SPAN_COUNT: usize = 4;
+
+pub const SPAN_NAMES: [&str; SPAN_COUNT] = [
+    "loop",
+    "input",
+    "update",
+    "render",
+];
+
+#[derive(Default)]
+pub struct FrameStats {
+    pub loop_body: TimeSpan,
+    pub input_gather: TimeSpan,
+    pub update: TimeSpan,
+    pub render: TimeSpan,
+}
+
+/// The durations of the spans of a single frame, in the same order as
+/// `SPAN_NAMES`.
+pub type FrameDurations = [Duration; SPAN_COUNT];
+
+impl FrameStats {
+    pub fn durations(&self) -> FrameDurations {
+        [
+            self.loop_body.duration(),
+            self.input_gather.duration(),
+            self.update.duration(),
+            self.render.duration(),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Summary {
+    pub min: Duration,
+    pub avg: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "min {: >6.3} avg {: >6.3} p95 {: >6.3} p99 {: >6.3} ms",
+            ms(self.min),
+            ms(self.avg),
+            ms(self.p95),
+            ms(self.p99),
+        )
+    }
+}
+
+/// Ten seconds worth at 60 FPS.
+pub const HISTORY_LENGTH: usize = 600;
+
+/// A ring buffer of the durations of the last `HISTORY_LENGTH` frames.
+pub struct History {
+    frames: Box<[FrameDurations; HISTORY_LENGTH]>,
+    /// The index the next frame will be written to.
+    next: usize,
+    len: usize,
+    /// Kept around so we don't need to allocate every time we summarize.
+    scratch: Vec<Duration>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            frames: Box::new([FrameDurations::default(); HISTORY_LENGTH]),
+            next: 0,
+            len: 0,
+            scratch: Vec::with_capacity(HISTORY_LENGTH),
+        }
+    }
+}
+
+impl History {
+    pub fn push(&mut self, stats: &FrameStats) {
+        self.frames[self.next] = stats.durations();
+        self.next = (self.next + 1) % HISTORY_LENGTH;
+        self.len = core::cmp::min(self.len + 1, HISTORY_LENGTH);
+    }
+
+    /// Iterates from the oldest frame to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = &FrameDurations> {
+        let start = (self.next + HISTORY_LENGTH - self.len) % HISTORY_LENGTH;
+
+        (0..self.len).map(move |i| &self.frames[(start + i) % HISTORY_LENGTH])
+    }
+
+    pub fn latest(&self) -> Option<&FrameDurations> {
+        self.iter().last()
+    }
+
+    /// `span_index` is an index into `SPAN_NAMES`.
+    pub fn summary(&mut self, span_index: usize) -> Summary {
+        let mut scratch = core::mem::take(&mut self.scratch);
+        scratch.clear();
+        scratch.extend(self.iter().map(|frame| frame[span_index]));
+
+        let summary = if scratch.is_empty() {
+            Summary::default()
+        } else {
+            scratch.sort_unstable();
+
+            // Nearest rank percentiles.
+            let percentile = |p: usize| {
+                let rank = (p * scratch.len()).div_ceil(100);
+                scratch[rank.saturating_sub(1)]
+            };
+
+            Summary {
+                min: scratch[0],
+                avg: scratch.iter().sum::<Duration>() / scratch.len() as u32,
+                p95: percentile(95),
+                p99: percentile(99),
+            }
+        };
+
+        self.scratch = scratch;
+
+        summary
+    }
+
+    pub fn write_csv(&self, mut w: impl std::io::Write) -> std::io::Result<()> {
+        write!(w, "frame")?;
+        for name in SPAN_NAMES {
+            write!(w, ",{}_ms", name)?;
+        }
+        writeln!(w)?;
+
+        for (i, frame) in self.iter().enumerate() {
+            write!(w, "{}", i)?;
+            for duration in frame {
+                write!(w, ",{:.3}", ms(*duration))?;
+            }
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(duration: Duration) -> TimeSpan {
+        let start = Instant::now();
+        TimeSpan {
+            start,
+            end: start + duration,
+        }
+    }
+
+    /// A frame where the loop took `loop_ms` milliseconds, and the other spans
+    /// took 1, 2 and 3 milliseconds.
+    fn frame(loop_ms: u64) -> FrameStats {
+        FrameStats {
+            loop_body: span(Duration::from_millis(loop_ms)),
+            input_gather: span(Duration::from_millis(1)),
+            update: span(Duration::from_millis(2)),
+            render: span(Duration::from_millis(3)),
+        }
+    }
+
+    fn loop_ms(history: &History) -> Vec<u128> {
+        history.iter().map(|frame| frame[0].as_millis()).collect()
+    }
+
+    fn csv(history: &History) -> String {
+        let mut output = Vec::new();
+        history.write_csv(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn an_empty_history_has_an_empty_summary() {
+        let mut history = History::default();
+
+        assert!(history.latest().is_none());
+        assert_eq!(history.iter().count(), 0);
+        assert_eq!(history.summary(0).avg, Duration::ZERO);
+        assert_eq!(history.summary(0).p99, Duration::ZERO);
+        assert_eq!(csv(&history), "frame,loop_ms,input_ms,update_ms,render_ms\n");
+    }
+
+    #[test]
+    fn a_single_sample_is_every_statistic() {
+        let mut history = History::default();
+        history.push(&frame(7));
+
+        let summary = history.summary(0);
+        let seven = Duration::from_millis(7);
+        assert_eq!(
+            (summary.min, summary.avg, summary.p95, summary.p99),
+            (seven, seven, seven, seven)
+        );
+        assert_eq!(history.summary(3).p95, Duration::from_millis(3));
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let mut history = History::default();
+        // Pushed out of order, to check they get sorted.
+        for ms in (1..=100).rev() {
+            history.push(&frame(ms));
+        }
+
+        let summary = history.summary(0);
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.avg, Duration::from_micros(50_500));
+        assert_eq!(summary.p95, Duration::from_millis(95));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+    }
+
+    #[test]
+    fn the_oldest_frames_are_overwritten_once_the_history_is_full() {
+        let mut history = History::default();
+        let extra = 10;
+        for ms in 0..(HISTORY_LENGTH + extra) as u64 {
+            history.push(&frame(ms));
+        }
+
+        let expected: Vec<u128> = (extra as u128..(HISTORY_LENGTH + extra) as u128).collect();
+        assert_eq!(loop_ms(&history), expected);
+        assert_eq!(
+            history.latest().map(|frame| frame[0]),
+            Some(Duration::from_millis((HISTORY_LENGTH + extra - 1) as u64))
+        );
+        assert_eq!(history.summary(0).min, Duration::from_millis(extra as u64));
+    }
+
+    #[test]
+    fn csv_has_a_row_per_frame_from_oldest_to_newest() {
+        let mut history = History::default();
+        history.push(&frame(16));
+        history.push(&frame(17));
+
+        assert_eq!(
+            csv(&history),
+            "frame,loop_ms,input_ms,update_ms,render_ms\n\
+             0,16.000,1.000,2.000,3.000\n\
+             1,17.000,1.000,2.000,3.000\n"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Plays the first board of the seed without a window, with scripted input,
+//! for a set number of frames, as fast as it can. Useful for soak testing and
+//! for producing checksum logs to compare, on machines without a display.
+
+use std::time::Instant;
+
+use crate::platform::{Frame, Input, Platform};
+
+/// Matches the default target FPS, so a run behaves like one with a window.
+const FRAME_TIME: game::DeltaTimeInSeconds = 1. / 60.;
+
+/// A common window size.
+const DRAW_WH: game::DrawWH = game::DrawWH { w: 1280., h: 720. };
+
+/// Directions to hold, and for how many frames, over and over. The same for
+/// every run, so runs with the same seed play the same way, but uneven enough
+/// to get around most boards.
+const SCRIPT: [(game::InputFlags, u32); 8] = [
+    (game::INPUT_RIGHT_DOWN, 40),
+    (game::INPUT_DOWN_DOWN, 25),
+    (game::INPUT_LEFT_DOWN, 60),
+    (0, 10),
+    (game::INPUT_UP_DOWN, 35),
+    (game::INPUT_DOWN_DOWN | game::INPUT_RIGHT_DOWN, 50),
+    (game::INPUT_UP_DOWN | game::INPUT_LEFT_DOWN, 20),
+    (game::INPUT_DOWN_DOWN | game::INPUT_LEFT_DOWN, 45),
+];
+
+fn scripted_flags(frame: u32) -> game::InputFlags {
+    let length: u32 = SCRIPT.iter().map(|&(_, frames)| frames).sum();
+
+    let mut remaining = frame % length;
+    for (flags, frames) in SCRIPT {
+        if remaining < frames {
+            return flags
+        }
+        remaining -= frames;
+    }
+
+    0
+}
+
+pub struct HeadlessPlatform {
+    frames_left: u32,
+    frame: u32,
+}
+
+impl HeadlessPlatform {
+    pub fn new(frame_count: u32) -> Self {
+        Self {
+            frames_left: frame_count,
+            frame: 0,
+        }
+    }
+}
+
+impl Platform for HeadlessPlatform {
+    fn has_player(&self) -> bool {
+        false
+    }
+
+    fn should_close(&self) -> bool {
+        self.frames_left == 0
+    }
+
+    fn frame_time(&self) -> game::DeltaTimeInSeconds {
+        FRAME_TIME
+    }
+
+    fn poll_input(&mut self, _: &mut game::State) -> Input {
+        let mut input = Input::default();
+        input.device_flags[0] = scripted_flags(self.frame);
+        self.frame = self.frame.wrapping_add(1);
+
+        input
+    }
+
+    fn draw_wh(&mut self) -> game::DrawWH {
+        DRAW_WH
+    }
+
+    fn apply_settings(&mut self, _: &game::Settings) {}
+
+    fn set_clipboard_text(&mut self, _: &str) -> Result<(), String> {
+        Err("There is no clipboard when headless".to_string())
+    }
+
+    fn present(&mut self, _: Frame) -> Instant {
+        self.frames_left = self.frames_left.saturating_sub(1);
+
+        Instant::now()
+    }
+}
@@ -0,0 +1,16 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use std::io;
+
+use crate::config_file;
+
+const FILE_NAME: &str = "high-scores.txt";
+
+pub fn load() -> game::score::HighScores {
+    config_file::load_or_default(FILE_NAME, game::score::parse)
+}
+
+pub fn save(text: &str) -> io::Result<()> {
+    config_file::save_atomically(FILE_NAME, text)
+}
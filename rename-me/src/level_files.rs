@@ -0,0 +1,23 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use std::{
+    fs,
+    io,
+    path::PathBuf,
+};
+
+use crate::config_file;
+
+/// Levels live next to the settings file, one file per slot.
+fn relative_path(slot: game::LevelSlot) -> PathBuf {
+    PathBuf::from("levels").join(format!("slot-{}.level", slot))
+}
+
+pub fn load(slot: game::LevelSlot) -> io::Result<String> {
+    fs::read_to_string(config_file::path(relative_path(slot))?)
+}
+
+pub fn save(slot: game::LevelSlot, text: &str) -> io::Result<()> {
+    config_file::save_atomically(relative_path(slot), text)
+}
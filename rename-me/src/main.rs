@@ -1,21 +1,6 @@
 #![deny(unused)]
 #![deny(bindings_with_variant_name)]
 
-extern crate alloc;
-use alloc::vec::Vec;
-
-struct Storage<A>(Vec<A>);
-
-impl <A> game::ClearableStorage<A> for Storage<A> {
-    fn clear(&mut self) {
-        self.0.clear();
-    }
-
-    fn push(&mut self, a: A) {
-        self.0.push(a);
-    }
-}
-
 const SAMPLING_SHADER: &str = include_str!("../assets/sampling.fs");
 
 const SPRITESHEET_BYTES: &[u8] = include_bytes!("../assets/spritesheet.png");
@@ -43,7 +28,12 @@ fn source_spec(sprite: SpriteKind) -> SourceSpec {
         | NarrowRightEye
         | ClosedEye
         | HalfLidEye => 0.,
-        Arrow(_, Green)| DirEye(_) => 1.
+        Arrow(_, Green)| DirEye(_) => 1.,
+        Floor
+        | Wall
+        | Goal
+        | Ice
+        | Teleporter(_) => 2.,
     };
 
     let sy = match sprite {
@@ -70,6 +60,12 @@ fn source_spec(sprite: SpriteKind) -> SourceSpec {
         NarrowRightEye => 12.,
         NarrowLeftEye => 13.,
         SmallPupilEye => 14.,
+        Floor => 0.,
+        Wall => 1.,
+        Goal => 2.,
+        Ice => 3.,
+        // There are only sprites for this many pairs.
+        Teleporter(id) => 4. + (id % game::TELEPORTER_PAIR_COUNT) as f32,
     };
 
     SourceSpec {
@@ -78,428 +74,154 @@ fn source_spec(sprite: SpriteKind) -> SourceSpec {
     }
 }
 
-const WINDOW_TITLE: &str = "rename-me";
-
-fn main() {
-    raylib_rs_platform::inner_main();
+const WINDOW_TITLE: &str = game::TITLE;
+
+mod frame_stats;
+mod config_file;
+mod settings_file;
+mod level_files;
+mod high_scores_file;
+mod date;
+mod netplay_socket;
+mod captures;
+mod platform;
+mod main_loop;
+mod raylib_rs_platform;
+mod headless_platform;
+
+const USAGE: &str = "\
+Usage: rename-me [--daily | --seed <code>] [--checksum-log <path>] [--headless <frames>]
+                 [--capture-frame-skip <frames>] [<netplay options>]
+
+    --checksum-log <path>   Write a checksum of the game after each update step,
+                            for comparing runs with checksum-diff
+    --headless <frames>     Play level 1 for that many frames, with scripted
+                            input and no window, as fast as possible. The
+                            settings and high score files are not touched
+    --capture-frame-skip <frames>
+                            Frames to skip after each one captured while
+                            recording with F4 (default 1)
+
+Netplay options:
+    --host [<port>]         Wait for another player to join, on port 7727 by default
+    --join <address:port>   Join a player who is hosting
+    --input-delay <frames>  Delay our inputs, to need fewer rollbacks (default 4)
+    --latency <ms>          Hold back packets we send, for testing
+    --loss <percent>        Drop some of the packets we send, for testing";
+
+struct Args {
+    seed: Option<game::Seed>,
+    checksum_log: Option<std::path::PathBuf>,
+    /// How many frames to run for without a window, if we should.
+    headless: Option<u32>,
+    capture_frame_skip: u32,
+    netplay: Option<netplay_socket::Options>,
 }
 
-/// Let's keep all the raylib specific stuff in one module to make it easier to add
-/// any different backends later.
-mod raylib_rs_platform {
-    use super::{
-        Storage,
-        source_spec,
-        SPRITE_PIXELS_PER_TILE_SIDE,
-        SPRITESHEET_BYTES,
-        SAMPLING_SHADER,
-        WINDOW_TITLE
-    };
-    use raylib::prelude::{
-        *,
-        KeyboardKey::*,
-        ffi::LoadImageFromMemory,
-        core::{
-            drawing::{RaylibTextureModeExt, RaylibShaderModeExt},
-            logging
-        }
-    };
-
-    use ::core::{
-        convert::TryInto,
-    };
+fn number_arg<N: core::str::FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> Result<N, String> {
+    let arg = args.next()
+        .ok_or_else(|| format!("{} needs a number after it", flag))?;
+    arg.parse()
+        .map_err(|_| format!("{} needs a number after it, not {:?}", flag, arg))
+}
 
-    fn draw_wh(rl: &RaylibHandle) -> game::DrawWH {
-        game::DrawWH {
-            w: rl.get_screen_width() as game::DrawW,
-            h: rl.get_screen_height() as game::DrawH,
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut args = args.peekable();
+    let mut seed = None;
+    let mut checksum_log = None;
+    let mut headless = None;
+    let mut capture_frame_skip = 1;
+    let mut role = None;
+    let mut input_delay = game::netplay::DEFAULT_INPUT_DELAY;
+    let mut conditions = game::netplay::Conditions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--daily" => {
+                seed = Some(game::seed::daily(date::today()));
+            },
+            "--seed" => {
+                let code = args.next()
+                    .ok_or_else(|| "--seed needs a code after it".to_string())?;
+                seed = Some(
+                    game::seed::parse(&code)
+                        .map_err(|err| format!("Bad seed code {:?}: {}", code, err))?
+                );
+            },
+            "--checksum-log" => {
+                let path = args.next()
+                    .ok_or_else(|| "--checksum-log needs a path after it".to_string())?;
+                checksum_log = Some(path.into());
+            },
+            "--headless" => {
+                headless = Some(number_arg(&mut args, &arg)?);
+            },
+            "--capture-frame-skip" => {
+                capture_frame_skip = number_arg(&mut args, &arg)?;
+            },
+            "--host" => {
+                let port = match args.next_if(|arg| !arg.starts_with('-')) {
+                    Some(port) => port.parse()
+                        .map_err(|_| format!("Bad port {:?}", port))?,
+                    None => netplay_socket::DEFAULT_PORT,
+                };
+                role = Some(netplay_socket::Role::Host { port });
+            },
+            "--join" => {
+                let address = args.next()
+                    .ok_or_else(|| "--join needs an address after it".to_string())?;
+                role = Some(netplay_socket::Role::Join { address });
+            },
+            "--input-delay" => {
+                input_delay = number_arg(&mut args, &arg)?;
+            },
+            "--latency" => {
+                conditions.latency = number_arg(&mut args, &arg)?;
+            },
+            "--loss" => {
+                conditions.loss_percent = number_arg::<u8>(&mut args, &arg)?.min(100);
+            },
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            },
+            _ => return Err(format!("Unknown argument {:?}", arg)),
         }
     }
 
-    pub fn inner_main() {
-        let (mut rl, thread) = {
-            // TODO: Read display size ourselves, since while raylib tries to figure
-            // out the right size if `0, 0` is passed, it sometimes gets the wrong
-            // answer. In particular, on my current dev setup which uses Linux.
-            // Since as of this writing, I'm unable to find a small cross-platform
-            // crate for this, one option to get the info is to copy what the
-            // `winit` crate does. However, that turns out to be rather complicated,
-            // even just for x11, and hardcoding it for Linux currently seems
-            // preferable to either including winit as a dependency without using
-            // most of it, or spending the time to whittle away all the parts we
-            // don't need, since we just want the current monitor's size.
-            #[cfg(target_os = "linux")]
-            const W: i32 = 1920;
-            #[cfg(target_os = "linux")]
-            const H: i32 = 1080;
-
-            #[cfg(not(target_os = "linux"))]
-            const W: i32 = 0;
-            #[cfg(not(target_os = "linux"))]
-            const H: i32 = 0;
-
-            raylib::init()
-            .size(W, H)
-            .resizable()
-            .title(WINDOW_TITLE)
-            .build()
-        };
-
-        if cfg!(debug_assertions) {
-            logging::set_trace_log(TraceLogLevel::LOG_WARNING);
-        }
-
-        rl.set_target_fps(60);
-        rl.toggle_fullscreen();
-
-        // We need a reference to this so we can use `draw_text_rec`
-        let font = rl.get_font_default();
-
-        let spritesheet_img = {
-            let byte_count: i32 = SPRITESHEET_BYTES.len()
-                .try_into()
-                .expect("(2^31)-1 bytes ought to be enough for anybody!");
-
-            let bytes = SPRITESHEET_BYTES.as_ptr();
-
-            let file_type = b".png\0" as *const u8 as *const i8;
-
-            unsafe {
-                Image::from_raw(LoadImageFromMemory(
-                    file_type,
-                    bytes,
-                    byte_count
-                ))
-            }
-        };
-
-        let spritesheet = rl.load_texture_from_image(
-            &thread,
-            &spritesheet_img
-        ).expect(
-            "Embedded spritesheet could not be loaded!"
-        );
-
-        // This call currently (sometimes?) produces warnings about not being able
-        // to find shader attributes/uniforms. These warnings seem harmless at the
-        // moment. I think the cause is that the unused parts are being optimized
-        // out by the GPU when it interprets the shader, as mentioned here:
-        // https://github.com/raysan5/raylib/issues/2211
-        let grid_shader = rl.load_shader_from_memory(
-            &thread,
-            None,
-            Some(SAMPLING_SHADER)
-        );
-
-        // This seems like a safe texture size, with wide GPU support.
-        // TODO What we should do is query GL_MAX_TEXTURE_SIZE and figure
-        // out what to do if we get a smaller value than this.
-//        const RENDER_TARGET_SIZE: u32 = 8192;
-        // On the other hand, 8192 makes my old intergrated graphics laptop overheat
-        // Maybe it would be faster/less hot to avoiding clearing the whole thing
-        // each frame?
-        const RENDER_TARGET_SIZE: u32 = 2048;
-
-        // We'll let the OS reclaim the memory when the game closes.
-        let mut render_target = rl.load_render_texture(
-            &thread,
-            RENDER_TARGET_SIZE,
-            RENDER_TARGET_SIZE
-        ).unwrap();
-
-        let seed: u128 = {
-            use std::time::SystemTime;
-
-            let duration = match
-                SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-            {
-                Ok(d) => d,
-                Err(err) => err.duration(),
-            };
-
-            duration.as_nanos()
-        };
-        println!("{}", seed);
-
-        let mut state = game::State::from_seed(seed.to_le_bytes());
-        let mut commands = Storage(Vec::with_capacity(1024));
-
-        // generate the commands for the first frame
-        game::update(&mut state, &mut commands, 0, draw_wh(&rl), rl.get_frame_time());
-
-        const BACKGROUND: Color = Color{ r: 0x22, g: 0x22, b: 0x22, a: 255 };
-        const WHITE: Color = Color{ r: 0xee, g: 0xee, b: 0xee, a: 255 };
-        const TEXT: Color = WHITE;
-        const NO_TINT: Color = WHITE;
-        const OUTLINE: Color = WHITE;
-
-        let mut show_stats = false;
-        use std::time::Instant;
-        struct TimeSpan {
-            start: Instant,
-            end: Instant,
-        }
-
-        impl Default for TimeSpan {
-            fn default() -> Self {
-                let start = Instant::now();
-                Self {
-                    start,
-                    end: start,
-                }
-            }
-        }
-
-        impl std::fmt::Display for TimeSpan {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                write!(
-                    f,
-                    "{: >6.3} ms",
-                    (self.end - self.start).as_micros() as f32 / 1000.0
-                )
-            }
-        }
+    Ok(Args {
+        seed,
+        checksum_log,
+        headless,
+        capture_frame_skip,
+        netplay: role.map(|role| netplay_socket::Options {
+            role,
+            input_delay,
+            conditions,
+        }),
+    })
+}
 
-        #[derive(Default)]
-        struct FrameStats {
-            loop_body: TimeSpan,
-            input_gather: TimeSpan,
-            update: TimeSpan,
-            render: TimeSpan,
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(1);
         }
+    };
 
-        let mut prev_stats = FrameStats::default();
-
-        while !rl.window_should_close() {
-            let mut current_stats = FrameStats::default();
-            current_stats.loop_body.start = Instant::now();
-            current_stats.input_gather.start = current_stats.loop_body.start;
-
-            let dt = rl.get_frame_time();
-
-            if rl.is_key_pressed(KEY_F11) {
-                rl.toggle_fullscreen();
-            }
-
-            if rl.is_key_pressed(KEY_F10) {
-                show_stats = !show_stats;
-            }
-
-            let mut input_flags = 0;
-
-            if rl.is_key_pressed(KEY_SPACE) || rl.is_key_pressed(KEY_ENTER) {
-                input_flags |= game::INPUT_INTERACT_PRESSED;
-            }
-
-            if rl.is_key_down(KEY_UP) || rl.is_key_down(KEY_W) {
-                input_flags |= game::INPUT_UP_DOWN;
-            }
-
-            if rl.is_key_down(KEY_DOWN) || rl.is_key_down(KEY_S) {
-                input_flags |= game::INPUT_DOWN_DOWN;
-            }
-
-            if rl.is_key_down(KEY_LEFT) || rl.is_key_down(KEY_A) {
-                input_flags |= game::INPUT_LEFT_DOWN;
-            }
-
-            if rl.is_key_down(KEY_RIGHT) || rl.is_key_down(KEY_D) {
-                input_flags |= game::INPUT_RIGHT_DOWN;
-            }
-
-            if rl.is_key_pressed(KEY_UP) || rl.is_key_pressed(KEY_W) {
-                input_flags |= game::INPUT_UP_PRESSED;
-            }
-
-            if rl.is_key_pressed(KEY_DOWN) || rl.is_key_pressed(KEY_S) {
-                input_flags |= game::INPUT_DOWN_PRESSED;
-            }
-
-            if rl.is_key_pressed(KEY_LEFT) || rl.is_key_pressed(KEY_A) {
-                input_flags |= game::INPUT_LEFT_PRESSED;
-            }
-
-            if rl.is_key_pressed(KEY_RIGHT) || rl.is_key_pressed(KEY_D) {
-                input_flags |= game::INPUT_RIGHT_PRESSED;
-            }
-
-            current_stats.input_gather.end = Instant::now();
-            current_stats.update.start = current_stats.input_gather.end;
-
-            game::update(
-                &mut state,
-                &mut commands,
-                input_flags,
-                draw_wh(&rl),
-                dt
-            );
-
-            current_stats.update.end = Instant::now();
-            current_stats.render.start = current_stats.update.end;
-
-            let screen_render_rect = Rectangle {
-                x: 0.,
-                y: 0.,
-                width: rl.get_screen_width() as _,
-                height: rl.get_screen_height() as _
-            };
-
-            let sizes = game::sizes(&state);
-
-            let mut d = rl.begin_drawing(&thread);
-
-            d.clear_background(BACKGROUND);
-
-            {
-                let mut texture_d = d.begin_texture_mode(
-                    &thread,
-                    &mut render_target
-                );
-
-                let mut shader_d = texture_d.begin_shader_mode(
-                    &grid_shader
-                );
-
-                shader_d.clear_background(BACKGROUND);
-
-                // the -1 and +2 business makes the border lie just outside the actual
-                // play area
-                shader_d.draw_rectangle_lines(
-                    sizes.play_xywh.x as i32 - 1,
-                    sizes.play_xywh.y as i32 - 1,
-                    sizes.play_xywh.w as i32 + 2,
-                    sizes.play_xywh.h as i32 + 2,
-                    OUTLINE
-                );
-
-                let tile_base_source_rect = Rectangle {
-                    x: 0.,
-                    y: 0.,
-                    width: SPRITE_PIXELS_PER_TILE_SIDE,
-                    height: SPRITE_PIXELS_PER_TILE_SIDE,
-                };
-
-                let tile_base_render_rect = Rectangle {
-                    x: 0.,
-                    y: 0.,
-                    width: sizes.tile_side_length,
-                    height: sizes.tile_side_length,
-                };
-
-                // I don't know why the texture lookup seems to be offset by these
-                // amounts, but it seems to be.
-                const X_SOURCE_FUDGE: f32 = -2.;
-                const Y_SOURCE_FUDGE: f32 = -1.;
-
-                for cmd in commands.0.iter() {
-                    use game::draw::Command::*;
-                    match cmd {
-                        Sprite(s) => {
-                            let spec = source_spec(s.sprite);
-
-                            let origin = Vector2 {
-                                x: (tile_base_render_rect.width / 2.).round(),
-                                y: (tile_base_render_rect.height / 2.).round(),
-                            };
-
-                            let render_rect = Rectangle {
-                                x: s.xy.x + origin.x,
-                                y: s.xy.y + origin.y,
-                                ..tile_base_render_rect
-                            };
-
-                            let source_rect = Rectangle {
-                                x: spec.x + X_SOURCE_FUDGE,
-                                y: spec.y + Y_SOURCE_FUDGE,
-                                ..tile_base_source_rect
-                            };
-
-                            shader_d.draw_texture_pro(
-                                &spritesheet,
-                                source_rect,
-                                render_rect,
-                                origin,
-                                0.0, // Rotation
-                                NO_TINT
-                            );
-                        }
-                        Text(t) => {
-                            use game::draw::TextKind;
-                            match t.kind {
-                                TextKind::UI => {
-                                    shader_d.draw_text_rec(
-                                        &font,
-                                        &t.text,
-                                        Rectangle {
-                                            x: t.xy.x,
-                                            y: t.xy.y,
-                                            width: t.wh.w,
-                                            height: t.wh.h,
-                                        },
-                                        // Constant arrived at through trial and error.
-                                        sizes.draw_wh.w * (1./48.),
-                                        1.,
-                                        true, // word_wrap
-                                        TEXT
-                                    );
-                                },
-                            };
-                        }
-                    }
-                }
-
-                if show_stats {
-                    shader_d.draw_text_rec(
-                        &font,
-                        &format!(
-                            "loop {}\ninput {}\nupdate {}\nrender {}",
-                            prev_stats.loop_body,
-                            prev_stats.input_gather,
-                            prev_stats.update,
-                            prev_stats.render,
-                        ),
-                        Rectangle {
-                            x: 0.,
-                            y: 0.,
-                            width: sizes.play_xywh.x,
-                            height: sizes.play_xywh.h,
-                        },
-                        // Constant arrived at through trial and error.
-                        sizes.draw_wh.w * (1./96.),
-                        1.,
-                        true, // word_wrap
-                        TEXT
-                    );
-                }
-            }
-
-            let render_target_source_rect = Rectangle {
-                x: 0.,
-                y: (RENDER_TARGET_SIZE as f32) - screen_render_rect.height,
-                width: screen_render_rect.width,
-                // y flip for openGL
-                height: -screen_render_rect.height
-            };
-
-            d.draw_texture_pro(
-                &render_target,
-                render_target_source_rect,
-                screen_render_rect,
-                Vector2::default(),
-                0.0,
-                NO_TINT
-            );
-
-            current_stats.render.end = Instant::now();
-
-            // Have Raylib perform the frame waiting.
-            drop(d);
-
-            current_stats.loop_body.end = Instant::now();
-
-            prev_stats = current_stats;
-        }
+    match args.headless {
+        Some(frame_count) => main_loop::run(
+            &mut headless_platform::HeadlessPlatform::new(frame_count),
+            args,
+        ),
+        None => main_loop::run(
+            &mut raylib_rs_platform::RaylibPlatform::new(args.capture_frame_skip),
+            args,
+        ),
     }
-
 }
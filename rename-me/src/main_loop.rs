@@ -0,0 +1,444 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! The parts of running the game that don't depend on how it is shown:
+//! setting up the state, files, netplay and profiling, and the loop itself.
+
+use std::time::Instant;
+
+use crate::{
+    date,
+    frame_stats::{FrameStats, History},
+    high_scores_file,
+    level_files,
+    netplay_socket,
+    platform::{Frame, Platform, Stats},
+    settings_file,
+    Args,
+};
+
+fn unix_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn write_frame_stats_csv(history: &History) {
+    let path = format!("frame-stats-{}.csv", unix_seconds());
+
+    let result = std::fs::File::create(&path)
+        .map(std::io::BufWriter::new)
+        .and_then(|file| history.write_csv(file));
+
+    match result {
+        Ok(()) => println!("Wrote frame stats to {}", path),
+        Err(err) => eprintln!("Could not write frame stats to {}: {}", path, err),
+    }
+}
+
+fn profile_clock() -> game::profile::Nanos {
+    use std::sync::OnceLock;
+    static START: OnceLock<Instant> = OnceLock::new();
+
+    START.get_or_init(Instant::now).elapsed().as_nanos() as _
+}
+
+/// `events` should have been produced by `write_chrome_trace_events`.
+fn write_chrome_trace(events: &str) {
+    let path = format!("trace-{}.json", unix_seconds());
+
+    let result = std::fs::write(
+        &path,
+        format!("{{\"traceEvents\":[\n{}\n]}}\n", events)
+    );
+
+    match result {
+        Ok(()) => println!("Wrote trace to {}", path),
+        Err(err) => eprintln!("Could not write trace to {}: {}", path, err),
+    }
+}
+
+fn handle_request(state: &mut game::State) {
+    match game::take_request(state) {
+        Some(game::Request::SaveLevel { slot }) => {
+            let mut text = String::new();
+            // Writing to a `String` never fails.
+            let _ = game::write_saved_level(state, &mut text);
+            let status = match level_files::save(slot, &text) {
+                Ok(()) => format!("Saved slot {}", slot),
+                Err(err) => format!("Could not save slot {}: {}", slot, err),
+            };
+            game::set_status(state, &status);
+        },
+        Some(game::Request::LoadLevel { slot }) => {
+            match level_files::load(slot) {
+                Ok(text) => {
+                    if let Err(err) = game::load_level(state, &text) {
+                        eprintln!("Could not load slot {}: {}", slot, err);
+                    }
+                },
+                Err(err) => {
+                    game::set_status(
+                        state,
+                        &format!("Could not load slot {}: {}", slot, err)
+                    );
+                },
+            }
+        },
+        Some(game::Request::SaveHighScores) => {
+            let mut text = String::new();
+            // Writing to a `String` never fails.
+            let _ = game::write_high_scores(state, &mut text);
+            if let Err(err) = high_scores_file::save(&text) {
+                eprintln!("Could not save high scores: {}", err);
+            }
+        },
+        None => {},
+    }
+}
+
+pub fn run(platform: &mut impl Platform, args: Args) {
+    let has_player = platform.has_player();
+
+    let settings = if has_player {
+        settings_file::load()
+    } else {
+        <_>::default()
+    };
+    platform.apply_settings(&settings);
+
+    let seed = args.seed.unwrap_or_else(|| {
+        use std::time::SystemTime;
+
+        let duration = match
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+        {
+            Ok(d) => d,
+            Err(err) => err.duration(),
+        };
+
+        duration.as_nanos().to_le_bytes()
+    });
+    println!("Seed: {}", game::seed::encode(seed));
+
+    let mut state = game::State::from_seed(seed);
+    game::set_date(&mut state, date::today());
+    if has_player {
+        game::set_high_scores(&mut state, high_scores_file::load());
+    }
+    game::set_profile_clock(&mut state, Some(profile_clock));
+    game::set_settings(&mut state, settings);
+    // The settings as they were last saved, so we know when to save again.
+    let mut saved_settings = settings;
+
+    let mut checksum_log = args.checksum_log.and_then(|path| {
+        match std::fs::File::create(&path) {
+            Ok(file) => {
+                game::enable_checksum_log(&mut state);
+                Some(std::io::BufWriter::new(file))
+            },
+            Err(err) => {
+                eprintln!("Could not create {}: {}", path.display(), err);
+                None
+            },
+        }
+    });
+
+    // After the checksum log is started, so the board goes in it.
+    if !has_player {
+        game::start_solo(&mut state, 1);
+    }
+
+    let mut netplay_socket = args.netplay.and_then(|options| {
+        let socket = match netplay_socket::Socket::open(&options) {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("Could not start netplay: {}", err);
+                return None
+            },
+        };
+
+        match &options.role {
+            netplay_socket::Role::Host { .. } => {
+                match socket.local_addr() {
+                    Ok(addr) => println!("Waiting for someone to join on port {}.", addr.port()),
+                    Err(_) => println!("Waiting for someone to join."),
+                }
+                game::host_netplay(&mut state, options.input_delay);
+            },
+            netplay_socket::Role::Join { address } => {
+                println!("Joining {}.", address);
+                game::join_netplay(&mut state, options.input_delay);
+            },
+        }
+
+        Some(socket)
+    });
+
+    // Holds the events recorded so far, while recording.
+    let mut trace_events: Option<String> = None;
+    let mut commands: Vec<game::draw::Command> = Vec::with_capacity(1024);
+
+    // generate the commands for the first frame
+    game::update(
+        &mut state,
+        &mut commands,
+        [0; game::MAX_PLAYERS],
+        platform.draw_wh(),
+        platform.frame_time()
+    );
+
+    let mut history = History::default();
+
+    while !platform.should_close() && !game::should_quit(&state) {
+        let mut current_stats = FrameStats::default();
+        current_stats.loop_body.start = Instant::now();
+        current_stats.input_gather.start = current_stats.loop_body.start;
+
+        let dt = platform.frame_time();
+
+        let input = platform.poll_input(&mut state);
+
+        if input.hotkeys.toggle_fullscreen {
+            let mut settings = game::settings(&state);
+            settings.fullscreen = !settings.fullscreen;
+            game::set_settings(&mut state, settings);
+        }
+
+        if input.hotkeys.toggle_stats {
+            let mut settings = game::settings(&state);
+            settings.show_stats = !settings.show_stats;
+            game::set_settings(&mut state, settings);
+        }
+
+        if input.hotkeys.write_frame_stats {
+            write_frame_stats_csv(&history);
+        }
+
+        if input.hotkeys.toggle_trace {
+            match trace_events.take() {
+                Some(events) => write_chrome_trace(&events),
+                None => {
+                    println!("Recording trace. Press F7 again to stop.");
+                    trace_events = Some(String::new());
+                }
+            }
+        }
+
+        if input.hotkeys.copy_seed {
+            let code = game::seed_code(&state);
+            match platform.set_clipboard_text(&code) {
+                Ok(()) => println!("Copied seed {} to the clipboard.", code),
+                Err(err) => eprintln!("Could not copy seed {}: {}", code, err),
+            }
+        }
+
+        let mut player_inputs = [0; game::MAX_PLAYERS];
+        let player_count = usize::from(game::player_count(&state));
+        if player_count <= 1 {
+            // A single player can use whichever they like.
+            player_inputs[0] = input.device_flags.iter().fold(0, |acc, &flags| acc | flags);
+        } else {
+            player_inputs[..player_count]
+                .copy_from_slice(&input.device_flags[..player_count]);
+        }
+        // Any player can control the rest, so it doesn't matter whose
+        // flags these go in.
+        player_inputs[0] |= input.shared_flags;
+
+        current_stats.input_gather.end = Instant::now();
+        current_stats.update.start = current_stats.input_gather.end;
+
+        let draw_wh = platform.draw_wh();
+
+        game::update(
+            &mut state,
+            &mut commands,
+            player_inputs,
+            draw_wh,
+            dt
+        );
+
+        let settings = game::settings(&state);
+        platform.apply_settings(&settings);
+
+        if has_player && settings != saved_settings {
+            if let Err(err) = settings_file::save(&settings) {
+                eprintln!("Could not save settings: {}", err);
+            }
+            saved_settings = settings;
+        }
+
+        if let Some(socket) = netplay_socket.as_mut() {
+            socket.exchange(&mut state);
+        }
+
+        if let Some(file) = checksum_log.as_mut() {
+            use std::io::Write;
+
+            let text = game::take_checksum_log(&mut state);
+            if let Err(err) = file.write_all(text.as_bytes()) {
+                eprintln!("Could not write checksums, so stopping: {}", err);
+                checksum_log = None;
+            }
+        }
+
+        if has_player {
+            handle_request(&mut state);
+        }
+
+        if let Some(events) = trace_events.as_mut() {
+            let needs_comma = !events.is_empty();
+            // Writing to a `String` never fails.
+            let _ = game::profile::write_chrome_trace_events(
+                events,
+                game::profile_records(&state),
+                game::profile_dropped(&state),
+                needs_comma,
+            );
+        }
+
+        // A stable sort, so commands on the same layer keep their order.
+        commands.sort_by_key(|cmd| cmd.layer());
+
+        current_stats.update.end = Instant::now();
+        current_stats.render.start = current_stats.update.end;
+
+        let sizes = game::sizes(&state);
+
+        current_stats.render.end = platform.present(Frame {
+            commands: &commands,
+            sizes: &sizes,
+            stats: settings.show_stats.then(|| Stats {
+                history: &mut history,
+                records: game::profile_records(&state),
+                dropped_records: game::profile_dropped(&state),
+            }),
+        });
+
+        current_stats.loop_body.end = Instant::now();
+
+        history.push(&current_stats);
+    }
+
+    if let Some(socket) = netplay_socket.as_mut() {
+        game::leave_netplay(&mut state);
+        socket.flush(&mut state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless_platform::HeadlessPlatform, platform::Input};
+    use game::SpriteKind;
+
+    /// Passes everything through to a `HeadlessPlatform`, keeping track of what
+    /// it was asked to draw.
+    struct Recorder {
+        headless: HeadlessPlatform,
+        presented: u32,
+        layers_were_sorted: bool,
+        last_sprites: Vec<SpriteKind>,
+    }
+
+    impl Platform for Recorder {
+        fn has_player(&self) -> bool {
+            self.headless.has_player()
+        }
+
+        fn should_close(&self) -> bool {
+            self.headless.should_close()
+        }
+
+        fn frame_time(&self) -> game::DeltaTimeInSeconds {
+            self.headless.frame_time()
+        }
+
+        fn poll_input(&mut self, state: &mut game::State) -> Input {
+            self.headless.poll_input(state)
+        }
+
+        fn draw_wh(&mut self) -> game::DrawWH {
+            self.headless.draw_wh()
+        }
+
+        fn apply_settings(&mut self, settings: &game::Settings) {
+            self.headless.apply_settings(settings)
+        }
+
+        fn set_clipboard_text(&mut self, text: &str) -> Result<(), String> {
+            self.headless.set_clipboard_text(text)
+        }
+
+        fn present(&mut self, frame: Frame) -> Instant {
+            self.presented += 1;
+            self.layers_were_sorted &= frame.commands
+                .windows(2)
+                .all(|pair| pair[0].layer() <= pair[1].layer());
+            self.last_sprites = frame.commands.iter()
+                .filter_map(|command| match command {
+                    game::draw::Command::Sprite(spec) => Some(spec.sprite),
+                    _ => None,
+                })
+                .collect();
+
+            self.headless.present(frame)
+        }
+    }
+
+    fn is_eye(sprite: SpriteKind) -> bool {
+        use SpriteKind::*;
+        match sprite {
+            NeutralEye
+            | DirEye(_)
+            | SmallPupilEye
+            | NarrowLeftEye
+            | NarrowCenterEye
+            | NarrowRightEye
+            | ClosedEye
+            | HalfLidEye => true,
+            Arrow(..) | Floor | Wall | Goal | Ice | Teleporter(_) => false,
+        }
+    }
+
+    #[test]
+    fn headless_runs_step_and_draw_every_frame() {
+        const FRAMES: u32 = 30;
+
+        let log_path = std::env::temp_dir()
+            .join(format!("rename-me-main-loop-test-{}.log", std::process::id()));
+
+        let mut platform = Recorder {
+            headless: HeadlessPlatform::new(FRAMES),
+            presented: 0,
+            layers_were_sorted: true,
+            last_sprites: Vec::new(),
+        };
+        run(&mut platform, Args {
+            seed: Some([0x5E; 16]),
+            checksum_log: Some(log_path.clone()),
+            headless: Some(FRAMES),
+            capture_frame_skip: 0,
+            netplay: None,
+        });
+
+        assert_eq!(platform.presented, FRAMES);
+        assert!(platform.layers_were_sorted);
+        assert_eq!(platform.last_sprites.iter().filter(|&&s| is_eye(s)).count(), 1);
+        assert!(platform.last_sprites.contains(&SpriteKind::Goal));
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let _ = std::fs::remove_file(&log_path);
+        let entries = game::checksum::parse_log(&log).unwrap();
+
+        // One checksum per update step, with the time from each frame, plus
+        // the one before the loop, turned into steps.
+        let expected_steps = (FRAMES + 1) * game::UPDATES_PER_SECOND / 60;
+        let steps: Vec<_> = entries.iter().map(|entry| entry.step).collect();
+        assert_eq!(steps, (1..=expected_steps).collect::<Vec<_>>());
+        assert!(entries.iter().all(|entry| entry.board == entries[0].board));
+    }
+}
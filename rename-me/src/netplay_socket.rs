@@ -0,0 +1,136 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Instant,
+};
+
+use game::netplay::{Conditions, Frame, Millis, SimulatedLink};
+
+pub const DEFAULT_PORT: u16 = 7727;
+
+/// Comfortably bigger than any packet the game sends.
+const MAX_PACKET_LENGTH: usize = 1024;
+
+/// The simulated conditions are the same each run, so problems they cause can
+/// be reproduced.
+const LINK_SEED: game::Seed = [0x11; 16];
+
+#[derive(Clone, Debug)]
+pub enum Role {
+    Host { port: u16 },
+    Join { address: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub role: Role,
+    pub input_delay: Frame,
+    /// Applied to the packets we send. Giving both peers the same conditions
+    /// makes the connection equally bad both ways.
+    pub conditions: Conditions,
+}
+
+pub struct Socket {
+    socket: UdpSocket,
+    /// Where packets go. The host doesn't know until someone joins.
+    peer: Option<SocketAddr>,
+    link: SimulatedLink,
+    opened_at: Instant,
+}
+
+impl Socket {
+    pub fn open(options: &Options) -> io::Result<Self> {
+        let (socket, peer) = match &options.role {
+            Role::Host { port } => (UdpSocket::bind(("0.0.0.0", *port))?, None),
+            Role::Join { address } => {
+                let peer = address.to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("could not find {}", address),
+                    ))?;
+                let any = if peer.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+
+                (UdpSocket::bind(any)?, Some(peer))
+            },
+        };
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            peer,
+            link: SimulatedLink::new(options.conditions, LINK_SEED),
+            opened_at: Instant::now(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn now(&self) -> Millis {
+        self.opened_at.elapsed().as_millis() as Millis
+    }
+
+    /// Sends what the game has for the other peer, and passes on what they
+    /// have sent us. Should be called after each `game::update`.
+    pub fn exchange(&mut self, state: &mut game::State) {
+        let now = self.now();
+
+        while let Some(packet) = game::take_packet(state) {
+            self.link.send(now, packet);
+        }
+
+        while let Some(packet) = self.link.take_due(now) {
+            self.send(&packet);
+        }
+
+        let mut buffer = [0; MAX_PACKET_LENGTH];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) => {
+                    // The first peer to send us anything is the one we play
+                    // with, and anyone else is ignored.
+                    if *self.peer.get_or_insert(from) == from {
+                        game::receive_packet(state, &buffer[..length]);
+                    }
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                // Some platforms report packets we sent that nobody was
+                // listening for here, which just means the other peer isn't
+                // running yet.
+                Err(err) if err.kind() == io::ErrorKind::ConnectionReset => {},
+                Err(err) => {
+                    eprintln!("Could not receive from the other player: {}", err);
+                    break
+                },
+            }
+        }
+    }
+
+    /// Sends everything straight away, ignoring the simulated conditions, for
+    /// when we are about to quit.
+    pub fn flush(&mut self, state: &mut game::State) {
+        while let Some(packet) = game::take_packet(state) {
+            self.send(&packet);
+        }
+    }
+
+    fn send(&self, packet: &[u8]) {
+        let Some(peer) = self.peer else {
+            return
+        };
+
+        match self.socket.send_to(packet, peer) {
+            Ok(_) => {},
+            // Lost packets are expected anyway, and the game sends its inputs
+            // again until they arrive.
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock
+            || err.kind() == io::ErrorKind::ConnectionRefused => {},
+            Err(err) => eprintln!("Could not send to the other player: {}", err),
+        }
+    }
+}
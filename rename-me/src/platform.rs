@@ -0,0 +1,74 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! What the main loop needs from whatever is showing the game, so the same
+//! loop can run with a window, or without one.
+
+use std::time::Instant;
+
+use crate::frame_stats;
+
+/// Keys that do things outside the game itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hotkeys {
+    pub toggle_fullscreen: bool,
+    pub toggle_stats: bool,
+    pub write_frame_stats: bool,
+    /// Starts recording a trace, or stops and writes it out.
+    pub toggle_trace: bool,
+    pub copy_seed: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Input {
+    /// One set of flags per input device, in the order players get them.
+    pub device_flags: [game::InputFlags; game::MAX_PLAYERS],
+    /// Flags for things any player can control, like the menus.
+    pub shared_flags: game::InputFlags,
+    pub hotkeys: Hotkeys,
+}
+
+/// Shown over the game, when the settings say to.
+pub struct Stats<'frame> {
+    pub history: &'frame mut frame_stats::History,
+    pub records: &'frame [game::profile::Record],
+    /// How many scopes didn't fit in `records`.
+    pub dropped_records: usize,
+}
+
+pub struct Frame<'frame> {
+    /// Sorted by layer.
+    pub commands: &'frame [game::draw::Command],
+    pub sizes: &'frame game::draw::Sizes,
+    pub stats: Option<Stats<'frame>>,
+}
+
+pub trait Platform {
+    /// False when nobody is playing, in which case the game starts straight on
+    /// a board, and the player's own files are left alone.
+    fn has_player(&self) -> bool;
+
+    /// True once the window has been closed, or whatever else means the game
+    /// should stop.
+    fn should_close(&self) -> bool;
+
+    /// How long the last frame took.
+    fn frame_time(&self) -> game::DeltaTimeInSeconds;
+
+    /// Passes any text typed since the last call to `game::type_char`, if the
+    /// game wants text, and returns everything else.
+    fn poll_input(&mut self, state: &mut game::State) -> Input;
+
+    /// The size the game should draw at this frame.
+    fn draw_wh(&mut self) -> game::DrawWH;
+
+    /// Called after each update, since the game only records the settings.
+    fn apply_settings(&mut self, settings: &game::Settings);
+
+    fn set_clipboard_text(&mut self, text: &str) -> Result<(), String>;
+
+    /// Draws the frame. Returns when the drawing was finished, which is before
+    /// any waiting for the next frame, so that waiting isn't counted as
+    /// rendering time.
+    fn present(&mut self, frame: Frame) -> Instant;
+}
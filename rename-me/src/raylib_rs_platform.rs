@@ -0,0 +1,892 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! All the raylib specific stuff, behind the `Platform` trait.
+
+use crate::{
+    captures,
+    frame_stats,
+    platform::{Frame, Hotkeys, Input, Platform},
+    source_spec,
+    SPRITE_PIXELS_PER_TILE_SIDE,
+    SPRITESHEET_BYTES,
+    SAMPLING_SHADER,
+    WINDOW_TITLE
+};
+use raylib::prelude::{
+    *,
+    KeyboardKey::*,
+    ffi::{GetCharPressed, GetTextureData, LoadImageFromMemory},
+    core::{
+        drawing::{RaylibTextureModeExt, RaylibShaderModeExt},
+        logging
+    }
+};
+
+use ::core::{
+    convert::TryInto,
+};
+
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// The keys one player uses to move, when players share the keyboard.
+struct MoveKeys {
+    up: KeyboardKey,
+    down: KeyboardKey,
+    left: KeyboardKey,
+    right: KeyboardKey,
+    interact: KeyboardKey,
+}
+
+const WASD_KEYS: MoveKeys = MoveKeys {
+    up: KEY_W,
+    down: KEY_S,
+    left: KEY_A,
+    right: KEY_D,
+    interact: KEY_SPACE,
+};
+
+const ARROW_KEYS: MoveKeys = MoveKeys {
+    up: KEY_UP,
+    down: KEY_DOWN,
+    left: KEY_LEFT,
+    right: KEY_RIGHT,
+    interact: KEY_ENTER,
+};
+
+fn move_key_flags(rl: &RaylibHandle, keys: &MoveKeys) -> game::InputFlags {
+    let mut flags = 0;
+
+    let pairs = [
+        (keys.up, game::INPUT_UP_DOWN, game::INPUT_UP_PRESSED),
+        (keys.down, game::INPUT_DOWN_DOWN, game::INPUT_DOWN_PRESSED),
+        (keys.left, game::INPUT_LEFT_DOWN, game::INPUT_LEFT_PRESSED),
+        (keys.right, game::INPUT_RIGHT_DOWN, game::INPUT_RIGHT_PRESSED),
+    ];
+
+    for (key, down_flag, pressed_flag) in pairs {
+        if rl.is_key_down(key) {
+            flags |= down_flag;
+        }
+        if rl.is_key_pressed(key) {
+            flags |= pressed_flag;
+        }
+    }
+
+    if rl.is_key_pressed(keys.interact) {
+        flags |= game::INPUT_INTERACT_PRESSED;
+    }
+
+    flags
+}
+
+fn gamepad_flags(rl: &RaylibHandle, gamepad: i32) -> game::InputFlags {
+    use GamepadButton::*;
+
+    if !rl.is_gamepad_available(gamepad) {
+        return 0
+    }
+
+    let mut flags = 0;
+
+    let pairs = [
+        (GAMEPAD_BUTTON_LEFT_FACE_UP, game::INPUT_UP_DOWN, game::INPUT_UP_PRESSED),
+        (GAMEPAD_BUTTON_LEFT_FACE_DOWN, game::INPUT_DOWN_DOWN, game::INPUT_DOWN_PRESSED),
+        (GAMEPAD_BUTTON_LEFT_FACE_LEFT, game::INPUT_LEFT_DOWN, game::INPUT_LEFT_PRESSED),
+        (GAMEPAD_BUTTON_LEFT_FACE_RIGHT, game::INPUT_RIGHT_DOWN, game::INPUT_RIGHT_PRESSED),
+    ];
+
+    for (button, down_flag, pressed_flag) in pairs {
+        if rl.is_gamepad_button_down(gamepad, button) {
+            flags |= down_flag;
+        }
+        if rl.is_gamepad_button_pressed(gamepad, button) {
+            flags |= pressed_flag;
+        }
+    }
+
+    if rl.is_gamepad_button_pressed(gamepad, GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+        flags |= game::INPUT_INTERACT_PRESSED;
+    }
+
+    if rl.is_gamepad_button_pressed(gamepad, GAMEPAD_BUTTON_MIDDLE_RIGHT) {
+        flags |= game::INPUT_MENU_PRESSED;
+    }
+
+    flags
+}
+
+fn to_color(colour: game::draw::Colour) -> Color {
+    Color {
+        r: colour.r,
+        g: colour.g,
+        b: colour.b,
+        a: colour.a,
+    }
+}
+
+fn screen_wh(rl: &RaylibHandle) -> game::DrawWH {
+    game::DrawWH {
+        w: rl.get_screen_width() as game::DrawW,
+        h: rl.get_screen_height() as game::DrawH,
+    }
+}
+
+/// This seems like a safe texture size, with wide GPU support, so we use it
+/// if we can't find out what the actual maximum is.
+const FALLBACK_MAX_TEXTURE_SIZE: u32 = 2048;
+
+fn max_texture_size() -> u32 {
+    // Raylib doesn't expose this, but it does link to OpenGL, and this
+    // function has been around since OpenGL 1.0. OpenGL uses the system
+    // calling convention, which is `stdcall` on 32 bit Windows.
+    extern "system" {
+        fn glGetIntegerv(pname: u32, data: *mut i32);
+    }
+    const GL_MAX_TEXTURE_SIZE: u32 = 0x0D33;
+
+    let mut size: i32 = 0;
+    // SAFETY: There is a current OpenGL context once the window has been
+    // created, and `GL_MAX_TEXTURE_SIZE` produces a single value.
+    unsafe {
+        glGetIntegerv(GL_MAX_TEXTURE_SIZE, &mut size);
+    }
+
+    if size > 0 {
+        size as u32
+    } else {
+        FALLBACK_MAX_TEXTURE_SIZE
+    }
+}
+
+/// We round render target sizes up to a multiple of this, so that resizing
+/// the window doesn't mean re-creating the render target every frame.
+const RENDER_TARGET_GRANULARITY: u32 = 256;
+
+fn render_target_length(screen_length: game::DrawLength, max: u32) -> u32 {
+    let length = (screen_length.max(1.) as u32)
+        .div_ceil(RENDER_TARGET_GRANULARITY)
+        * RENDER_TARGET_GRANULARITY;
+
+    core::cmp::min(length, max)
+}
+
+struct RenderTarget {
+    texture: RenderTexture2D,
+    w: u32,
+    h: u32,
+}
+
+impl RenderTarget {
+    /// Returns a render target large enough to cover the screen, if the GPU
+    /// allows it. If a big enough one can't be made, then whatever we can
+    /// make will be scaled up to cover the screen. See `game_wh`.
+    fn fit_to_screen(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        screen_wh: game::DrawWH,
+        max_texture_size: &mut u32,
+        old: Option<Self>,
+    ) -> Self {
+        let w = render_target_length(screen_wh.w, *max_texture_size);
+        let h = render_target_length(screen_wh.h, *max_texture_size);
+
+        match old {
+            Some(old) if old.w == w && old.h == h => return old,
+            _ => {}
+        }
+
+        // Dropping the old render target, if any, unloads it.
+        match rl.load_render_texture(thread, w, h) {
+            Ok(texture) => Self { texture, w, h },
+            Err(err) => {
+                // The driver claimed it could do this size, but it couldn't,
+                // so don't try anything this large again.
+                *max_texture_size = FALLBACK_MAX_TEXTURE_SIZE;
+
+                let w = core::cmp::min(w, FALLBACK_MAX_TEXTURE_SIZE);
+                let h = core::cmp::min(h, FALLBACK_MAX_TEXTURE_SIZE);
+
+                eprintln!(
+                    "Could not make render target, falling back to {}x{}: {}",
+                    w, h, err
+                );
+
+                Self {
+                    texture: rl.load_render_texture(thread, w, h)
+                        .expect("Could not make fallback render target!"),
+                    w,
+                    h,
+                }
+            }
+        }
+    }
+
+    /// The size we tell the game to draw at. This is the screen size, unless
+    /// the render target is too small, in which case it is the largest size
+    /// with the same aspect ratio as the screen that fits in the render
+    /// target.
+    fn game_wh(&self, screen_wh: game::DrawWH) -> game::DrawWH {
+        let scale = (self.w as game::DrawLength / screen_wh.w)
+            .min(self.h as game::DrawLength / screen_wh.h)
+            .min(1.);
+
+        game::DrawWH {
+            w: (screen_wh.w * scale).floor(),
+            h: (screen_wh.h * scale).floor(),
+        }
+    }
+}
+
+/// What was drawn to the render target this frame, the right way up, and
+/// without any unused space around it.
+fn game_image(render_target: &RenderTarget, game_wh: game::DrawWH) -> Image {
+    // SAFETY: raylib has been initialized, and the render target is loaded.
+    let mut image = unsafe {
+        Image::from_raw(GetTextureData(render_target.texture.texture))
+    };
+
+    // OpenGL puts the first row at the bottom.
+    image.flip_vertical();
+    image.crop(Rectangle {
+        x: 0.,
+        y: 0.,
+        width: game_wh.w,
+        height: game_wh.h,
+    });
+
+    image
+}
+
+fn export_png(image: &Image, path: &Path) -> Result<(), String> {
+    image.export_image(&path.to_string_lossy());
+
+    // Raylib only logs it when exporting fails, so we check for the file.
+    if path.exists() {
+        Ok(())
+    } else {
+        Err(format!("{} was not written", path.display()))
+    }
+}
+
+/// A numbered PNG per captured frame, since that is simple to write, and
+/// easy to turn into a GIF or video with other tools.
+struct Recording {
+    dir: PathBuf,
+    frame_count: u32,
+    frames_to_skip: u32,
+}
+
+pub struct RaylibPlatform {
+    rl: RaylibHandle,
+    thread: RaylibThread,
+    font: WeakFont,
+    spritesheet: Texture2D,
+    grid_shader: Shader,
+    max_texture_size: u32,
+    /// Only `None` while it is being replaced, in `draw_wh`.
+    render_target: Option<RenderTarget>,
+    /// The sizes from the last call to `draw_wh`, which `present` needs.
+    screen_wh: game::DrawWH,
+    game_wh: game::DrawWH,
+    /// The last target FPS we passed to raylib, if any.
+    target_fps: Option<game::settings::TargetFps>,
+    /// How many frames to skip after each one captured while recording.
+    capture_frame_skip: u32,
+    take_screenshot: bool,
+    recording: Option<Recording>,
+}
+
+impl RaylibPlatform {
+    pub fn new(capture_frame_skip: u32) -> Self {
+        let (mut rl, thread) = {
+            // TODO: Read display size ourselves, since while raylib tries to figure
+            // out the right size if `0, 0` is passed, it sometimes gets the wrong
+            // answer. In particular, on my current dev setup which uses Linux.
+            // Since as of this writing, I'm unable to find a small cross-platform
+            // crate for this, one option to get the info is to copy what the
+            // `winit` crate does. However, that turns out to be rather complicated,
+            // even just for x11, and hardcoding it for Linux currently seems
+            // preferable to either including winit as a dependency without using
+            // most of it, or spending the time to whittle away all the parts we
+            // don't need, since we just want the current monitor's size.
+            #[cfg(target_os = "linux")]
+            const W: i32 = 1920;
+            #[cfg(target_os = "linux")]
+            const H: i32 = 1080;
+
+            #[cfg(not(target_os = "linux"))]
+            const W: i32 = 0;
+            #[cfg(not(target_os = "linux"))]
+            const H: i32 = 0;
+
+            raylib::init()
+            .size(W, H)
+            .resizable()
+            .title(WINDOW_TITLE)
+            .build()
+        };
+
+        if cfg!(debug_assertions) {
+            logging::set_trace_log(TraceLogLevel::LOG_WARNING);
+        }
+
+        // We want to use escape to open the pause menu instead.
+        rl.set_exit_key(None);
+
+        // We need a reference to this so we can use `draw_text_ex` and
+        // `draw_text_rec`
+        let font = rl.get_font_default();
+
+        let spritesheet_img = {
+            let byte_count: i32 = SPRITESHEET_BYTES.len()
+                .try_into()
+                .expect("(2^31)-1 bytes ought to be enough for anybody!");
+
+            let bytes = SPRITESHEET_BYTES.as_ptr();
+
+            let file_type = b".png\0" as *const u8 as *const i8;
+
+            unsafe {
+                Image::from_raw(LoadImageFromMemory(
+                    file_type,
+                    bytes,
+                    byte_count
+                ))
+            }
+        };
+
+        let spritesheet = rl.load_texture_from_image(
+            &thread,
+            &spritesheet_img
+        ).expect(
+            "Embedded spritesheet could not be loaded!"
+        );
+
+        // This call currently (sometimes?) produces warnings about not being able
+        // to find shader attributes/uniforms. These warnings seem harmless at the
+        // moment. I think the cause is that the unused parts are being optimized
+        // out by the GPU when it interprets the shader, as mentioned here:
+        // https://github.com/raysan5/raylib/issues/2211
+        let grid_shader = rl.load_shader_from_memory(
+            &thread,
+            None,
+            Some(SAMPLING_SHADER)
+        );
+
+        let mut max_texture_size = max_texture_size();
+
+        let current_screen_wh = screen_wh(&rl);
+
+        let render_target = RenderTarget::fit_to_screen(
+            &mut rl,
+            &thread,
+            current_screen_wh,
+            &mut max_texture_size,
+            None,
+        );
+
+        let game_wh = render_target.game_wh(current_screen_wh);
+
+        Self {
+            rl,
+            thread,
+            font,
+            spritesheet,
+            grid_shader,
+            max_texture_size,
+            render_target: Some(render_target),
+            screen_wh: current_screen_wh,
+            game_wh,
+            target_fps: None,
+            capture_frame_skip,
+            take_screenshot: false,
+            recording: None,
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(recording) => println!(
+                "Wrote {} frames to {}",
+                recording.frame_count,
+                recording.dir.display()
+            ),
+            None => match captures::recording_dir() {
+                Ok(dir) => {
+                    println!("Recording to {}. Press F4 again to stop.", dir.display());
+                    self.recording = Some(Recording {
+                        dir,
+                        frame_count: 0,
+                        frames_to_skip: 0,
+                    });
+                },
+                Err(err) => eprintln!("Could not start recording: {}", err),
+            },
+        }
+    }
+}
+
+const BACKGROUND: Color = Color{ r: 0x22, g: 0x22, b: 0x22, a: 255 };
+const WHITE: Color = Color{ r: 0xee, g: 0xee, b: 0xee, a: 255 };
+const TEXT: Color = WHITE;
+const NO_TINT: Color = WHITE;
+const OUTLINE: Color = WHITE;
+const GRAPH: Color = Color{ r: 0x3a, g: 0xc2, b: 0x4a, a: 255 };
+const OVER_BUDGET: Color = Color{ r: 0xde, g: 0x3a, b: 0x3a, a: 255 };
+
+impl Platform for RaylibPlatform {
+    fn has_player(&self) -> bool {
+        true
+    }
+
+    fn should_close(&self) -> bool {
+        self.rl.window_should_close()
+    }
+
+    fn frame_time(&self) -> game::DeltaTimeInSeconds {
+        self.rl.get_frame_time()
+    }
+
+    fn poll_input(&mut self, state: &mut game::State) -> Input {
+        if self.rl.is_key_pressed(KEY_F5) {
+            self.take_screenshot = true;
+        }
+
+        if self.rl.is_key_pressed(KEY_F4) {
+            self.toggle_recording();
+        }
+
+        let rl = &mut self.rl;
+
+        let mut hotkeys = Hotkeys {
+            toggle_fullscreen: rl.is_key_pressed(KEY_F11),
+            toggle_stats: rl.is_key_pressed(KEY_F10),
+            write_frame_stats: rl.is_key_pressed(KEY_F8),
+            toggle_trace: rl.is_key_pressed(KEY_F7),
+            copy_seed: false,
+        };
+
+        // Letter keys type characters instead of doing what they usually
+        // do, when the game wants text.
+        let typing = game::wants_text_input(state);
+        if typing {
+            loop {
+                // SAFETY: raylib has been initialized.
+                let codepoint = unsafe { GetCharPressed() };
+                if codepoint == 0 {
+                    break
+                }
+                if let Some(c) = char::from_u32(codepoint as u32) {
+                    game::type_char(state, c);
+                }
+            }
+
+            let control_down = rl.is_key_down(KEY_LEFT_CONTROL)
+                || rl.is_key_down(KEY_RIGHT_CONTROL);
+            if control_down && rl.is_key_pressed(KEY_V) {
+                if let Ok(text) = rl.get_clipboard_text() {
+                    for c in text.chars() {
+                        game::type_char(state, c);
+                    }
+                }
+            }
+        } else {
+            hotkeys.copy_seed = rl.is_key_pressed(KEY_F6);
+        }
+
+        let mut input_flags = 0;
+
+        if rl.is_key_pressed(KEY_BACKSPACE) {
+            input_flags |= game::INPUT_BACKSPACE_PRESSED;
+        }
+
+        if !typing && rl.is_key_pressed(KEY_R) {
+            input_flags |= game::INPUT_ROTATE_PRESSED;
+        }
+
+        if !typing && rl.is_key_pressed(KEY_E) {
+            input_flags |= game::INPUT_PLACE_START_PRESSED;
+        }
+
+        if rl.is_key_pressed(KEY_ESCAPE) || (!typing && rl.is_key_pressed(KEY_P)) {
+            input_flags |= game::INPUT_MENU_PRESSED;
+        }
+
+        if rl.is_key_pressed(KEY_F9) {
+            input_flags |= game::INPUT_INSPECTOR_PRESSED;
+        }
+
+        if rl.is_key_pressed(KEY_EQUAL) || rl.is_key_pressed(KEY_KP_ADD) {
+            input_flags |= game::INPUT_ZOOM_IN_PRESSED;
+        }
+
+        if rl.is_key_pressed(KEY_MINUS) || rl.is_key_pressed(KEY_KP_SUBTRACT) {
+            input_flags |= game::INPUT_ZOOM_OUT_PRESSED;
+        }
+
+        if !typing && rl.is_key_down(KEY_I) {
+            input_flags |= game::INPUT_PAN_UP_DOWN;
+        }
+
+        if !typing && rl.is_key_down(KEY_K) {
+            input_flags |= game::INPUT_PAN_DOWN_DOWN;
+        }
+
+        if !typing && rl.is_key_down(KEY_J) {
+            input_flags |= game::INPUT_PAN_LEFT_DOWN;
+        }
+
+        if !typing && rl.is_key_down(KEY_L) {
+            input_flags |= game::INPUT_PAN_RIGHT_DOWN;
+        }
+
+        Input {
+            device_flags: [
+                if typing { 0 } else { move_key_flags(rl, &WASD_KEYS) },
+                move_key_flags(rl, &ARROW_KEYS),
+                gamepad_flags(rl, 0),
+                gamepad_flags(rl, 1),
+            ],
+            shared_flags: input_flags,
+            hotkeys,
+        }
+    }
+
+    fn draw_wh(&mut self) -> game::DrawWH {
+        self.screen_wh = screen_wh(&self.rl);
+
+        let render_target = RenderTarget::fit_to_screen(
+            &mut self.rl,
+            &self.thread,
+            self.screen_wh,
+            &mut self.max_texture_size,
+            self.render_target.take(),
+        );
+
+        self.game_wh = render_target.game_wh(self.screen_wh);
+        self.render_target = Some(render_target);
+
+        self.game_wh
+    }
+
+    fn apply_settings(&mut self, settings: &game::Settings) {
+        if settings.fullscreen != self.rl.is_window_fullscreen() {
+            self.rl.toggle_fullscreen();
+        }
+
+        if self.target_fps != Some(settings.target_fps) {
+            self.rl.set_target_fps(settings.target_fps);
+            self.target_fps = Some(settings.target_fps);
+        }
+    }
+
+    fn set_clipboard_text(&mut self, text: &str) -> Result<(), String> {
+        self.rl.set_clipboard_text(text)
+            .map_err(|err| err.to_string())
+    }
+
+    fn present(&mut self, frame: Frame) -> Instant {
+        let Self {
+            rl,
+            thread,
+            font,
+            spritesheet,
+            grid_shader,
+            render_target,
+            screen_wh,
+            game_wh,
+            capture_frame_skip,
+            take_screenshot,
+            recording,
+            ..
+        } = self;
+
+        let render_target = render_target.as_mut()
+            .expect("render_target should only be None inside draw_wh");
+
+        let screen_render_rect = Rectangle {
+            x: 0.,
+            y: 0.,
+            width: screen_wh.w,
+            height: screen_wh.h,
+        };
+
+        let sizes = frame.sizes;
+
+        let mut d = rl.begin_drawing(thread);
+
+        d.clear_background(BACKGROUND);
+
+        {
+            let mut texture_d = d.begin_texture_mode(
+                thread,
+                &mut render_target.texture
+            );
+
+            let mut shader_d = texture_d.begin_shader_mode(
+                grid_shader
+            );
+
+            shader_d.clear_background(BACKGROUND);
+
+            // the -1 and +2 business makes the border lie just outside the actual
+            // play area
+            shader_d.draw_rectangle_lines(
+                sizes.play_xywh.x as i32 - 1,
+                sizes.play_xywh.y as i32 - 1,
+                sizes.play_xywh.w as i32 + 2,
+                sizes.play_xywh.h as i32 + 2,
+                OUTLINE
+            );
+
+            let tile_base_source_rect = Rectangle {
+                x: 0.,
+                y: 0.,
+                width: SPRITE_PIXELS_PER_TILE_SIDE,
+                height: SPRITE_PIXELS_PER_TILE_SIDE,
+            };
+
+            let tile_base_render_rect = Rectangle {
+                x: 0.,
+                y: 0.,
+                width: sizes.tile_side_length,
+                height: sizes.tile_side_length,
+            };
+
+            // I don't know why the texture lookup seems to be offset by these
+            // amounts, but it seems to be.
+            const X_SOURCE_FUDGE: f32 = -2.;
+            const Y_SOURCE_FUDGE: f32 = -1.;
+
+            for cmd in frame.commands.iter() {
+                use game::draw::Command::*;
+                match cmd {
+                    Sprite(s) => {
+                        let spec = source_spec(s.sprite);
+
+                        let render_w = tile_base_render_rect.width * s.scale;
+                        let render_h = tile_base_render_rect.height * s.scale;
+
+                        let origin = Vector2 {
+                            x: (render_w / 2.).round(),
+                            y: (render_h / 2.).round(),
+                        };
+
+                        // We position the sprite by the center of its tile, so
+                        // that scaling and rotation leave it centered there.
+                        let render_rect = Rectangle {
+                            x: s.xy.x + (tile_base_render_rect.width / 2.).round(),
+                            y: s.xy.y + (tile_base_render_rect.height / 2.).round(),
+                            width: render_w,
+                            height: render_h,
+                        };
+
+                        // Raylib flips the sprite if the source width or
+                        // height is negative.
+                        let source_rect = Rectangle {
+                            x: spec.x + X_SOURCE_FUDGE,
+                            y: spec.y + Y_SOURCE_FUDGE,
+                            width: if s.flip.is_horizontal() {
+                                -tile_base_source_rect.width
+                            } else {
+                                tile_base_source_rect.width
+                            },
+                            height: if s.flip.is_vertical() {
+                                -tile_base_source_rect.height
+                            } else {
+                                tile_base_source_rect.height
+                            },
+                        };
+
+                        shader_d.draw_texture_pro(
+                            &*spritesheet,
+                            source_rect,
+                            render_rect,
+                            origin,
+                            s.rotation,
+                            to_color(s.tint)
+                        );
+                    }
+                    Text(t) => {
+                        let colour = to_color(t.colour);
+
+                        // We draw each glyph separately, so that the game gets
+                        // to decide where each glyph goes.
+                        let mut position = Vector2 { x: t.xy.x, y: t.xy.y };
+                        let mut buffer = [0; 4];
+                        for c in t.text.chars() {
+                            shader_d.draw_text_ex(
+                                &*font,
+                                c.encode_utf8(&mut buffer),
+                                position,
+                                t.size,
+                                0., // spacing
+                                colour
+                            );
+
+                            position.x += t.advance;
+                        }
+                    }
+                    Rect(r) => {
+                        shader_d.draw_rectangle_rec(
+                            Rectangle {
+                                x: r.xy.x,
+                                y: r.xy.y,
+                                width: r.wh.w,
+                                height: r.wh.h,
+                            },
+                            to_color(r.colour)
+                        );
+                    }
+                }
+            }
+
+            if let Some(stats) = frame.stats {
+                use frame_stats::{SPAN_COUNT, SPAN_NAMES};
+
+                let history = stats.history;
+
+                let mut text = String::new();
+                let latest = history.latest().copied().unwrap_or_default();
+                for i in 0..SPAN_COUNT {
+                    text.push_str(&format!(
+                        "{: <6} {: >6.3} ms | {}\n",
+                        SPAN_NAMES[i],
+                        frame_stats::ms(latest[i]),
+                        history.summary(i),
+                    ));
+                }
+
+                for record in stats.records {
+                    text.push_str(&format!(
+                        "{: <width$}{} {: >6.3} ms\n",
+                        "",
+                        record.id.name(),
+                        record.end.saturating_sub(record.start) as f32 / 1_000_000.,
+                        width = usize::from(record.depth) * 2,
+                    ));
+                }
+                if stats.dropped_records > 0 {
+                    text.push_str(&format!(
+                        "({} more scopes not recorded)\n",
+                        stats.dropped_records,
+                    ));
+                }
+
+                shader_d.draw_text_rec(
+                    &*font,
+                    &text,
+                    Rectangle {
+                        x: 0.,
+                        y: 0.,
+                        width: sizes.draw_wh.w,
+                        height: sizes.draw_wh.h / 4.,
+                    },
+                    // Constant arrived at through trial and error.
+                    sizes.draw_wh.w * (1./96.),
+                    1.,
+                    true, // word_wrap
+                    TEXT
+                );
+
+                // A graph of the loop time of each frame in the history, with
+                // a line at the time available per frame at 60 FPS.
+                const GRAPH_H: i32 = 128;
+                const GRAPH_MAX_MS: f32 = 2. * 1000. / 60.;
+                const BUDGET_MS: f32 = 1000. / 60.;
+
+                let graph_bottom = sizes.draw_wh.h as i32;
+                let budget_y = graph_bottom
+                    - (BUDGET_MS / GRAPH_MAX_MS * GRAPH_H as f32) as i32;
+
+                for (x, frame) in history.iter().enumerate() {
+                    let frame_ms = frame_stats::ms(frame[0]);
+                    let h = (frame_ms / GRAPH_MAX_MS * GRAPH_H as f32)
+                        .min(GRAPH_H as f32) as i32;
+
+                    shader_d.draw_line(
+                        x as i32,
+                        graph_bottom,
+                        x as i32,
+                        graph_bottom - h,
+                        if frame_ms > BUDGET_MS { OVER_BUDGET } else { GRAPH }
+                    );
+                }
+
+                shader_d.draw_line(
+                    0,
+                    budget_y,
+                    frame_stats::HISTORY_LENGTH as i32,
+                    budget_y,
+                    OUTLINE
+                );
+            }
+        }
+
+        if core::mem::take(take_screenshot) {
+            let result = captures::screenshot_path()
+                .map_err(|err| err.to_string())
+                .and_then(|path| {
+                    export_png(&game_image(render_target, *game_wh), &path)?;
+                    Ok(path)
+                });
+
+            match result {
+                Ok(path) => println!("Saved screenshot to {}", path.display()),
+                Err(err) => eprintln!("Could not save screenshot: {}", err),
+            }
+        }
+
+        let mut recording_failed = false;
+        if let Some(recording) = recording.as_mut() {
+            if recording.frames_to_skip > 0 {
+                recording.frames_to_skip -= 1;
+            } else {
+                let path = captures::frame_path(&recording.dir, recording.frame_count);
+                match export_png(&game_image(render_target, *game_wh), &path) {
+                    Ok(()) => {
+                        recording.frame_count += 1;
+                        recording.frames_to_skip = *capture_frame_skip;
+                    },
+                    Err(err) => {
+                        eprintln!("Could not capture frame, so stopping recording: {}", err);
+                        recording_failed = true;
+                    },
+                }
+            }
+        }
+        if recording_failed {
+            *recording = None;
+        }
+
+        // If the render target was too small to cover the screen, this scales
+        // up what we drew.
+        let render_target_source_rect = Rectangle {
+            x: 0.,
+            y: (render_target.h as f32) - game_wh.h,
+            width: game_wh.w,
+            // y flip for openGL
+            height: -game_wh.h
+        };
+
+        d.draw_texture_pro(
+            &render_target.texture,
+            render_target_source_rect,
+            screen_render_rect,
+            Vector2::default(),
+            0.0,
+            NO_TINT
+        );
+
+        let drawn = Instant::now();
+
+        // Have Raylib perform the frame waiting.
+        drop(d);
+
+        drawn
+    }
+}
@@ -0,0 +1,20 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+use std::io;
+
+use crate::config_file;
+
+const FILE_NAME: &str = "settings.toml";
+
+pub fn load() -> game::Settings {
+    config_file::load_or_default(FILE_NAME, game::settings::parse)
+}
+
+pub fn save(settings: &game::Settings) -> io::Result<()> {
+    let mut text = String::new();
+    // Writing to a `String` never fails.
+    let _ = game::settings::write(&mut text, settings);
+
+    config_file::save_atomically(FILE_NAME, &text)
+}
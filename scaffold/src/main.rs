@@ -0,0 +1,629 @@
+#![deny(unused)]
+#![deny(bindings_with_variant_name)]
+
+//! Turns a copy of the template into a new project: renames the `rename-me`
+//! crate and its folder, sets the window title, and optionally the authors
+//! and the names on the licenses, then checks that cargo still understands
+//! the workspace.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const USAGE: &str = "\
+Usage: cargo run -p scaffold -- [<options>] <name>
+
+Renames the rename-me crate, and its folder, to <name>.
+
+Options:
+    --dry-run                   Show what would change, without changing it
+    --title <title>             The window title, which is also used for the
+                                settings folder (default <name>)
+    --author <author>           Set the authors in each Cargo.toml, like
+                                \"Name <email>\". Can be given more than once
+    --copyright-holder <name>   Put this name on the licenses
+    --year <year>               Put this year on the licenses (default this year)";
+
+const TEMPLATE_NAME: &str = "rename-me";
+
+/// Names of the other crates in the workspace, which a new name can't match.
+const TAKEN_NAMES: [&str; 4] = ["game", "game-fuzz", "checksum-diff", "scaffold"];
+
+const LICENSES: [&str; 2] = ["LICENSE-MIT", "LICENSE-MIT-0"];
+
+const COPYRIGHT_PREFIX: &str = "Copyright (c) ";
+
+struct Args {
+    name: String,
+    title: Option<String>,
+    authors: Vec<String>,
+    copyright_holder: Option<String>,
+    year: Option<i64>,
+    dry_run: bool,
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} needs a value after it", flag))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut name = None;
+    let mut title = None;
+    let mut authors = Vec::new();
+    let mut copyright_holder = None;
+    let mut year = None;
+    let mut dry_run = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => {
+                dry_run = true;
+            },
+            "--title" => {
+                title = Some(value(&mut args, &arg)?);
+            },
+            "--author" => {
+                authors.push(value(&mut args, &arg)?);
+            },
+            "--copyright-holder" => {
+                copyright_holder = Some(value(&mut args, &arg)?);
+            },
+            "--year" => {
+                let year_arg = value(&mut args, &arg)?;
+                year = Some(
+                    year_arg.parse()
+                        .map_err(|_| format!("--year needs a number after it, not {:?}", year_arg))?
+                );
+            },
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            },
+            _ if arg.starts_with('-') => return Err(format!("Unknown argument {:?}", arg)),
+            _ if name.is_some() => return Err(format!("Only one name is needed, not {:?} too", arg)),
+            _ => {
+                name = Some(arg);
+            },
+        }
+    }
+
+    Ok(Args {
+        name: name.ok_or_else(|| "A name is needed".to_string())?,
+        title,
+        authors,
+        copyright_holder,
+        year,
+        dry_run,
+    })
+}
+
+/// A subset of what cargo allows, which is also a fine folder name on every
+/// platform.
+fn check_name(name: &str) -> Result<(), String> {
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Err(format!("{:?} should start with a letter", name))
+    }
+
+    if let Some(c) = name.chars().find(|&c| !(c.is_ascii_alphanumeric() || c == '-' || c == '_')) {
+        return Err(format!("{:?} can't contain {:?}, only letters, numbers, - and _", name, c))
+    }
+
+    if name == TEMPLATE_NAME || TAKEN_NAMES.contains(&name) {
+        return Err(format!("{:?} is already taken", name))
+    }
+
+    Ok(())
+}
+
+/// The year in UTC, from a count of days since the Unix epoch. This is the
+/// `civil_from_days` algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn year_from_unix_days(days: i64) -> i64 {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (
+        day_of_era
+        - day_of_era / 1_460
+        + day_of_era / 36_524
+        - day_of_era / 146_096
+    ) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+
+    // The years in this algorithm start in March, so January and February
+    // are in the next calendar year.
+    let is_january_or_february = month_index >= 10;
+
+    year_of_era + era * 400 + i64::from(is_january_or_february)
+}
+
+fn this_year() -> i64 {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    year_from_unix_days((seconds / (24 * 60 * 60)) as i64)
+}
+
+/// Applies `edit_line` to each line, without their line endings, keeping
+/// whatever line endings the text had.
+fn edit_lines(text: &str, mut edit_line: impl FnMut(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        match edit_line(content) {
+            Some(new_content) => {
+                output.push_str(&new_content);
+                output.push_str(&line[content.len()..]);
+            },
+            None => output.push_str(line),
+        }
+    }
+
+    output
+}
+
+/// A change to one file. Edits only ever change lines, and never add or
+/// remove them, which keeps the diffs simple.
+struct FileEdit {
+    path: PathBuf,
+    old: String,
+    new: String,
+}
+
+struct Plan {
+    edits: Vec<FileEdit>,
+    old_folder: PathBuf,
+    new_folder: PathBuf,
+}
+
+/// Every file in the template except the ones under `skip_dirs`, which are
+/// not part of the new project, or are this program itself.
+fn template_files(dir: &Path, skip_dirs: &[&Path], files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            let skipped = skip_dirs.contains(&path.as_path())
+                || matches!(
+                    path.file_name().and_then(|n| n.to_str()),
+                    Some(".git" | "target")
+                );
+            if !skipped {
+                template_files(&path, skip_dirs, files)?;
+            }
+        } else if path.file_name().and_then(|n| n.to_str()) != Some("Cargo.lock") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn plan(root: &Path, args: &Args) -> Result<Plan, String> {
+    let old_folder = root.join(TEMPLATE_NAME);
+    let new_folder = root.join(&args.name);
+
+    if !old_folder.is_dir() {
+        return Err(format!(
+            "There is no {} folder, so this was probably already done.",
+            old_folder.display()
+        ))
+    }
+
+    if new_folder.exists() {
+        return Err(format!("{} already exists", new_folder.display()))
+    }
+
+    let title = args.title.as_deref().unwrap_or(&args.name);
+    let title_line = format!("pub const TITLE: &str = {:?};", title);
+    let old_title_line = format!("pub const TITLE: &str = {:?};", TEMPLATE_NAME);
+
+    let authors_line = (!args.authors.is_empty()).then(|| format!(
+        "authors = [{}]",
+        args.authors.iter()
+            .map(|author| format!("{:?}", author))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+
+    let year = args.year.unwrap_or_else(this_year);
+
+    let mut files = Vec::new();
+    template_files(root, &[&root.join("scaffold")], &mut files)
+        .map_err(|err| format!("Could not list the files in {}: {}", root.display(), err))?;
+    files.sort();
+
+    let mut edits = Vec::new();
+    for path in files {
+        // Skip anything that isn't text, like images.
+        let Ok(old) = fs::read_to_string(&path) else { continue };
+
+        let is_manifest = path.file_name().and_then(|n| n.to_str()) == Some("Cargo.toml");
+        let is_license = path.parent() == Some(root)
+            && LICENSES.iter().any(|&license| path.file_name().and_then(|n| n.to_str()) == Some(license));
+
+        let new = edit_lines(&old, |line| {
+            if line == old_title_line {
+                return Some(title_line.clone())
+            }
+
+            if is_manifest && line.starts_with("authors = ") {
+                if let Some(authors_line) = &authors_line {
+                    return Some(authors_line.clone())
+                }
+            }
+
+            if is_license {
+                if let Some(rest) = line.strip_prefix(COPYRIGHT_PREFIX) {
+                    // The rest is the year, then the holder.
+                    let holder = match &args.copyright_holder {
+                        Some(holder) => holder.as_str(),
+                        None => rest.split_once(' ')
+                            .map(|(_, holder)| holder)
+                            .unwrap_or_default(),
+                    };
+                    return Some(format!("{}{} {}", COPYRIGHT_PREFIX, year, holder))
+                }
+            }
+
+            line.contains(TEMPLATE_NAME)
+                .then(|| line.replace(TEMPLATE_NAME, &args.name))
+        });
+
+        if new != old {
+            edits.push(FileEdit { path, old, new });
+        }
+    }
+
+    Ok(Plan {
+        edits,
+        old_folder,
+        new_folder,
+    })
+}
+
+fn relative<'path>(root: &Path, path: &'path Path) -> std::path::Display<'path> {
+    path.strip_prefix(root).unwrap_or(path).display()
+}
+
+/// Prints the changes like a unified diff, without any context lines, since
+/// every change is one line replaced with another.
+fn print_diff(root: &Path, plan: &Plan) {
+    println!("rename from {}", relative(root, &plan.old_folder));
+    println!("rename to {}", relative(root, &plan.new_folder));
+
+    for edit in &plan.edits {
+        let new_path = match edit.path.strip_prefix(&plan.old_folder) {
+            Ok(rest) => plan.new_folder.join(rest),
+            Err(_) => edit.path.clone(),
+        };
+
+        println!("--- a/{}", relative(root, &edit.path));
+        println!("+++ b/{}", relative(root, &new_path));
+
+        for (index, (old, new)) in edit.old.lines().zip(edit.new.lines()).enumerate() {
+            if old != new {
+                println!("@@ -{0},1 +{0},1 @@", index + 1);
+                println!("-{}", old);
+                println!("+{}", new);
+            }
+        }
+    }
+}
+
+fn apply(plan: &Plan) -> Result<(), String> {
+    for edit in &plan.edits {
+        fs::write(&edit.path, &edit.new)
+            .map_err(|err| format!("Could not write {}: {}", edit.path.display(), err))?;
+    }
+
+    fs::rename(&plan.old_folder, &plan.new_folder)
+        .map_err(|err| format!(
+            "Could not rename {} to {}: {}",
+            plan.old_folder.display(),
+            plan.new_folder.display(),
+            err
+        ))
+}
+
+/// Puts things back how they were, as far as possible, after `apply` failed
+/// partway through, or the result didn't check out.
+fn undo(plan: &Plan) {
+    if plan.new_folder.is_dir() && !plan.old_folder.exists() {
+        if let Err(err) = fs::rename(&plan.new_folder, &plan.old_folder) {
+            eprintln!(
+                "Could not rename {} back to {}: {}",
+                plan.new_folder.display(),
+                plan.old_folder.display(),
+                err
+            );
+        }
+    }
+
+    for edit in &plan.edits {
+        if let Err(err) = fs::write(&edit.path, &edit.old) {
+            eprintln!("Could not restore {}: {}", edit.path.display(), err);
+        }
+    }
+}
+
+/// Checks that cargo can still read the workspace, and finds the renamed crate
+/// in it.
+fn check_metadata(root: &Path, name: &str) -> Result<(), String> {
+    // Cargo tells programs it runs where it is.
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+
+    let output = Command::new(cargo)
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .current_dir(root)
+        .output()
+        .map_err(|err| format!("Could not run cargo metadata: {}", err))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+
+    // The output is JSON without any extra whitespace.
+    let package_name = format!("\"name\":{:?}", name);
+    if !String::from_utf8_lossy(&output.stdout).contains(&package_name) {
+        return Err(format!("cargo metadata did not list a package named {:?}", name))
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = check_name(&args.name) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+
+    // The workspace root, wherever this is run from.
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("scaffold should be in the workspace folder");
+
+    let plan = match plan(root, &args) {
+        Ok(plan) => plan,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if args.dry_run {
+        print_diff(root, &plan);
+        return
+    }
+
+    if let Err(err) = apply(&plan).and_then(|()| check_metadata(root, &args.name)) {
+        eprintln!("{}\nUndoing the changes.", err);
+        undo(&plan);
+        std::process::exit(1);
+    }
+
+    println!(
+        "Renamed {} to {}, and changed {} files.",
+        TEMPLATE_NAME,
+        args.name,
+        plan.edits.len()
+    );
+    println!("The scaffold folder can now be deleted, along with its entry in Cargo.toml.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_that_would_not_work_are_rejected() {
+        assert!(check_name("eye-maze").is_ok());
+        assert!(check_name("eye_maze2").is_ok());
+
+        assert!(check_name("").is_err());
+        assert!(check_name("2eyes").is_err());
+        assert!(check_name("eye maze").is_err());
+        assert!(check_name("eye/maze").is_err());
+        assert!(check_name("game").is_err());
+        assert!(check_name(TEMPLATE_NAME).is_err());
+    }
+
+    #[test]
+    fn years_change_on_new_years_day() {
+        assert_eq!(year_from_unix_days(0), 1970);
+        assert_eq!(year_from_unix_days(-1), 1969);
+        // 2000-02-29 and 2000-12-31
+        assert_eq!(year_from_unix_days(11_016), 2000);
+        assert_eq!(year_from_unix_days(11_322), 2000);
+        // 2024-01-01
+        assert_eq!(year_from_unix_days(19_723), 2024);
+        assert_eq!(year_from_unix_days(19_722), 2023);
+    }
+
+    #[test]
+    fn edited_lines_keep_their_line_endings() {
+        let edited = edit_lines("a rename-me\r\nb\nrename-me", |line| {
+            line.contains(TEMPLATE_NAME).then(|| line.replace(TEMPLATE_NAME, "new"))
+        });
+
+        assert_eq!(edited, "a new\r\nb\nnew");
+    }
+
+    /// A small copy of the template's layout, in a fresh temporary folder,
+    /// which is deleted on drop.
+    struct Template {
+        root: PathBuf,
+    }
+
+    impl Template {
+        fn new(test_name: &str, workspace_members: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "scaffold-{}-{}",
+                test_name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&root);
+
+            let workspace = format!("[workspace]\nmembers = [{}]\n", workspace_members);
+            let files: [(&str, &[u8]); 8] = [
+                ("Cargo.toml", workspace.as_bytes()),
+                (
+                    "rename-me/Cargo.toml",
+                    b"[package]\nname = \"rename-me\"\nversion = \"0.1.0\"\n\
+                      authors = [\"Old Author <old@example.com>\"]\nedition = \"2021\"\n",
+                ),
+                ("rename-me/src/main.rs", b"fn main() {}\n"),
+                ("game/src/game.rs", b"/// Shown on the title screen.\npub const TITLE: &str = \"rename-me\";\n"),
+                ("LICENSE-MIT", b"MIT License\n\nCopyright (c) 2021 Old Holder\n"),
+                ("LICENSE-MIT-0", b"MIT No Attribution\n\nCopyright (c) 2021 Old Holder\n"),
+                ("scaffold/src/main.rs", b"const TEMPLATE_NAME: &str = \"rename-me\";\n"),
+                // Not text, so it should be skipped, even with the name in it.
+                ("rename-me/assets/sprites.png", b"\x89PNG rename-me \xFF"),
+            ];
+
+            for (path, contents) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+
+            Self { root }
+        }
+
+        /// Every file, relative to the root, with its contents, sorted.
+        fn snapshot(&self) -> Vec<(PathBuf, Vec<u8>)> {
+            let mut files = Vec::new();
+            template_files(&self.root, &[], &mut files).unwrap();
+            files.sort();
+
+            files.into_iter()
+                .map(|path| {
+                    let contents = fs::read(&path).unwrap();
+                    (path.strip_prefix(&self.root).unwrap().to_owned(), contents)
+                })
+                .collect()
+        }
+
+        fn read(&self, path: &str) -> String {
+            fs::read_to_string(self.root.join(path)).unwrap()
+        }
+    }
+
+    impl Drop for Template {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn args(name: &str) -> Args {
+        Args {
+            name: name.to_string(),
+            title: Some("Eye Maze".to_string()),
+            authors: vec!["New Author <new@example.com>".to_string(), "Someone Else".to_string()],
+            copyright_holder: Some("New Holder".to_string()),
+            year: Some(2030),
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn renaming_changes_the_folder_title_authors_and_licenses() {
+        let template = Template::new("rename", "\"rename-me\"");
+
+        let plan = plan(&template.root, &args("eye-maze")).unwrap();
+        apply(&plan).unwrap();
+        check_metadata(&template.root, "eye-maze").unwrap();
+
+        assert!(!template.root.join("rename-me").exists());
+        assert_eq!(template.read("Cargo.toml"), "[workspace]\nmembers = [\"eye-maze\"]\n");
+        assert_eq!(
+            template.read("eye-maze/Cargo.toml"),
+            "[package]\nname = \"eye-maze\"\nversion = \"0.1.0\"\n\
+             authors = [\"New Author <new@example.com>\", \"Someone Else\"]\nedition = \"2021\"\n"
+        );
+        assert_eq!(
+            template.read("game/src/game.rs"),
+            "/// Shown on the title screen.\npub const TITLE: &str = \"Eye Maze\";\n"
+        );
+        assert_eq!(template.read("LICENSE-MIT"), "MIT License\n\nCopyright (c) 2030 New Holder\n");
+        assert_eq!(template.read("LICENSE-MIT-0"), "MIT No Attribution\n\nCopyright (c) 2030 New Holder\n");
+
+        // The scaffold itself, and files that aren't text, are left alone.
+        assert_eq!(
+            template.read("scaffold/src/main.rs"),
+            "const TEMPLATE_NAME: &str = \"rename-me\";\n"
+        );
+        assert_eq!(
+            fs::read(template.root.join("eye-maze/assets/sprites.png")).unwrap(),
+            b"\x89PNG rename-me \xFF"
+        );
+    }
+
+    #[test]
+    fn licenses_keep_their_holder_unless_told_otherwise() {
+        let template = Template::new("holder", "\"rename-me\"");
+
+        let plan = plan(&template.root, &Args {
+            copyright_holder: None,
+            authors: Vec::new(),
+            ..args("eye-maze")
+        }).unwrap();
+        apply(&plan).unwrap();
+
+        assert_eq!(template.read("LICENSE-MIT"), "MIT License\n\nCopyright (c) 2030 Old Holder\n");
+        assert!(template.read("eye-maze/Cargo.toml").contains("authors = [\"Old Author <old@example.com>\"]"));
+    }
+
+    #[test]
+    fn planning_alone_changes_nothing() {
+        let template = Template::new("dry-run", "\"rename-me\"");
+        let before = template.snapshot();
+
+        // What `--dry-run` does.
+        let plan = plan(&template.root, &args("eye-maze")).unwrap();
+        print_diff(&template.root, &plan);
+
+        assert!(!plan.edits.is_empty());
+        assert_eq!(template.snapshot(), before);
+    }
+
+    #[test]
+    fn failed_checks_are_undone() {
+        // Cargo can't read a workspace with a missing member.
+        let template = Template::new("undo", "\"rename-me\", \"missing\"");
+        let before = template.snapshot();
+
+        let plan = plan(&template.root, &args("eye-maze")).unwrap();
+        apply(&plan).unwrap();
+        assert!(check_metadata(&template.root, "eye-maze").is_err());
+
+        undo(&plan);
+        assert!(!template.root.join("eye-maze").exists());
+        assert_eq!(template.snapshot(), before);
+    }
+
+    #[test]
+    fn renaming_twice_is_refused() {
+        let template = Template::new("twice", "\"rename-me\"");
+
+        apply(&plan(&template.root, &args("eye-maze")).unwrap()).unwrap();
+
+        assert!(plan(&template.root, &args("other-name")).is_err());
+    }
+}